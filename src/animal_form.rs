@@ -1,33 +1,403 @@
 use anyhow::Result;
-use chrono::DateTime;
 use enum_ordinalize::Ordinalize;
 use fltk::{
-    input::Input,
+    browser::HoldBrowser,
+    button::Button,
+    dialog, draw,
+    enums::{CallbackTrigger, Color},
+    frame::Frame,
+    group::Flex,
+    input::{FloatInput, Input},
     menu::Choice,
-    prelude::{InputExt, WidgetExt,MenuExt},
+    misc::InputChoice,
+    prelude::{BrowserExt, GroupExt, InputExt, MenuExt, WidgetBase, WidgetExt},
     widget::Widget,
 };
 use simple_table::joe_table::JoeTable;
+use std::sync::{Arc, Mutex};
 
-use crate::{business_obj::*, event_form::EventTableModel, flok::*, form::*};
+use crate::{
+    breeding, business_obj::*, date_input::DateInput, event_form::EventTableModel, flok::*,
+    form::*, growth, treatment, validate::check_edited_animal,
+};
 
-#[derive(Clone, Default)]
-pub struct DateInput {
-    pub input: Input,
+/// An autocompleting input for a sire or dam, warning about ids that are not in the flock.
+#[derive(Clone)]
+pub struct ParentPicker {
+    pub group: Flex,
+    pub input: InputChoice,
+    pub warning: Frame,
+    pub create: Button,
+    pub sex: Sex,
+    pub flok: Arc<Mutex<Flok>>,
+    /// Offered first while no parent is picked, like the only ram the dam was with
+    pub likely: Option<Id>,
 }
 
-impl DateInput {
-    pub fn get_date(&self) -> Option<chrono::DateTime<chrono::Local>> {
-        dateparser::parse(&self.input.value())
-            .map(DateTime::from)
-            .ok()
+impl ParentPicker {
+    pub fn create(sex: Sex, flok: Arc<Mutex<Flok>>) -> Self {
+        let mut group = Flex::default().row();
+        let input = InputChoice::default();
+        let mut warning = Frame::default();
+        warning.set_label_color(Color::Red);
+        let mut create = Button::default().with_label("Create placeholder");
+        group.fixed(&warning, 160);
+        group.fixed(&create, 130);
+        group.end();
+        create.hide();
+
+        Self {
+            group,
+            input,
+            warning,
+            create,
+            sex,
+            flok,
+            likely: None,
+        }
+    }
+
+    pub fn value(&self) -> Option<Id> {
+        self.input
+            .value()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Offer the animals that could be this parent of an animal born on `born`, `likely` first.
+    pub fn set_value(&mut self, value: Option<Id>, born: Option<Date>, likely: Option<Id>) {
+        let mut candidates = self.flok.lock().unwrap().parent_candidates(self.sex, born);
+        if let Some(likely) = &likely {
+            candidates.retain(|c| c != likely);
+            candidates.insert(0, likely.clone());
+        }
+        self.likely = likely;
+        self.input.set_value(&value.unwrap_or_default());
+        self.suggest(&candidates);
+        self.check(born);
+
+        let mut p = self.clone();
+        self.create
+            .set_callback(move |_| p.create_placeholder(born));
+
+        let mut p = self.clone();
+        self.input.set_trigger(CallbackTrigger::Changed);
+        self.input.set_callback(move |_| {
+            p.suggest(&candidates);
+            p.check(born);
+        });
     }
+
+    fn suggest(&mut self, candidates: &[Id]) {
+        let typed = self.value().unwrap_or_default().to_lowercase();
+        self.input.clear();
+        candidates
+            .iter()
+            .filter(|id| id.to_lowercase().contains(&typed))
+            // '/' would create a submenu
+            .for_each(|id| self.input.add(&id.replace('/', "\\/")));
+    }
+
+    /// Show a warning for unknown ids or implausible parents, or the likely parent to pick.
+    fn check(&mut self, born: Option<Date>) {
+        self.warning.set_label_color(Color::Red);
+        let (problem, unknown) = match self.value() {
+            None => match &self.likely {
+                Some(likely) => {
+                    self.warning.set_label_color(Color::DarkGreen);
+                    (format!("Likely {likely}, pick to confirm"), false)
+                }
+                None => (String::new(), false),
+            },
+            Some(id) => match self.flok.lock().unwrap().find(id.clone()) {
+                None => (format!("Unknown animal {id}"), true),
+                Some(a) if a.sex != self.sex => (format!("{id} is {}", a.sex.name()), false),
+                Some(a) if a.born.zip(born).is_some_and(|(p, c)| p >= c) => {
+                    (format!("{id} was born after this animal"), false)
+                }
+                Some(_) => (String::new(), false),
+            },
+        };
+        self.warning.set_label(&problem);
+        if unknown {
+            self.create.show();
+        } else {
+            self.create.hide();
+        }
+        self.group.redraw();
+    }
+
+    fn create_placeholder(&mut self, born: Option<Date>) {
+        if let Some(id) = self.value() {
+            self.flok.lock().unwrap().animals.push(Animal {
+                id: vec![id],
+                sex: self.sex,
+                description: "placeholder parent".to_string(),
+                ..Default::default()
+            });
+        }
+        self.check(born);
+    }
+}
+
+/// The weighings of an animal, with the daily gain since the one before.
+#[derive(Clone)]
+pub struct WeightEditor {
+    pub group: Flex,
+    pub list: HoldBrowser,
+    pub date: DateInput,
+    pub value: FloatInput,
+    pub unit: Choice,
+    pub weights: Arc<Mutex<Vec<Weight>>>,
+    /// The unit weights and gains are listed in.
+    pub show_unit: WeightUnit,
 }
 
-impl From<DateInput> for Input {
-    fn from(val: DateInput) -> Self {
-        val.input
+impl WeightEditor {
+    pub fn create(show_unit: WeightUnit) -> Self {
+        let mut group = Flex::default().column();
+        let list = HoldBrowser::default();
+        let mut row = Flex::default().row();
+        let mut date = DateInput::default();
+        date.set_date(Some(Date::today()));
+        let value = FloatInput::default();
+        let mut unit = Choice::default();
+        for u in WeightUnit::VARIANTS {
+            unit.add_choice(u.name());
+        }
+        unit.set_value(show_unit.ordinal() as i32);
+        let mut add = Button::default().with_label("Add");
+        let mut remove = Button::default().with_label("Remove");
+        let mut chart = Button::default().with_label("Growth chart");
+        row.fixed(&unit, 50);
+        row.fixed(&add, 50);
+        row.fixed(&remove, 70);
+        row.fixed(&chart, 100);
+        row.end();
+        group.fixed(&row, 30);
+        group.end();
+
+        let editor = Self {
+            group,
+            list,
+            date,
+            value,
+            unit,
+            weights: Default::default(),
+            show_unit,
+        };
+        let mut e = editor.clone();
+        add.set_callback(move |_| e.add());
+        let mut e = editor.clone();
+        remove.set_callback(move |_| e.remove());
+        let e = editor.clone();
+        chart.set_callback(move |_| {
+            let animal = Animal {
+                weights: e.weights.lock().unwrap().clone(),
+                ..Default::default()
+            };
+            if let Err(err) = growth::report_growth(&animal, e.show_unit) {
+                dialog::message_default(&err.to_string());
+            }
+        });
+        editor
+    }
+
+    pub fn set_value(&mut self, weights: Vec<Weight>) {
+        *self.weights.lock().unwrap() = weights;
+        self.refresh();
+    }
+
+    fn add(&mut self) {
+        let (Some(date), Ok(value)) = (self.date.get_date(), self.value.value().parse::<f64>())
+        else {
+            dialog::message_default("Enter a date and a weight");
+            return;
+        };
+        let unit = WeightUnit::from_ordinal(self.unit.value() as i8).unwrap_or_default();
+        let mut weights = self.weights.lock().unwrap();
+        weights.push(Weight { date, value, unit });
+        weights.sort_by_key(|w| w.date);
+        drop(weights);
+        self.value.set_value("");
+        self.refresh();
+    }
+
+    fn remove(&mut self) {
+        let line = self.list.value();
+        if line > 0 {
+            self.weights.lock().unwrap().remove(line as usize - 1);
+            self.refresh();
+        }
     }
+
+    fn refresh(&mut self) {
+        let weights = self.weights.lock().unwrap().clone();
+        let unit = self.show_unit;
+        let gains = growth::gains(
+            &Animal {
+                weights: weights.clone(),
+                ..Default::default()
+            },
+            unit,
+        );
+        self.list.clear();
+        for w in &weights {
+            let gain = gains
+                .iter()
+                .find(|g| g.1 == w.date)
+                .map(|g| format!("   {:+.2} {}/day", g.2, unit.name()))
+                .unwrap_or_default();
+            self.list
+                .add(&format!("{}   {} {}{gain}", w.date, w.value, w.unit.name()));
+        }
+    }
+}
+
+/// FAMACHA and BCS scores of an animal, with a sparkline of each.
+#[derive(Clone)]
+pub struct ScoreEditor {
+    pub group: Flex,
+    pub list: HoldBrowser,
+    pub sparkline: Frame,
+    pub date: DateInput,
+    pub kind: Choice,
+    pub value: FloatInput,
+    pub scores: Arc<Mutex<Vec<Score>>>,
+}
+
+/// Colour a score kind is drawn in.
+fn score_color(kind: ScoreKind) -> Color {
+    match kind {
+        ScoreKind::Famacha => Color::Red,
+        ScoreKind::Bcs => Color::Blue,
+    }
+}
+
+impl ScoreEditor {
+    pub fn create() -> Self {
+        let mut group = Flex::default().row();
+        let list = HoldBrowser::default();
+        let mut right = Flex::default().column();
+        let mut sparkline = Frame::default();
+        sparkline.set_tooltip("FAMACHA in red, BCS in blue, 1 at the bottom to 5 at the top");
+        let mut row = Flex::default().row();
+        let mut date = DateInput::default();
+        date.set_date(Some(Date::today()));
+        let mut kind = Choice::default();
+        for k in ScoreKind::VARIANTS {
+            kind.add_choice(k.name());
+        }
+        kind.set_value(0);
+        let value = FloatInput::default();
+        let mut add = Button::default().with_label("Add");
+        let mut remove = Button::default().with_label("Remove");
+        row.fixed(&kind, 90);
+        row.fixed(&value, 40);
+        row.fixed(&add, 50);
+        row.fixed(&remove, 70);
+        row.end();
+        right.fixed(&row, 30);
+        right.end();
+        group.fixed(&list, 180);
+        group.end();
+
+        let editor = Self {
+            group,
+            list,
+            sparkline,
+            date,
+            kind,
+            value,
+            scores: Default::default(),
+        };
+        let scores = editor.scores.clone();
+        editor.sparkline.clone().draw(move |f| {
+            draw_sparkline(f, &scores.lock().unwrap());
+        });
+        let mut e = editor.clone();
+        add.set_callback(move |_| e.add());
+        let mut e = editor.clone();
+        remove.set_callback(move |_| e.remove());
+        editor
+    }
+
+    pub fn set_value(&mut self, scores: Vec<Score>) {
+        *self.scores.lock().unwrap() = scores;
+        self.refresh();
+    }
+
+    fn add(&mut self) {
+        let (Some(date), Ok(value)) = (self.date.get_date(), self.value.value().parse::<f64>())
+        else {
+            dialog::message_default("Enter a date and a score");
+            return;
+        };
+        let kind = ScoreKind::from_ordinal(self.kind.value() as i8).unwrap_or_default();
+        let score = Score { date, kind, value };
+        if let Some(e) = crate::validate::check_score(&score).first() {
+            dialog::message_default(&e.message);
+            return;
+        }
+        let mut scores = self.scores.lock().unwrap();
+        scores.push(score);
+        scores.sort_by_key(|s| s.date);
+        drop(scores);
+        self.value.set_value("");
+        self.refresh();
+    }
+
+    fn remove(&mut self) {
+        let line = self.list.value();
+        if line > 0 {
+            self.scores.lock().unwrap().remove(line as usize - 1);
+            self.refresh();
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.list.clear();
+        for s in self.scores.lock().unwrap().iter() {
+            self.list
+                .add(&format!("{}   {} {}", s.date, s.kind.name(), s.value));
+        }
+        self.sparkline.redraw();
+    }
+}
+
+/// A line per score kind across `frame`, oldest on the left.
+fn draw_sparkline(frame: &Frame, scores: &[Score]) {
+    let (x, y, w, h) = (frame.x() + 4, frame.y() + 4, frame.w() - 8, frame.h() - 8);
+    draw::draw_rect_fill(frame.x(), frame.y(), frame.w(), frame.h(), Color::White);
+    let (Some(first), Some(last)) = (
+        scores.iter().map(|s| s.date).min(),
+        scores.iter().map(|s| s.date).max(),
+    ) else {
+        return;
+    };
+    let days = (last.day - first.day).num_days().max(1) as f64;
+    let point = |s: &Score| {
+        (
+            x + ((s.date.day - first.day).num_days() as f64 / days * w as f64) as i32,
+            y + h - ((s.value - 1.0) / 4.0 * h as f64) as i32,
+        )
+    };
+    for kind in ScoreKind::VARIANTS {
+        draw::set_draw_color(score_color(*kind));
+        draw::set_line_style(draw::LineStyle::Solid, 2);
+        let points: Vec<(i32, i32)> = scores
+            .iter()
+            .filter(|s| s.kind == *kind)
+            .map(point)
+            .collect();
+        for pair in points.windows(2) {
+            draw::draw_line(pair[0].0, pair[0].1, pair[1].0, pair[1].1);
+        }
+        for (px, py) in points {
+            draw::draw_pie(px - 2, py - 2, 5, 5, 0.0, 360.0);
+        }
+    }
+    draw::set_line_style(draw::LineStyle::Solid, 0);
 }
 
 pub struct AnimalForm<A: 'static + BusinessObject<Type = Animal>> {
@@ -36,9 +406,16 @@ pub struct AnimalForm<A: 'static + BusinessObject<Type = Animal>> {
     pub born: DateInput,
     pub description: Input,
     pub animal: A,
-    pub dam: Input,
-    pub sire: Input,
+    pub dam: ParentPicker,
+    pub sire: ParentPicker,
     pub events: JoeTable<EventTableModel<A>>,
+    pub weights: WeightEditor,
+    pub scores: ScoreEditor,
+    /// When the animal is clear of its treatments
+    pub withdrawal: Frame,
+    pub index: Option<usize>,
+    /// The events when the form was filled, the event editor changes them in place
+    pub recorded: Vec<Event>,
 }
 
 impl<A: BusinessObject<Type = Animal>> Editor<A> for AnimalForm<A> {
@@ -46,50 +423,95 @@ impl<A: BusinessObject<Type = Animal>> Editor<A> for AnimalForm<A> {
         self.identity.set_value(&a.exec(|a| a.id.join(", ")));
         self.sex.set_value(a.exec(|a| a.sex.ordinal() as i32));
 
-        self.born
-            .input
-            .set_value(&a.exec(move |a| a.born.map(|d| d.to_string()).unwrap_or("".to_string())));
+        self.born.set_date(a.exec(|a| a.born));
         self.description
             .set_value(&a.exec(|a| a.description.clone()));
+        let animal = a.exec(|a| a.clone());
+        // the only ram the dam was with, for the user to confirm
+        let likely = match animal.sire {
+            None => breeding::infer_sire(&self.sire.flok.lock().unwrap(), &animal),
+            Some(_) => None,
+        };
         self.sire
-            .set_value(&a.exec(|a| a.sire.clone().unwrap_or("".to_string())));
-        self.dam
-            .set_value(&a.exec(|a| a.dam.clone().unwrap_or("".to_string())));
+            .set_value(animal.sire.clone(), animal.born, likely);
+        self.dam.set_value(animal.dam.clone(), animal.born, None);
         self.events.model.lock().unwrap().animal = a.clone();
+        self.recorded = animal.events.clone();
+        self.weights.set_value(a.exec(|a| a.weights.clone()));
+        self.scores.set_value(a.exec(|a| a.scores.clone()));
+        let animal = a.exec(|a| a.clone());
+        let flok = self.sire.flok.lock().unwrap();
+        let clear = |date: Option<Date>| match date {
+            Some(d) if d.day > Date::today().day => format!("clear on {d}"),
+            Some(_) => "clear".to_string(),
+            None => "no withdrawal".to_string(),
+        };
+        self.withdrawal.set_label(&format!(
+            "Meat {}, milk {}",
+            clear(treatment::withdrawal_clear(&flok, &animal)),
+            clear(treatment::milk_clear(&flok, &animal))
+        ));
     }
 
-    fn commit(&mut self) {
-        let e = self.events.model.lock().unwrap().animal.exec(|a|a.events.clone());
-        self.animal.exec(|animal| {
-            animal.id = self
-                .identity
-                .value()
-                .split(',')
-                .map(|s| s.trim().to_owned())
-                .collect();
-            animal.born = self.born.get_date();
-            animal.sire = if self.sire.value().is_empty() {
-                None
-            } else {
-                Some(self.sire.value())
-            };
-            animal.dam = if self.dam.value().is_empty() {
-                None
-            } else {
-                Some(self.dam.value())
-            };
-            animal.description = self.description.value();
-            animal.events = e.clone();
-            animal.sex = Sex::from_ordinal(self.sex.value() as i8).unwrap_or_default();
+    fn commit(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        let mut animal = self.animal.exec(|a| a.clone());
+        animal.id = self
+            .identity
+            .value()
+            .split(',')
+            .map(|s| s.trim().to_owned())
+            .collect();
+        animal.born = self.born.parse("Born").unwrap_or_else(|e| {
+            errors.push(e);
+            None
         });
+        animal.sire = self.sire.value();
+        animal.dam = self.dam.value();
+        animal.description = self.description.value();
+        animal.sex = Sex::from_ordinal(self.sex.value() as i8).unwrap_or_default();
+        animal.weights = self.weights.weights.lock().unwrap().clone();
+        animal.scores = self.scores.scores.lock().unwrap().clone();
+
+        errors.extend(check_edited_animal(
+            &self.sire.flok.lock().unwrap(),
+            self.index,
+            &animal,
+            &self.recorded,
+        ));
+        highlight(&mut self.identity, "Identity", &errors);
+        highlight(&mut self.born.input, "Born", &errors);
+        highlight(&mut self.sire.input.input(), "Sire", &errors);
+        highlight(&mut self.dam.input.input(), "Dam", &errors);
+
+        if errors.is_empty() {
+            self.animal.exec(|a| *a = animal.clone());
+            self.recorded = animal.events;
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 }
 
 impl<A: BusinessObject<Type = Animal>> AnimalForm<A> {
-    pub fn create(animal: A) -> Result<(Self, Widget)> {
+    /// `index` is the position of the animal in the flock, so it isn't reported as its own
+    /// duplicate.
+    pub fn create(
+        animal: A,
+        flok: Arc<Mutex<Flok>>,
+        index: Option<usize>,
+    ) -> Result<(Self, Widget)> {
         let events = JoeTable::new(EventTableModel::new(animal.clone()));
         let mut widget = events.to_widget();
         widget.set_size(0, 200);
+        let unit = flok.lock().unwrap().weight_unit;
+        let weights = WeightEditor::create(unit);
+        let mut weights_widget = weights.group.as_base_widget();
+        weights_widget.set_size(0, 150);
+        let scores = ScoreEditor::create();
+        let mut scores_widget = scores.group.as_base_widget();
+        scores_widget.set_size(0, 120);
         let form = Self {
             identity: Input::default(),
             sex: {
@@ -102,19 +524,27 @@ impl<A: BusinessObject<Type = Animal>> AnimalForm<A> {
             born: DateInput::default(),
             description: Input::default(),
             animal: animal.clone(),
-            dam: Input::default(),
-            sire: Input::default(),
+            dam: ParentPicker::create(Sex::Female, flok.clone()),
+            sire: ParentPicker::create(Sex::Male, flok),
             events,
+            weights,
+            scores,
+            withdrawal: Frame::default(),
+            index,
+            recorded: vec![],
         };
 
         let ui = create_form(vec![
             ("Identity", &form.identity),
             ("Sex", &form.sex),
-            ("Born", &form.born.input),
+            ("Born", &form.born.group),
             ("Description", &form.description),
-            ("Sire", &form.sire),
-            ("Dame", &form.dam),
+            ("Sire", &form.sire.group),
+            ("Dame", &form.dam.group),
             ("Events", &widget),
+            ("Weights", &weights_widget),
+            ("Scores", &scores_widget),
+            ("Withdrawal", &form.withdrawal),
         ])?;
 
         Ok((form, ui))
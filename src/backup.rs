@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use chrono::{Local, NaiveDateTime};
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{crypto, flok::*, flok_file, journal, storage};
+
+/// "herd.flok" is backed up as "herd-backup-20240301-061500.123.flok", so backups sort by age
+/// and open like the original.
+const MARKER: &str = "-backup-";
+const STAMP: &str = "%Y%m%d-%H%M%S%.3f";
+
+pub struct Backup {
+    pub path: PathBuf,
+    pub time: Date,
+}
+
+fn parts(file: &str) -> (PathBuf, String, String) {
+    let path = Path::new(file);
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (dir, stem, extension)
+}
+
+/// The backups of `file`, newest first.
+pub fn list(file: &str) -> Result<Vec<Backup>> {
+    let (dir, stem, extension) = parts(file);
+    let prefix = format!("{stem}{MARKER}");
+    let mut backups = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(stamp) = name
+            .strip_prefix(&prefix)
+            .and_then(|n| n.strip_suffix(&extension))
+        {
+            if let Ok(time) = NaiveDateTime::parse_from_str(stamp, STAMP) {
+                backups.push(Backup {
+                    path,
+                    time: time.into(),
+                });
+            }
+        }
+    }
+    backups.sort_by_key(|b| Reverse(b.time));
+    Ok(backups)
+}
+
+/// Copy `file` to a new backup, keeping only the newest `keep`.
+pub fn backup(file: &str, keep: usize) -> Result<()> {
+    if keep == 0 || !Path::new(file).exists() {
+        return Ok(());
+    }
+    let (dir, stem, extension) = parts(file);
+    let stamp = Local::now().naive_local().format(STAMP);
+    fs::copy(file, dir.join(format!("{stem}{MARKER}{stamp}{extension}")))?;
+    for old in list(file)?.iter().skip(keep) {
+        fs::remove_file(&old.path)?;
+    }
+    Ok(())
+}
+
+/// Encrypt the backups of `file` made before it had a passphrase.
+pub fn reseal(file: &str, passphrase: &str) -> Result<()> {
+    for b in list(file)? {
+        let data = fs::read(&b.path)?;
+        if !crypto::is_encrypted(&data) {
+            let temp = b.path.with_extension("tmp");
+            fs::write(&temp, crypto::encrypt(&data, passphrase)?)?;
+            fs::rename(temp, &b.path)?;
+        }
+    }
+    Ok(())
+}
+
+/// What a backup holds and what restoring it would change.
+pub fn preview(file: &str, backup: &Backup) -> Result<String> {
+    let flok = storage::open(path_str(&backup.path)?)?.load()?;
+    let events: Vec<&Event> = flok.animals.iter().flat_map(|a| &a.events).collect();
+    let mut lines = vec![
+        format!("Saved {}", backup.time),
+        format!("Flock: {}", flok.name),
+        format!(
+            "Animals: {} ({} female, {} male)",
+            flok.animals.len(),
+            flok.animals.iter().filter(|a| a.sex == Sex::Female).count(),
+            flok.animals.iter().filter(|a| a.sex == Sex::Male).count()
+        ),
+        format!(
+            "Events: {}, latest {}",
+            events.len(),
+            events
+                .iter()
+                .map(|e| e.date)
+                .max()
+                .map(|d| d.to_string())
+                .unwrap_or("none".to_string())
+        ),
+        String::new(),
+    ];
+    match flok_file::load(file) {
+        Ok(current) => {
+            let changes: Vec<String> = journal::changes(&current, &flok)
+                .iter()
+                .flat_map(|c| c.describe())
+                .collect();
+            if changes.is_empty() {
+                lines.push("Same as the current file".to_string());
+            } else {
+                lines.push("Restoring would change:".to_string());
+                lines.extend(changes.into_iter().map(|c| format!("  {c}")));
+            }
+        }
+        Err(e) => lines.push(format!("Unable to read the current file: {e}")),
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Save a backup over `file`. The current contents become a backup themselves.
+pub fn restore(file: &str, backup: &Backup) -> Result<Flok> {
+    let flok = storage::open(path_str(&backup.path)?)?.load()?;
+    flok_file::save(file, &flok)?;
+    Ok(flok)
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("Unable to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rolling_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("herd.flok");
+        let file = file.to_str().unwrap();
+        let mut flok = Flok {
+            backups: Some(2),
+            ..Default::default()
+        };
+        for name in ["one", "two", "three", "four"] {
+            flok.name = name.to_string();
+            flok_file::save(file, &flok).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let backups = list(file).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(preview(file, &backups[0]).unwrap().contains("Flock: three"));
+
+        let restored = restore(file, &backups[1]).unwrap();
+        assert_eq!(restored.name, "two");
+        assert_eq!(flok_file::load(file).unwrap().name, "two");
+        // the file being replaced was backed up
+        assert!(preview(file, &list(file).unwrap()[0])
+            .unwrap()
+            .contains("Flock: four"));
+
+        reseal(file, "correct horse").unwrap();
+        for b in list(file).unwrap() {
+            let sealed = fs::read(&b.path).unwrap();
+            assert!(crypto::is_encrypted(&sealed));
+            let plain = crypto::decrypt(&sealed, "correct horse").unwrap();
+            assert!(serde_json::from_slice::<Flok>(&plain).is_ok());
+        }
+    }
+}
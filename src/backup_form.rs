@@ -0,0 +1,78 @@
+use fltk::{
+    browser::HoldBrowser,
+    button::Button,
+    frame::Frame,
+    group::Flex,
+    prelude::{BrowserExt, DisplayExt, GroupExt, WidgetBase, WidgetExt, WindowExt},
+    text::{TextBuffer, TextDisplay},
+    window::Window,
+};
+use std::rc::Rc;
+
+use crate::{backup, flok::*, form::display_error};
+
+/// Pick one of the backups of `file`, with a preview of each. `done` gets the restored flock.
+pub fn show_restore(file: String, mut done: impl FnMut(Flok) + 'static) -> anyhow::Result<()> {
+    let backups = Rc::new(backup::list(&file)?);
+    if backups.is_empty() {
+        fltk::dialog::message_default("There are no backups of this file yet");
+        return Ok(());
+    }
+    let mut wind = Window::default()
+        .with_size(700, 400)
+        .with_label("Restore from backup");
+    let mut page = Flex::default_fill().column();
+    let mut panes = Flex::default().row();
+    let mut list = HoldBrowser::default();
+    for b in backups.iter() {
+        list.add(&b.time.to_string());
+    }
+    panes.fixed(&list, 180);
+    let mut preview = TextDisplay::default();
+    preview.set_buffer(TextBuffer::default());
+    panes.end();
+
+    let mut buttons = Flex::default().row();
+    buttons.resizable(&Frame::default());
+    {
+        let mut cancel = Button::default().with_label("Cancel");
+        let mut wind = wind.clone();
+        cancel.set_callback(move |_| wind.hide());
+        buttons.fixed(&cancel, 60);
+    }
+    {
+        let mut restore = Button::default().with_label("Restore");
+        let mut wind = wind.clone();
+        let (list, backups, file) = (list.clone(), backups.clone(), file.clone());
+        restore.set_callback(move |_| {
+            if list.value() > 0 {
+                match backup::restore(&file, &backups[list.value() as usize - 1]) {
+                    Ok(flok) => {
+                        done(flok);
+                        wind.hide();
+                    }
+                    Err(e) => display_error::<()>("Unable to restore backup", Err(e)),
+                }
+            }
+        });
+        buttons.fixed(&restore, 60);
+    }
+    buttons.end();
+    page.fixed(&buttons, 25);
+    page.end();
+
+    list.set_callback(move |l| {
+        if l.value() > 0 {
+            let text = backup::preview(&file, &backups[l.value() as usize - 1])
+                .unwrap_or_else(|e| format!("Unable to read backup: {e}"));
+            preview.buffer().unwrap().set_text(&text);
+        }
+    });
+    list.select(1);
+    list.do_callback();
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+    Ok(())
+}
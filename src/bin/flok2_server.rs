@@ -0,0 +1,23 @@
+//! Keeps the flocks shared with `flok2 sync` and the Sync menu. It needs no display, so it can
+//! run on a small always-on machine.
+
+use anyhow::Result;
+use clap::Parser;
+
+#[path = "../sync_server.rs"]
+mod sync_server;
+
+/// Share flocks with other computers on the local network
+#[derive(Parser)]
+struct Args {
+    #[clap(long, short, default_value = "127.0.0.1:8080")]
+    addr: String,
+    /// Where the flocks are stored
+    #[clap(long, short, default_value = ".")]
+    dir: std::path::PathBuf,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    sync_server::serve(&args.addr, args.dir)
+}
@@ -0,0 +1,480 @@
+use anyhow::Result;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::{
+    flok::*,
+    report::{escape, open_in_browser},
+};
+
+/// Sheep carry their lambs for about 147 days, give or take 5.
+pub const GESTATION_DAYS: u64 = 147;
+pub const GESTATION_SPREAD: u64 = 5;
+
+/// Ewes cycle every 17 days, so an exposure with no end date covers two cycles.
+pub const OPEN_EXPOSURE_DAYS: u64 = 34;
+
+/// A ram put with ewes, from `from` until `to`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Exposure {
+    pub name: String,
+    pub ram: Id,
+    pub ewes: Vec<Id>,
+    pub from: Date,
+    /// `None` while the ram is still in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Date>,
+}
+
+impl Exposure {
+    /// The last day the ram could have bred a ewe.
+    pub fn end(&self) -> Date {
+        self.to
+            .unwrap_or_else(|| add_days(self.from, OPEN_EXPOSURE_DAYS))
+    }
+}
+
+/// Events named like these record a ram marking, breeding or being put in with a ewe, the
+/// ram's id in `value`.
+const MARK_EVENTS: [&str; 7] = [
+    "marked", "mated", "bred", "tupped", "covered", "exposed", "ram in",
+];
+
+/// Events named like these are pregnancy scans, with the number of lambs in `value`
+/// ("2", "twins", "open") and the days pregnant as "60 days" in `value` or `notes`.
+const SCAN_EVENTS: [&str; 3] = ["scan", "scanned", "preg check"];
+
+fn add_days(date: Date, days: u64) -> Date {
+    (date.day + Days::new(days)).into()
+}
+
+fn sub_days(date: Date, days: u64) -> Date {
+    (date.day - Days::new(days)).into()
+}
+
+fn named(event: &Event, names: &[&str]) -> bool {
+    names.contains(&event.name.trim().to_lowercase().as_str())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scan {
+    pub date: Date,
+    pub lambs: u32,
+    pub days_pregnant: Option<u64>,
+}
+
+pub fn scan(event: &Event) -> Option<Scan> {
+    if !named(event, &SCAN_EVENTS) {
+        return None;
+    }
+    let text = format!("{} {}", event.value, event.notes).to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|w| !w.is_empty())
+        .collect();
+    let lambs = words.iter().find_map(|w| match *w {
+        "open" | "empty" | "dry" => Some(0),
+        "single" => Some(1),
+        "twins" => Some(2),
+        "triplets" => Some(3),
+        "quads" => Some(4),
+        _ => None,
+    });
+    let lambs = lambs.or_else(|| {
+        words
+            .iter()
+            .zip(words.iter().skip(1).map(Some).chain([None]))
+            .find(|(_, next)| !next.is_some_and(|n| n.starts_with("day")))
+            .and_then(|(w, _)| w.parse().ok())
+    })?;
+    let days_pregnant = words
+        .windows(2)
+        .find(|w| w[1].starts_with("day"))
+        .and_then(|w| w[0].parse().ok());
+    Some(Scan {
+        date: event.date,
+        lambs,
+        days_pregnant,
+    })
+}
+
+/// Where an expected lambing comes from.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum Basis {
+    Exposure(String),
+    Mark,
+    Scan,
+}
+
+impl Basis {
+    pub fn describe(&self) -> String {
+        match self {
+            Basis::Exposure(name) if name.is_empty() => "exposure".to_string(),
+            Basis::Exposure(name) => format!("exposure {name}"),
+            Basis::Mark => "marked".to_string(),
+            Basis::Scan => "scan".to_string(),
+        }
+    }
+}
+
+/// When a ewe should lamb.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Lambing {
+    pub ewe: Id,
+    /// The most likely day
+    pub expected: Date,
+    pub earliest: Date,
+    pub latest: Date,
+    /// The ram, if there was only one
+    pub ram: Option<Id>,
+    /// Lambs seen at the last scan
+    pub lambs: Option<u32>,
+    pub basis: Basis,
+}
+
+/// When the ewe was put with a ram or marked, newest last.
+pub fn bred(flok: &Flok, ewe: &Animal) -> Vec<Date> {
+    let mut dates: Vec<Date> = flok
+        .exposures
+        .iter()
+        .filter(|e| ewe.id.iter().any(|id| e.ewes.contains(id)))
+        .map(|e| e.from)
+        .chain(
+            ewe.events
+                .iter()
+                .filter(|e| named(e, &MARK_EVENTS))
+                .map(|e| e.date),
+        )
+        .collect();
+    dates.sort();
+    dates
+}
+
+/// Rams that could have bred `ewe` for a lamb born on `born`, from exposures and marks.
+pub fn possible_sires(flok: &Flok, ewe: &Animal, born: Date) -> Vec<Id> {
+    let bred_from = sub_days(born, GESTATION_DAYS + GESTATION_SPREAD);
+    let bred_to = sub_days(born, GESTATION_DAYS - GESTATION_SPREAD);
+    let mut rams: Vec<Id> = flok
+        .exposures
+        .iter()
+        .filter(|e| ewe.id.iter().any(|id| e.ewes.contains(id)))
+        .filter(|e| e.from <= bred_to && e.end() >= bred_from)
+        .map(|e| e.ram.clone())
+        .chain(
+            ewe.events
+                .iter()
+                .filter(|e| named(e, &MARK_EVENTS) && !e.value.trim().is_empty())
+                .filter(|e| e.date >= bred_from && e.date <= bred_to)
+                .map(|e| e.value.trim().to_string()),
+        )
+        .collect();
+    rams.sort();
+    rams.dedup();
+    rams
+}
+
+/// The sire of `lamb`, when its dam's breeding records leave only one ram.
+pub fn infer_sire(flok: &Flok, lamb: &Animal) -> Option<Id> {
+    let (dam, born) = (flok.find(lamb.dam.clone()?)?, lamb.born?);
+    match possible_sires(flok, dam, born).as_slice() {
+        [ram] => Some(ram.clone()),
+        _ => None,
+    }
+}
+
+/// Fill in the sire of every lamb that has none and can be inferred. Returns the lambs changed.
+pub fn infer_sires(flok: &mut Flok) -> Vec<Id> {
+    let inferred: Vec<(usize, Id)> = flok
+        .animals
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| a.sire.is_none())
+        .filter_map(|(i, a)| infer_sire(flok, a).map(|ram| (i, ram)))
+        .collect();
+    inferred
+        .into_iter()
+        .map(|(i, ram)| {
+            flok.animals[i].sire = Some(ram);
+            flok.animals[i].id.first().cloned().unwrap_or_default()
+        })
+        .collect()
+}
+
+/// The ewe's next lambing, from her latest scan, mark or exposure. `None` once she has lambed
+/// since, or if the scan found her open.
+pub fn expected_lambing(flok: &Flok, ewe: &Animal) -> Option<Lambing> {
+    let id = ewe.id.first().cloned().unwrap_or_default();
+    let exposure = flok
+        .exposures
+        .iter()
+        .filter(|e| ewe.id.iter().any(|id| e.ewes.contains(id)))
+        .max_by_key(|e| e.from);
+    let mark = ewe
+        .events
+        .iter()
+        .filter(|e| named(e, &MARK_EVENTS))
+        .max_by_key(|e| e.date);
+    // a mark inside the exposure is more precise than the exposure
+    let mut lambing = match (exposure, mark) {
+        (Some(x), Some(m)) if x.from > m.date => None,
+        (_, Some(m)) => Some(Lambing {
+            ewe: id.clone(),
+            expected: add_days(m.date, GESTATION_DAYS),
+            earliest: add_days(m.date, GESTATION_DAYS - GESTATION_SPREAD),
+            latest: add_days(m.date, GESTATION_DAYS + GESTATION_SPREAD),
+            ram: Some(m.value.trim().to_string()).filter(|r| !r.is_empty()),
+            lambs: None,
+            basis: Basis::Mark,
+        }),
+        _ => None,
+    }
+    .or_else(|| {
+        let x = exposure?;
+        let earliest = add_days(x.from, GESTATION_DAYS - GESTATION_SPREAD);
+        let latest = add_days(x.end(), GESTATION_DAYS + GESTATION_SPREAD);
+        Some(Lambing {
+            ewe: id.clone(),
+            expected: (earliest.day + (latest.day - earliest.day) / 2).into(),
+            earliest,
+            latest,
+            ram: Some(x.ram.clone()),
+            lambs: None,
+            basis: Basis::Exposure(x.name.clone()),
+        })
+    })?;
+    let bred = sub_days(lambing.earliest, GESTATION_DAYS - GESTATION_SPREAD);
+    if let Some(scan) = ewe
+        .events
+        .iter()
+        .filter(|e| e.date >= bred)
+        .filter_map(scan)
+        .max_by_key(|s| s.date)
+    {
+        if scan.lambs == 0 {
+            return None;
+        }
+        lambing.lambs = Some(scan.lambs);
+        if let Some(days) = scan.days_pregnant {
+            let expected = add_days(sub_days(scan.date, days), GESTATION_DAYS);
+            lambing.expected = expected;
+            lambing.earliest = sub_days(expected, GESTATION_SPREAD);
+            lambing.latest = add_days(expected, GESTATION_SPREAD);
+            lambing.basis = Basis::Scan;
+        }
+    }
+    let lambed = flok.animals.iter().any(|a| {
+        a.dam.as_ref().is_some_and(|d| ewe.id.contains(d)) && a.born.is_some_and(|b| b > bred)
+    }) || ewe
+        .events
+        .iter()
+        .any(|e| e.name.trim().to_lowercase().starts_with("lambed") && e.date > bred);
+    (!lambed).then_some(lambing)
+}
+
+/// Every ewe still to lamb, soonest first.
+pub fn lambings(flok: &Flok) -> Vec<Lambing> {
+    let mut lambings: Vec<Lambing> = flok
+        .animals
+        .iter()
+        .filter(|a| a.sex == Sex::Female && a.in_flock())
+        .filter_map(|a| expected_lambing(flok, a))
+        .collect();
+    lambings.sort_by(|a, b| (a.expected, &a.ewe).cmp(&(b.expected, &b.ewe)));
+    lambings
+}
+
+/// A month per table, each expected lambing on its day and each day shaded by how many ewes
+/// could lamb then.
+pub fn calendar_html(flok: &Flok) -> String {
+    let lambings = lambings(flok);
+    let mut months = BTreeSet::new();
+    for l in &lambings {
+        let mut day = l.earliest.day.with_day(1).unwrap();
+        while day <= l.latest.day {
+            months.insert((day.year(), day.month()));
+            day = day + Months::new(1);
+        }
+    }
+    let mut html = String::from(
+        "<html><head><title>Lambing calendar</title><style>\
+         td { vertical-align: top; width: 8em; height: 4em; }</style></head><body>\n\
+         <h1>Lambing calendar</h1>\n\
+         <p>Each ewe is on her expected day. Darker days have more ewes that could lamb.</p>\n",
+    );
+    for (year, month) in &months {
+        let first = NaiveDate::from_ymd_opt(*year, *month, 1).unwrap();
+        html += &format!(
+            "<h2>{}</h2>\n<table border=\"1\">\n<tr><th>Sun</th><th>Mon</th><th>Tue</th>\
+             <th>Wed</th><th>Thu</th><th>Fri</th><th>Sat</th></tr>\n<tr>",
+            first.format("%B %Y")
+        );
+        let blanks = first.weekday().num_days_from_sunday();
+        html += &"<td></td>".repeat(blanks as usize);
+        let mut day = first;
+        while day.month() == *month {
+            if day.weekday().num_days_from_sunday() == 0 && day != first {
+                html += "</tr>\n<tr>";
+            }
+            let possible = lambings
+                .iter()
+                .filter(|l| l.earliest.day <= day && day <= l.latest.day)
+                .count();
+            let shade = 255 - (possible * 20).min(120) as u8;
+            let ewes: Vec<String> = lambings
+                .iter()
+                .filter(|l| l.expected.day == day)
+                .map(|l| {
+                    format!(
+                        "<span title=\"{} to {}, {}\">{}{}</span>",
+                        l.earliest,
+                        l.latest,
+                        escape(&l.basis.describe()),
+                        escape(&l.ewe),
+                        l.lambs.map(|n| format!(" ({n})")).unwrap_or_default()
+                    )
+                })
+                .collect();
+            html += &format!(
+                "<td style=\"background: rgb({shade},{shade},255)\"><b>{}</b><br>{}</td>",
+                day.day(),
+                ewes.join("<br>")
+            );
+            day = day.succ_opt().unwrap();
+        }
+        html += "</tr>\n</table>\n";
+    }
+    html += "</body></html>\n";
+    html
+}
+
+pub fn report_calendar(flok: &Flok) -> Result<()> {
+    open_in_browser(&calendar_html(flok), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn animal(id: &str, sex: Sex) -> Animal {
+        Animal {
+            id: vec![id.to_string()],
+            sex,
+            ..Default::default()
+        }
+    }
+
+    fn event(name: &str, value: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            value: value.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scans() {
+        let s = |value: &str| scan(&event("Scanned", value, "2024-01-01"));
+        assert_eq!(s("twins").map(|s| s.lambs), Some(2));
+        assert_eq!(s("open").map(|s| s.lambs), Some(0));
+        assert_eq!(
+            s("2, 60 days").map(|s| (s.lambs, s.days_pregnant)),
+            Some((2, Some(60)))
+        );
+        assert_eq!(s("?"), None);
+        assert_eq!(scan(&event("sold", "2", "2024-01-01")), None);
+    }
+
+    #[test]
+    fn lambing_windows_and_sires() {
+        let mut marked = animal("marked", Sex::Female);
+        marked
+            .events
+            .push(event("marked", "blue ram", "2024-10-10"));
+        let mut scanned = animal("scanned", Sex::Female);
+        scanned
+            .events
+            .push(event("scan", "twins 50 days", "2024-11-29"));
+        let mut open = animal("open", Sex::Female);
+        open.events.push(event("scan", "open", "2024-11-29"));
+        let mut flok = Flok {
+            exposures: vec![Exposure {
+                name: "fall".to_string(),
+                ram: "red ram".to_string(),
+                ewes: vec![
+                    "exposed".to_string(),
+                    "scanned".to_string(),
+                    "open".to_string(),
+                ],
+                from: Date::parse("2024-10-01").unwrap(),
+                to: Some(Date::parse("2024-11-04").unwrap()),
+            }],
+            animals: vec![
+                marked,
+                animal("exposed", Sex::Female),
+                scanned,
+                open,
+                animal("red ram", Sex::Male),
+            ],
+            ..Default::default()
+        };
+        let windows: Vec<(String, String, String, String, Option<u32>)> = lambings(&flok)
+            .into_iter()
+            .map(|l| {
+                (
+                    l.ewe,
+                    l.earliest.to_iso(),
+                    l.expected.to_iso(),
+                    l.latest.to_iso(),
+                    l.lambs,
+                )
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            vec![
+                (
+                    "marked".to_string(),
+                    "2025-03-01".to_string(),
+                    "2025-03-06".to_string(),
+                    "2025-03-11".to_string(),
+                    None
+                ),
+                (
+                    "scanned".to_string(),
+                    "2025-03-01".to_string(),
+                    "2025-03-06".to_string(),
+                    "2025-03-11".to_string(),
+                    Some(2)
+                ),
+                (
+                    "exposed".to_string(),
+                    "2025-02-20".to_string(),
+                    "2025-03-14".to_string(),
+                    "2025-04-05".to_string(),
+                    None
+                ),
+            ]
+        );
+        assert!(calendar_html(&flok).contains("March 2025"));
+
+        let lamb = |dam: &str, born: &str| Animal {
+            id: vec![format!("{dam} lamb")],
+            dam: Some(dam.to_string()),
+            born: Date::parse(born),
+            ..animal("", Sex::Male)
+        };
+        flok.animals.push(lamb("exposed", "2025-03-10"));
+        flok.animals.push(lamb("marked", "2025-03-06"));
+        // born too early for the exposure
+        flok.animals.push(lamb("scanned", "2025-01-01"));
+        assert_eq!(infer_sires(&mut flok), vec!["exposed lamb", "marked lamb"]);
+        assert_eq!(
+            flok.find("marked lamb".to_string()).unwrap().sire,
+            Some("blue ram".to_string())
+        );
+        // lambed, no longer expected
+        assert!(expected_lambing(&flok, &flok.animals[0]).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use fltk::{
+    input::{Input, MultilineInput},
+    prelude::InputExt,
+    widget::Widget,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    breeding::Exposure,
+    date_input::DateInput,
+    flok::*,
+    form::*,
+    list_editor::{show_list_editor, ItemForm},
+};
+
+/// The fields of one breeding group: a ram and the ewes he was with.
+#[derive(Clone)]
+pub struct ExposureForm {
+    pub name: Input,
+    pub ram: Input,
+    pub ewes: MultilineInput,
+    pub from: DateInput,
+    pub to: DateInput,
+}
+
+impl ItemForm<Exposure> for ExposureForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let mut form = Self {
+            name: Input::default(),
+            ram: Input::default(),
+            ewes: MultilineInput::default(),
+            from: DateInput::default(),
+            to: DateInput::default(),
+        };
+        form.ewes
+            .set_tooltip("Ewe ids, separated by commas or on separate lines");
+        form.to
+            .input
+            .set_tooltip("When the ram came out, blank while he is still in");
+        let ui = create_form(vec![
+            ("Name", &form.name),
+            ("Ram", &form.ram),
+            ("Ewes", &form.ewes),
+            ("Ram in", &form.from.group),
+            ("Ram out", &form.to.group),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, e: &Exposure) {
+        self.name.set_value(&e.name);
+        self.ram.set_value(&e.ram);
+        self.ewes.set_value(&e.ewes.join("\n"));
+        // a new group starts today
+        self.from.set_date(Some(if e.ram.is_empty() {
+            Date::today()
+        } else {
+            e.from
+        }));
+        self.to.set_date(e.to);
+    }
+
+    fn item(&mut self, flok: &Flok, _index: Option<usize>) -> Result<Exposure, Vec<FieldError>> {
+        let mut errors = vec![];
+        let ram = self.ram.value().trim().to_string();
+        match flok.find(ram.clone()) {
+            _ if ram.is_empty() => errors.push(FieldError::new("Ram", "Ram is empty")),
+            None => errors.push(FieldError::new("Ram", format!("{ram} is not in the flock"))),
+            Some(a) if a.sex == Sex::Female => {
+                errors.push(FieldError::new("Ram", format!("{ram} is a ewe")))
+            }
+            Some(_) => (),
+        }
+        let ewes: Vec<Id> = self
+            .ewes
+            .value()
+            .split(|c: char| c == ',' || c == '\n')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        if ewes.is_empty() {
+            errors.push(FieldError::new("Ewes", "No ewes"));
+        }
+        let missing: Vec<&str> = ewes
+            .iter()
+            .filter(|e| flok.find((*e).clone()).is_none())
+            .map(|e| e.as_str())
+            .collect();
+        if !missing.is_empty() {
+            errors.push(FieldError::new(
+                "Ewes",
+                format!("Not in the flock: {}", missing.join(", ")),
+            ));
+        }
+        let from = self.from.parse("Ram in").unwrap_or_else(|e| {
+            errors.push(e);
+            None
+        });
+        if from.is_none() && !errors.iter().any(|e| e.field == "Ram in") {
+            errors.push(FieldError::new("Ram in", "When did the ram go in?"));
+        }
+        let to = self.to.parse("Ram out").unwrap_or_else(|e| {
+            errors.push(e);
+            None
+        });
+        if let (Some(from), Some(to)) = (from, to) {
+            if to < from {
+                errors.push(FieldError::new(
+                    "Ram out",
+                    "The ram came out before he went in",
+                ));
+            }
+        }
+
+        highlight(&mut self.ram, "Ram", &errors);
+        highlight(&mut self.ewes, "Ewes", &errors);
+        highlight(&mut self.from.input, "Ram in", &errors);
+        highlight(&mut self.to.input, "Ram out", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Exposure {
+            name: self.name.value().trim().to_string(),
+            ram,
+            ewes,
+            from: from.unwrap_or_default(),
+            to,
+        })
+    }
+}
+
+/// Edit which rams were put with which ewes, and when.
+pub fn show_exposures(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Exposure, ExposureForm>(
+        "Breeding groups",
+        flok,
+        |f| &mut f.exposures,
+        |e| {
+            let name = if e.name.is_empty() { &e.ram } else { &e.name };
+            format!("{name} ({}, {} ewes)", e.from, e.ewes.len())
+        },
+    )
+}
@@ -0,0 +1,2178 @@
+use anyhow::{anyhow, Result};
+use enum_ordinalize::Ordinalize;
+use serde::Serialize;
+use std::{collections::BTreeMap, io::Write};
+
+use crate::{
+    breeding, contacts, crypto, cull,
+    export::{self, Column, ExportOptions},
+    feed,
+    flok::*,
+    flok_file, grazing, groups, growth, health, journal, ledger, merge, merge_form, productivity,
+    regulatory, report, scores, storage, sync, treatment, validate,
+};
+
+/// Commands that work on a .flok file without opening a window.
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// One line per animal
+    List {
+        file: String,
+        /// Only members of this group
+        #[clap(long, short)]
+        group: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// An animal and its events
+    Show {
+        file: String,
+        id: Id,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Record an event against an animal and save the file. Events named after a product are
+    /// treatments; the dose is worked out from the animal's weight when no value is given.
+    AddEvent {
+        file: String,
+        id: Id,
+        #[clap(long, short)]
+        name: String,
+        #[clap(long, short, default_value = "")]
+        value: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, default_value = "")]
+        notes: String,
+        /// Who the animal was bought from or sold to
+        #[clap(long)]
+        contact: Option<String>,
+    },
+    /// Record a weighing against an animal and save the file
+    AddWeight {
+        file: String,
+        id: Id,
+        weight: f64,
+        /// Defaults to the flock's unit
+        #[clap(long, short, value_enum)]
+        unit: Option<WeightUnit>,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// An animal's weighings with the daily gain since the one before, and its adjusted weights
+    Weights {
+        file: String,
+        id: Id,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Record a FAMACHA or body condition score against an animal and save the file
+    AddScore {
+        file: String,
+        id: Id,
+        #[clap(value_enum)]
+        kind: ScoreKind,
+        /// 1 to 5, BCS in half steps
+        score: f64,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// An animal's FAMACHA and body condition scores
+    Scores {
+        file: String,
+        id: Id,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Animals to deworm, to watch or to consider culling from their scores and dewormings
+    Parasites {
+        file: String,
+        /// Only members of this group
+        #[clap(long, short)]
+        group: Option<String>,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Lambs born in a season ranked by average daily gain
+    Adg {
+        file: String,
+        /// Year the lambs were born
+        season: i32,
+        /// Only members of this group
+        #[clap(long, short)]
+        group: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// A lambing season's lambing and weaning rates and lamb deaths, or with --ewes each ewe's
+    /// lifetime production
+    Productivity {
+        file: String,
+        /// Year the lambs were born
+        season: i32,
+        #[clap(long)]
+        ewes: bool,
+        /// Count lambs weaned by this date, defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Ewes and rams ranked for culling, with the reasons. The rules are read from herd.cull.toml
+    /// next to herd.flok, or the defaults if there is none.
+    Cull {
+        file: String,
+        /// Another rules file
+        #[clap(long, short)]
+        rules: Option<String>,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Write the default cull rules next to the file to edit, unless there are rules already
+    CullRules { file: String },
+    /// Income and expenses, oldest first
+    Ledger {
+        file: String,
+        /// Only this year
+        #[clap(long, short)]
+        year: Option<i32>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Record income or an expense and save the file
+    AddEntry {
+        file: String,
+        #[clap(value_enum)]
+        category: Category,
+        amount: f64,
+        /// Blank for flock-wide costs, which are shared by the animals on hand
+        #[clap(long)]
+        animal: Option<Id>,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, default_value = "")]
+        description: String,
+    },
+    /// Add prices recorded as events, like "purchase price" and "sale $", to the ledger and save
+    /// the file
+    RecordPrices { file: String },
+    /// Profit and loss per animal, lamb crop or year
+    Pnl {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        by: PnlBy,
+        /// Only transactions in this year, for --by animal
+        #[clap(long, short)]
+        year: Option<i32>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// The people animals are bought from and sold to
+    Contacts {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Add someone animals are bought from or sold to and save the file
+    AddContact {
+        file: String,
+        name: String,
+        #[clap(long, default_value = "")]
+        farm: String,
+        #[clap(long, default_value = "")]
+        address: String,
+        #[clap(long, default_value = "")]
+        phone: String,
+        #[clap(long, default_value = "")]
+        premises_id: String,
+    },
+    /// Every animal bought from or sold to a contact
+    ContactHistory {
+        file: String,
+        name: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Link purchase and sale events to the contacts named in their notes and save the file
+    LinkContacts { file: String },
+    /// Show the farm's premises ID, or set it and save the file
+    Premises {
+        file: String,
+        premises_id: Option<String>,
+    },
+    /// Record an animal's official ID, like a scrapie tag, and save the file
+    OfficialId {
+        file: String,
+        id: Id,
+        tag: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// Animals acquired and disposed of, with their official IDs, for an inspector
+    Movements {
+        file: String,
+        #[clap(long)]
+        from: Option<String>,
+        #[clap(long)]
+        to: Option<String>,
+        /// Write the printable HTML records instead
+        #[clap(long)]
+        html: bool,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// The groups animals are run in
+    Groups {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Add a group of animals, or add animals to one, and save the file
+    AddGroup {
+        file: String,
+        name: String,
+        members: Vec<Id>,
+    },
+    /// The pastures animals are moved between
+    Pastures {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Add a pasture and save the file
+    AddPasture {
+        file: String,
+        name: String,
+        #[clap(long, default_value = "")]
+        notes: String,
+        #[clap(long)]
+        acres: Option<f64>,
+        /// Days it should rest between grazings, 30 if not given
+        #[clap(long)]
+        rest_days: Option<u32>,
+    },
+    /// Record an event against every member of a group still in the flock and save the file
+    GroupEvent {
+        file: String,
+        group: String,
+        #[clap(long, short)]
+        name: String,
+        #[clap(long, short, default_value = "")]
+        value: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, default_value = "")]
+        notes: String,
+    },
+    /// Move a group, or one animal, to a pasture and save the file
+    Move {
+        file: String,
+        /// A group name or an animal's id
+        who: String,
+        pasture: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// Which pasture every animal is in
+    Where {
+        file: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// How each pasture has been grazed this year and where to move animals next
+    Grazing {
+        file: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Feed left and how many days it will last at the current rate
+    FeedInventory {
+        file: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Record feed bought, like 40 bales of hay for $300, and save the file
+    AddFeedPurchase {
+        file: String,
+        feed: String,
+        quantity: f64,
+        /// For the whole quantity
+        cost: f64,
+        /// Like "bales" or "lb"
+        #[clap(long, default_value = "")]
+        unit: String,
+        #[clap(long, value_enum, default_value_t)]
+        kind: feed::FeedKind,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// Record feed given to a group, in the units it was bought in, and save the file
+    AddFeeding {
+        file: String,
+        group: String,
+        feed: String,
+        quantity: f64,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// The catalogue of wormers, vaccines and other products
+    Products {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Add a product to the catalogue, or replace the one with the same name
+    AddProduct {
+        file: String,
+        name: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        kind: ProductKind,
+        /// Days before a treated animal can be slaughtered
+        #[clap(long, default_value_t = 0)]
+        meat: u32,
+        /// Days before a treated animal's milk can be used
+        #[clap(long, default_value_t = 0)]
+        milk: u32,
+        /// Dose in ml per kg of body weight
+        #[clap(long)]
+        dose_per_kg: Option<f64>,
+    },
+    /// Animals still inside a withdrawal period
+    Withdrawal {
+        file: String,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// The recurring health protocols
+    Protocols {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Animals due or overdue a treatment under the health protocols
+    Due {
+        file: String,
+        /// Only members of this group
+        #[clap(long, short)]
+        group: Option<String>,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Record a protocol's treatment for the animals due it and save the file
+    RecordDue {
+        file: String,
+        protocol: String,
+        /// Only these animals, defaults to all that are due
+        ids: Vec<Id>,
+        /// Defaults to today
+        #[clap(long, short)]
+        date: Option<String>,
+    },
+    /// Put a ram in with ewes and save the file
+    Expose {
+        file: String,
+        ram: Id,
+        ewes: Vec<Id>,
+        /// Defaults to today
+        #[clap(long)]
+        from: Option<String>,
+        /// When the ram came out, blank while he is still in
+        #[clap(long)]
+        to: Option<String>,
+        /// The breeding group, eg "fall 2024"
+        #[clap(long, short, default_value = "")]
+        name: String,
+    },
+    /// Ewes still to lamb and when, from exposures, marks and scans
+    Lambing {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Fill in the sire of lambs whose dam was with only one ram, and save the file
+    InferSires { file: String },
+    /// Write the flock to a spreadsheet, or to stdout as JSON or CSV
+    Export {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+        /// A .xlsx, .ods or .csv file. CSV writes one file per sheet.
+        #[clap(long, short)]
+        output: Option<String>,
+        /// Animal columns, defaults to all of them
+        #[clap(long, value_enum, value_delimiter = ',')]
+        columns: Vec<Column>,
+        /// Event names to show as animal columns, or "all"
+        #[clap(long, value_delimiter = ',')]
+        pivot: Vec<String>,
+        /// Add a sheet with one row per event, needs --output
+        #[clap(long)]
+        events: bool,
+        /// Add a sheet with one row per lamb, needs --output
+        #[clap(long)]
+        lambing: bool,
+        /// Add a sheet with one row per sale, needs --output
+        #[clap(long)]
+        sales: bool,
+    },
+    /// Write a report, to stdout unless --output is given
+    Report {
+        file: String,
+        #[clap(value_enum)]
+        report: ReportKind,
+        #[clap(long, short)]
+        output: Option<String>,
+        /// The animal to report on, for reports about one animal
+        #[clap(long)]
+        id: Option<Id>,
+    },
+    /// List problems with the data. Exits with an error if there are any.
+    Validate {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Summary counts
+    Stats {
+        file: String,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Changes to an animal, from the journal kept next to the file
+    History {
+        file: String,
+        id: Id,
+        #[clap(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Rebuild the flock as it was at the end of a past date from its journal
+    AsOf {
+        file: String,
+        date: String,
+        /// A .flok file, defaults to stdout
+        #[clap(long, short)]
+        output: Option<String>,
+    },
+    /// Copy a flock between formats, eg from herd.flok to herd.flokdb (SQLite).
+    /// Encrypts the copy when FLOK_PASSPHRASE is set, unless --decrypt is given.
+    Convert {
+        from: String,
+        to: String,
+        #[clap(long)]
+        decrypt: bool,
+    },
+    /// Combine two edited copies of a flock, asking which side wins where both changed the same
+    /// field
+    Merge {
+        /// The copy both sides started from
+        base: String,
+        mine: String,
+        theirs: String,
+        /// Defaults to overwriting mine
+        #[clap(long, short)]
+        output: Option<String>,
+        #[clap(long, short, value_enum, default_value_t)]
+        prefer: Prefer,
+    },
+    /// Merge changes from the server and send ours, then save the file
+    Sync {
+        file: String,
+        /// Start sharing on this server, eg http://barn:8080
+        #[clap(long, short, requires = "name")]
+        server: Option<String>,
+        /// Name of the flock on the server
+        #[clap(long, short)]
+        name: Option<String>,
+        /// Who wins where this and another device changed the same field
+        #[clap(long, short, value_enum, default_value_t)]
+        prefer: Prefer,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+/// Who wins a merge conflict
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Prefer {
+    /// Open a window to choose
+    #[default]
+    Ask,
+    Mine,
+    Theirs,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default)]
+pub enum PnlBy {
+    #[default]
+    Animal,
+    Crop,
+    Year,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum ReportKind {
+    /// SVG graph of ancestors
+    Lineage,
+    /// SVG chart of one animal's weight against age, needs --id
+    Growth,
+    /// HTML calendar of expected lambings
+    LambingCalendar,
+    /// HTML profit and loss by year, lamb crop and animal
+    ProfitAndLoss,
+    /// SVG timeline of this year's grazing and rest per pasture
+    GrazingTimeline,
+}
+
+#[derive(Serialize)]
+struct AnimalRow {
+    id: Id,
+    tags: String,
+    sex: String,
+    born: Option<Date>,
+    sire: Option<Id>,
+    dam: Option<Id>,
+    description: String,
+    events: usize,
+}
+
+impl From<&Animal> for AnimalRow {
+    fn from(a: &Animal) -> Self {
+        Self {
+            id: a.id.first().cloned().unwrap_or_default(),
+            tags: a.id.iter().skip(1).cloned().collect::<Vec<_>>().join(" "),
+            sex: a.sex.name().to_string(),
+            born: a.born,
+            sire: a.sire.clone(),
+            dam: a.dam.clone(),
+            description: a.description.clone(),
+            events: a.events.len(),
+        }
+    }
+}
+
+/// An event with every column, for CSV, which needs the same columns in each row.
+#[derive(Serialize)]
+struct EventRow {
+    name: String,
+    value: String,
+    date: Date,
+    notes: String,
+    contact: String,
+}
+
+impl From<&Event> for EventRow {
+    fn from(e: &Event) -> Self {
+        Self {
+            name: e.name.clone(),
+            value: e.value.clone(),
+            date: e.date,
+            notes: e.notes.clone(),
+            contact: e.contact.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HistoryRow {
+    time: Date,
+    user: String,
+    change: String,
+}
+
+#[derive(Serialize)]
+struct WeightRow {
+    date: Date,
+    weight: f64,
+    unit: String,
+    /// Gain per day since the weighing before
+    adg: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct ProductRow {
+    name: String,
+    kind: String,
+    meat_withdrawal: u32,
+    milk_withdrawal: u32,
+    dose_per_kg: Option<f64>,
+}
+
+impl From<&Product> for ProductRow {
+    fn from(p: &Product) -> Self {
+        Self {
+            name: p.name.clone(),
+            kind: p.kind.name().to_string(),
+            meat_withdrawal: p.meat_withdrawal,
+            milk_withdrawal: p.milk_withdrawal,
+            dose_per_kg: p.dose_per_kg,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProtocolRow {
+    name: String,
+    treatment: String,
+    schedule: String,
+    target: String,
+    months: String,
+}
+
+#[derive(Serialize)]
+struct ParasiteRow {
+    id: Id,
+    action: String,
+    famacha: String,
+    bcs: String,
+    dewormings: usize,
+    reasons: String,
+}
+
+#[derive(Serialize)]
+struct LambingRow {
+    ewe: Id,
+    expected: Date,
+    earliest: Date,
+    latest: Date,
+    ram: String,
+    lambs: Option<u32>,
+    basis: String,
+}
+
+#[derive(Serialize)]
+struct MeasureRow {
+    measure: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct EweRow {
+    id: Id,
+    born: Option<Date>,
+    lambings: usize,
+    lambs: usize,
+    weaned: usize,
+    lambs_per_lambing: String,
+    by_year: String,
+    weight_weaned: String,
+    flags: String,
+    description: String,
+}
+
+#[derive(Serialize)]
+struct CullRow {
+    id: Id,
+    sex: String,
+    age: Option<i64>,
+    points: f64,
+    reasons: String,
+}
+
+#[derive(Serialize)]
+struct LedgerRow {
+    date: Date,
+    category: String,
+    income: String,
+    expense: String,
+    animal: String,
+    description: String,
+}
+
+#[derive(Serialize)]
+struct ContactRow {
+    name: String,
+    farm: String,
+    address: String,
+    phone: String,
+    premises_id: String,
+}
+
+impl From<&Contact> for ContactRow {
+    fn from(c: &Contact) -> Self {
+        Self {
+            name: c.name.clone(),
+            farm: c.farm.clone(),
+            address: c.address.clone(),
+            phone: c.phone.clone(),
+            premises_id: c.premises_id.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct YearRow {
+    year: i32,
+    income: f64,
+    expenses: f64,
+    profit: f64,
+}
+
+#[derive(Serialize)]
+struct GroupRow {
+    name: String,
+    members: String,
+}
+
+#[derive(Serialize)]
+struct PastureRow {
+    name: String,
+    notes: String,
+    acres: Option<f64>,
+    rest_days: Option<u32>,
+}
+
+impl From<&groups::Pasture> for PastureRow {
+    fn from(p: &groups::Pasture) -> Self {
+        Self {
+            name: p.name.clone(),
+            notes: p.notes.clone(),
+            acres: p.acres,
+            rest_days: p.rest_days,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WhereRow {
+    pasture: String,
+    id: Id,
+    since: Option<Date>,
+}
+
+#[derive(Serialize)]
+struct MovementRow {
+    date: Date,
+    direction: String,
+    official_id: String,
+    id: Id,
+    event: String,
+    party: String,
+    premises_id: String,
+    missing_id: bool,
+}
+
+#[derive(Serialize)]
+struct Problem {
+    id: Id,
+    field: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Stat {
+    name: String,
+    count: usize,
+}
+
+/// Encrypted files are read, and every file is written encrypted, with the passphrase in
+/// the FLOK_PASSPHRASE environment variable.
+pub fn run(command: Command) -> Result<()> {
+    crypto::set_passphrase(std::env::var(crypto::PASSPHRASE_VAR).ok());
+    match command {
+        Command::List {
+            file,
+            group,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let member = member_of(&flok, &group)?;
+            let rows: Vec<AnimalRow> = flok
+                .animals
+                .iter()
+                .map(AnimalRow::from)
+                .filter(|a| member(&a.id))
+                .collect();
+            print_rows(&rows, format, |a| {
+                format!(
+                    "{:<10} {:<6} {:<10} {:<10} {:<10} {}",
+                    a.id,
+                    a.sex,
+                    a.born.map(|d| d.to_string()).unwrap_or_default(),
+                    a.sire.clone().unwrap_or_default(),
+                    a.dam.clone().unwrap_or_default(),
+                    a.description
+                )
+            })
+        }
+        Command::Show { file, id, format } => {
+            let storage = storage::open(&file)?;
+            crate::date::set_format(storage.date_format()?);
+            let animal = &storage
+                .find(&id)?
+                .ok_or_else(|| anyhow!("No animal {id}"))?;
+            match format {
+                OutputFormat::Json => {
+                    serde_json::to_writer_pretty(std::io::stdout(), animal)?;
+                    println!();
+                    Ok(())
+                }
+                OutputFormat::Csv => {
+                    let rows: Vec<EventRow> = animal.events.iter().map(EventRow::from).collect();
+                    print_rows(&rows, format, |_| String::new())
+                }
+                OutputFormat::Text => {
+                    let a = AnimalRow::from(animal);
+                    println!("ID:          {}", a.id);
+                    println!("Other tags:  {}", a.tags);
+                    println!("Sex:         {}", a.sex);
+                    println!(
+                        "Born:        {}",
+                        a.born.map(|d| d.to_string()).unwrap_or_default()
+                    );
+                    println!("Sire:        {}", a.sire.unwrap_or_default());
+                    println!("Dam:         {}", a.dam.unwrap_or_default());
+                    println!("Description: {}", a.description);
+                    for e in &animal.events {
+                        println!(
+                            "  {:<12} {:<20} {:<10} {}",
+                            e.date, e.name, e.value, e.notes
+                        );
+                    }
+                    Ok(())
+                }
+            }
+        }
+        Command::AddEvent {
+            file,
+            id,
+            name,
+            value,
+            date,
+            notes,
+            contact,
+        } => {
+            let mut flok = load(&file)?;
+            let date = parse_date(date)?;
+            let index = flok
+                .animals
+                .iter()
+                .position(|a| a.id.contains(&id))
+                .ok_or_else(|| anyhow!("No animal {id}"))?;
+            let mut animal = flok.animals[index].clone();
+            let value = match flok.product(&name) {
+                Some(product) if value.is_empty() => {
+                    treatment::suggested_dose(product, &animal).unwrap_or_default()
+                }
+                _ => value,
+            };
+            animal.events.push(Event {
+                name,
+                value,
+                date,
+                notes,
+                contact,
+            });
+            let errors = validate::check_animal(&flok, Some(index), &animal);
+            if !errors.is_empty() {
+                return Err(validate::validation_error(errors));
+            }
+            flok.animals[index] = animal;
+            flok_file::save(&file, &flok)
+        }
+        Command::AddWeight {
+            file,
+            id,
+            weight,
+            unit,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let date = parse_date(date)?;
+            let unit = unit.unwrap_or(flok.weight_unit);
+            let animal = flok
+                .animals
+                .iter_mut()
+                .find(|a| a.id.contains(&id))
+                .ok_or_else(|| anyhow!("No animal {id}"))?;
+            animal.weights.push(Weight {
+                date,
+                value: weight,
+                unit,
+            });
+            flok_file::save(&file, &flok)
+        }
+        Command::Weights { file, id, format } => {
+            let flok = load(&file)?;
+            let unit = flok.weight_unit;
+            let animal = flok
+                .find(id.clone())
+                .ok_or_else(|| anyhow!("No animal {id}"))?;
+            let gains = growth::gains(animal, unit);
+            let mut weights = animal.weights.clone();
+            weights.sort_by_key(|w| w.date);
+            let rows: Vec<WeightRow> = weights
+                .iter()
+                .map(|w| WeightRow {
+                    date: w.date,
+                    weight: w.value_in(unit),
+                    unit: unit.name().to_string(),
+                    adg: gains.iter().find(|g| g.1 == w.date).map(|g| g.2),
+                })
+                .collect();
+            print_rows(&rows, format, |r| {
+                format!(
+                    "{:<12} {:>8.1} {} {}",
+                    r.date,
+                    r.weight,
+                    r.unit,
+                    r.adg.map(|g| format!("{g:+.2}/day")).unwrap_or_default()
+                )
+            })?;
+            if format == OutputFormat::Text {
+                if let Some(adg) = growth::adg(animal, unit) {
+                    println!("Average daily gain: {adg:.2} {}/day", unit.name());
+                }
+                for days in growth::ADJUSTED_DAYS {
+                    if let Some(w) = growth::adjusted(animal, days, unit) {
+                        println!("Adjusted {days} day weight: {w:.1} {}", unit.name());
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::AddScore {
+            file,
+            id,
+            kind,
+            score,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let score = Score {
+                date: parse_date(date)?,
+                kind,
+                value: score,
+            };
+            let errors = validate::check_score(&score);
+            if !errors.is_empty() {
+                return Err(validate::validation_error(errors));
+            }
+            flok.animals
+                .iter_mut()
+                .find(|a| a.id.contains(&id))
+                .ok_or_else(|| anyhow!("No animal {id}"))?
+                .scores
+                .push(score);
+            flok_file::save(&file, &flok)
+        }
+        Command::Scores { file, id, format } => {
+            let flok = load(&file)?;
+            let animal = flok
+                .find(id.clone())
+                .ok_or_else(|| anyhow!("No animal {id}"))?;
+            let mut scores = animal.scores.clone();
+            scores.sort_by_key(|s| s.date);
+            print_rows(&scores, format, |s| {
+                format!("{:<12} {:<8} {}", s.date, s.kind.name(), s.value)
+            })
+        }
+        Command::Parasites {
+            file,
+            group,
+            date,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let member = member_of(&flok, &group)?;
+            let show = |n: Option<f64>| n.map(|n| n.to_string()).unwrap_or_default();
+            let rows: Vec<ParasiteRow> = scores::flagged(&flok, parse_date(date)?)
+                .into_iter()
+                .filter(|f| member(&f.id))
+                .map(|f| ParasiteRow {
+                    action: f.action.name().to_string(),
+                    famacha: show(f.famacha),
+                    bcs: show(f.bcs),
+                    dewormings: f.dewormings,
+                    reasons: f.reasons.join("; "),
+                    id: f.id,
+                })
+                .collect();
+            print_rows(&rows, format, |r| {
+                format!(
+                    "{:<10} {:<17} FAMACHA {:<3} BCS {:<3} {}",
+                    r.id, r.action, r.famacha, r.bcs, r.reasons
+                )
+            })
+        }
+        Command::Adg {
+            file,
+            season,
+            group,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let member = member_of(&flok, &group)?;
+            let number = |n: Option<f64>| n.map(|n| format!("{n:.2}")).unwrap_or_default();
+            let mut ranked = growth::ranking(&flok, season);
+            ranked.retain(|r| member(&r.id));
+            print_rows(&ranked, format, |r| {
+                format!(
+                    "{:<10} {:<12} {:>8} {:>8} {:>8} {:>8}",
+                    r.id,
+                    r.born.map(|d| d.to_string()).unwrap_or_default(),
+                    number(r.adg),
+                    number(r.adjusted_60),
+                    number(r.adjusted_90),
+                    number(r.adjusted_120)
+                )
+            })
+        }
+        Command::Productivity {
+            file,
+            season,
+            ewes,
+            date,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let date = parse_date(date)?;
+            if !ewes {
+                let rows: Vec<MeasureRow> = productivity::season(&flok, season, date)
+                    .rows()
+                    .into_iter()
+                    .map(|(measure, value)| MeasureRow { measure, value })
+                    .collect();
+                return print_rows(&rows, format, |r| format!("{:<20} {}", r.measure, r.value));
+            }
+            let rows: Vec<EweRow> = productivity::ewes(&flok, date)
+                .into_iter()
+                .map(|e| EweRow {
+                    id: e.id,
+                    born: e.born,
+                    lambings: e.lambings,
+                    lambs: e.lambs,
+                    weaned: e.weaned,
+                    lambs_per_lambing: e
+                        .lambs_per_lambing
+                        .map(|n| format!("{n:.2}"))
+                        .unwrap_or_default(),
+                    by_year: e
+                        .by_year
+                        .iter()
+                        .map(|(y, n)| format!("{y}:{n}"))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    weight_weaned: format!("{:.1}", e.weight_weaned),
+                    flags: e.flags.join(", "),
+                    description: e.description,
+                })
+                .collect();
+            print_rows(&rows, format, |e| {
+                format!(
+                    "{:<10} {:>3} lambs {:>3} weaned {:>8} {:<20} {}",
+                    e.id, e.lambs, e.weaned, e.weight_weaned, e.by_year, e.flags
+                )
+            })
+        }
+        Command::Cull {
+            file,
+            rules,
+            date,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let path = rules
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|| cull::rules_path(&file));
+            let rules = cull::read_rules(&path)?;
+            let rows: Vec<CullRow> = cull::candidates(&flok, &rules, parse_date(date)?)
+                .into_iter()
+                .map(|c| CullRow {
+                    id: c.id,
+                    sex: c.sex.name().to_string(),
+                    age: c.age,
+                    points: c.points,
+                    reasons: cull::describe(&c.reasons),
+                })
+                .collect();
+            print_rows(&rows, format, |c| {
+                format!("{:<10} {:>5} {}", c.id, c.points, c.reasons)
+            })
+        }
+        Command::CullRules { file } => {
+            let path = cull::rules_path(&file);
+            if !path.exists() {
+                std::fs::write(&path, cull::DEFAULT_RULES)?;
+            }
+            println!("{}", path.display());
+            Ok(())
+        }
+        Command::Ledger { file, year, format } => {
+            use chrono::Datelike;
+            let flok = load(&file)?;
+            let mut entries: Vec<&LedgerEntry> = flok
+                .ledger
+                .iter()
+                .filter(|e| year.is_none_or(|y| e.date.day.year() == y))
+                .collect();
+            entries.sort_by_key(|e| e.date);
+            let money = |show: bool, amount: f64| {
+                if show {
+                    format!("{amount:.2}")
+                } else {
+                    String::new()
+                }
+            };
+            let rows: Vec<LedgerRow> = entries
+                .iter()
+                .map(|e| LedgerRow {
+                    date: e.date,
+                    category: e.category.name().to_string(),
+                    income: money(e.category.is_income(), e.amount),
+                    expense: money(!e.category.is_income(), e.amount),
+                    animal: e.animal.clone().unwrap_or_default(),
+                    description: e.description.clone(),
+                })
+                .collect();
+            print_rows(&rows, format, |r| {
+                format!(
+                    "{:<12} {:<14} {:>10} {:>10} {:<10} {}",
+                    r.date, r.category, r.income, r.expense, r.animal, r.description
+                )
+            })
+        }
+        Command::AddEntry {
+            file,
+            category,
+            amount,
+            animal,
+            date,
+            description,
+        } => {
+            let mut flok = load(&file)?;
+            if let Some(id) = &animal {
+                flok.find(id.clone())
+                    .ok_or_else(|| anyhow!("No animal {id}"))?;
+            }
+            if amount <= 0.0 {
+                return Err(anyhow!("The amount must be more than 0"));
+            }
+            flok.ledger.push(LedgerEntry {
+                date: parse_date(date)?,
+                category,
+                amount,
+                animal,
+                description,
+            });
+            flok_file::save(&file, &flok)
+        }
+        Command::RecordPrices { file } => {
+            let mut flok = load(&file)?;
+            let added = ledger::prices_from_events(&mut flok);
+            flok_file::save(&file, &flok)?;
+            println!("Added {added} prices");
+            Ok(())
+        }
+        Command::Pnl {
+            file,
+            by,
+            year,
+            format,
+        } => {
+            let flok = load(&file)?;
+            match by {
+                PnlBy::Animal => {
+                    print_rows(&ledger::animal_pnl(&flok, year), format, |p| {
+                        format!(
+                            "{:<10} income {:>10.2} expenses {:>10.2} share {:>10.2} \
+                             feed {:>10.2} profit {:>10.2}",
+                            p.id, p.income, p.expenses, p.allocated, p.feed, p.profit
+                        )
+                    })?;
+                    let unallocated = feed::feed_costs(&flok, year).unallocated;
+                    if unallocated > 0.0 {
+                        eprintln!(
+                            "{unallocated:.2} of feed was fed to groups with no members on hand \
+                             and is not in any animal's share"
+                        );
+                    }
+                    Ok(())
+                }
+                PnlBy::Crop => print_rows(&ledger::crop_pnl(&flok), format, |c| {
+                    format!(
+                        "{} {:>4} lambs profit {:>10.2} per lamb {:>8.2}",
+                        c.season, c.lambs, c.profit, c.per_lamb
+                    )
+                }),
+                PnlBy::Year => {
+                    let rows: Vec<YearRow> = ledger::year_pnl(&flok)
+                        .into_iter()
+                        .map(|y| YearRow {
+                            year: y.year,
+                            income: y.income,
+                            expenses: y.expenses,
+                            profit: y.profit,
+                        })
+                        .collect();
+                    print_rows(&rows, format, |y| {
+                        format!(
+                            "{} income {:>10.2} expenses {:>10.2} profit {:>10.2}",
+                            y.year, y.income, y.expenses, y.profit
+                        )
+                    })
+                }
+            }
+        }
+        Command::Contacts { file, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<ContactRow> = flok.contacts.iter().map(ContactRow::from).collect();
+            print_rows(&rows, format, |c| {
+                format!(
+                    "{:<20} {:<20} {:<14} {:<10} {}",
+                    c.name, c.farm, c.phone, c.premises_id, c.address
+                )
+            })
+        }
+        Command::AddContact {
+            file,
+            name,
+            farm,
+            address,
+            phone,
+            premises_id,
+        } => {
+            let mut flok = load(&file)?;
+            if name.trim().is_empty() {
+                return Err(anyhow!("The contact needs a name"));
+            }
+            if flok.contact(&name).is_some() {
+                return Err(anyhow!("There is already a contact called {name}"));
+            }
+            flok.contacts.push(Contact {
+                name: name.trim().to_string(),
+                farm,
+                address,
+                phone,
+                premises_id,
+            });
+            flok_file::save(&file, &flok)
+        }
+        Command::ContactHistory { file, name, format } => {
+            let flok = load(&file)?;
+            flok.contact(&name)
+                .ok_or_else(|| anyhow!("No contact {name}"))?;
+            print_rows(&contacts::history(&flok, &name), format, contacts::describe)
+        }
+        Command::LinkContacts { file } => {
+            let mut flok = load(&file)?;
+            let linked = contacts::link_contacts(&mut flok);
+            flok_file::save(&file, &flok)?;
+            println!("Linked {linked} events to contacts");
+            Ok(())
+        }
+        Command::Premises { file, premises_id } => {
+            let mut flok = load(&file)?;
+            match premises_id {
+                Some(premises_id) => {
+                    flok.premises_id = premises_id.trim().to_string();
+                    flok_file::save(&file, &flok)
+                }
+                None => {
+                    println!("{}", flok.premises_id);
+                    Ok(())
+                }
+            }
+        }
+        Command::OfficialId {
+            file,
+            id,
+            tag,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let date = parse_date(date)?;
+            let index = flok
+                .animals
+                .iter()
+                .position(|a| a.id.contains(&id))
+                .ok_or_else(|| anyhow!("No animal {id}"))?;
+            let mut animal = flok.animals[index].clone();
+            regulatory::record_official_id(&mut animal, &tag, date);
+            let errors = validate::check_animal(&flok, Some(index), &animal);
+            if !errors.is_empty() {
+                return Err(validate::validation_error(errors));
+            }
+            flok.animals[index] = animal;
+            flok_file::save(&file, &flok)
+        }
+        Command::Movements {
+            file,
+            from,
+            to,
+            html,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let from = from.map(|d| parse_date(Some(d))).transpose()?;
+            let to = to.map(|d| parse_date(Some(d))).transpose()?;
+            if html {
+                print!("{}", regulatory::movements_html(&flok, from, to));
+                return Ok(());
+            }
+            let rows: Vec<MovementRow> = regulatory::movements(&flok, from, to)
+                .into_iter()
+                .map(|m| MovementRow {
+                    date: m.date,
+                    direction: format!("{:?}", m.direction).to_lowercase(),
+                    missing_id: m.missing_id(),
+                    official_id: m.official_id.unwrap_or_default(),
+                    id: m.id,
+                    event: m.event,
+                    party: m.party,
+                    premises_id: m.premises_id,
+                })
+                .collect();
+            print_rows(&rows, format, |m| {
+                format!(
+                    "{:<12} {:<5} {:<16} {:<10} {:<16} {:<20} {}{}",
+                    m.date,
+                    m.direction,
+                    m.official_id,
+                    m.id,
+                    m.event,
+                    m.party,
+                    m.premises_id,
+                    if m.missing_id { " NO OFFICIAL ID" } else { "" }
+                )
+            })
+        }
+        Command::Groups { file, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<GroupRow> = flok
+                .groups
+                .iter()
+                .map(|g| GroupRow {
+                    name: g.name.clone(),
+                    members: g.members.join(" "),
+                })
+                .collect();
+            print_rows(&rows, format, |g| format!("{:<20} {}", g.name, g.members))
+        }
+        Command::AddGroup {
+            file,
+            name,
+            members,
+        } => {
+            let mut flok = load(&file)?;
+            if name.trim().is_empty() {
+                return Err(anyhow!("The group needs a name"));
+            }
+            if let Some(id) = members.iter().find(|id| flok.find((*id).clone()).is_none()) {
+                return Err(anyhow!("No animal {id}"));
+            }
+            match flok
+                .groups
+                .iter_mut()
+                .find(|g| g.name.trim().eq_ignore_ascii_case(name.trim()))
+            {
+                Some(group) => {
+                    for id in members {
+                        if !group.members.contains(&id) {
+                            group.members.push(id);
+                        }
+                    }
+                }
+                None => flok.groups.push(groups::Group {
+                    name: name.trim().to_string(),
+                    members,
+                }),
+            }
+            flok_file::save(&file, &flok)
+        }
+        Command::Pastures { file, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<PastureRow> = flok.pastures.iter().map(PastureRow::from).collect();
+            print_rows(&rows, format, |p| format!("{:<20} {}", p.name, p.notes))
+        }
+        Command::AddPasture {
+            file,
+            name,
+            notes,
+            acres,
+            rest_days,
+        } => {
+            let mut flok = load(&file)?;
+            if name.trim().is_empty() {
+                return Err(anyhow!("The pasture needs a name"));
+            }
+            if groups::pasture(&flok, &name).is_some() {
+                return Err(anyhow!("There is already a pasture called {name}"));
+            }
+            flok.pastures.push(groups::Pasture {
+                name: name.trim().to_string(),
+                notes,
+                acres,
+                rest_days,
+            });
+            flok_file::save(&file, &flok)
+        }
+        Command::GroupEvent {
+            file,
+            group,
+            name,
+            value,
+            date,
+            notes,
+        } => {
+            let mut flok = load(&file)?;
+            let event = Event {
+                name,
+                value,
+                date: parse_date(date)?,
+                notes,
+                contact: None,
+            };
+            let errors = validate::check_event(&event);
+            if !errors.is_empty() {
+                return Err(validate::validation_error(errors));
+            }
+            if event.name.trim().eq_ignore_ascii_case(groups::MOVE_EVENT)
+                && groups::pasture(&flok, &event.value).is_none()
+            {
+                return Err(anyhow!("No pasture {}", event.value));
+            }
+            let recorded = groups::record(&mut flok, &group, &event)?;
+            flok_file::save(&file, &flok)?;
+            println!("Recorded for {recorded} animals");
+            Ok(())
+        }
+        Command::Move {
+            file,
+            who,
+            pasture,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let pasture = groups::pasture(&flok, &pasture)
+                .ok_or_else(|| anyhow!("No pasture {pasture}"))?
+                .name
+                .clone();
+            let event = Event {
+                name: groups::MOVE_EVENT.to_string(),
+                value: pasture,
+                date: parse_date(date)?,
+                ..Default::default()
+            };
+            if groups::group(&flok, &who).is_some() {
+                let moved = groups::record(&mut flok, &who, &event)?;
+                println!("Moved {moved} animals");
+            } else {
+                let index = flok
+                    .animals
+                    .iter()
+                    .position(|a| a.id.contains(&who))
+                    .ok_or_else(|| anyhow!("No group or animal {who}"))?;
+                let mut animal = flok.animals[index].clone();
+                animal.events.push(event);
+                let errors = validate::check_animal(&flok, Some(index), &animal);
+                if !errors.is_empty() {
+                    return Err(validate::validation_error(errors));
+                }
+                flok.animals[index] = animal;
+            }
+            flok_file::save(&file, &flok)
+        }
+        Command::Where { file, date, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<WhereRow> = groups::whereabouts(&flok, parse_date(date)?)
+                .into_iter()
+                .flat_map(|p| {
+                    p.animals.into_iter().map(move |(id, since)| WhereRow {
+                        pasture: p.pasture.clone(),
+                        id,
+                        since,
+                    })
+                })
+                .collect();
+            print_rows(&rows, format, |w| {
+                format!(
+                    "{:<20} {:<10} {}",
+                    if w.pasture.is_empty() {
+                        "(not moved)"
+                    } else {
+                        &w.pasture
+                    },
+                    w.id,
+                    w.since.map(|d| d.to_string()).unwrap_or_default()
+                )
+            })
+        }
+        Command::Grazing { file, date, format } => {
+            let flok = load(&file)?;
+            let date = parse_date(date)?;
+            let rows = grazing::grazing(&flok, grazing::year_of(date).0, date);
+            print_rows(&rows, format, |g| {
+                format!(
+                    "{:<20} {:>3} animals {:>4} days {:>8.1} AUD  rested {:<12} target {}",
+                    g.pasture,
+                    g.animals_now,
+                    g.occupied_days,
+                    g.animal_unit_days,
+                    g.rested
+                        .map(|d| format!("{d} days"))
+                        .unwrap_or_else(|| "never grazed".to_string()),
+                    g.rest_target
+                )
+            })?;
+            if format == OutputFormat::Text {
+                if let Some(s) = grazing::suggest(&rows) {
+                    println!("{}", s.describe(&rows));
+                }
+            }
+            Ok(())
+        }
+        Command::FeedInventory { file, date, format } => {
+            let flok = load(&file)?;
+            print_rows(&feed::inventory(&flok, parse_date(date)?), format, |s| {
+                format!(
+                    "{:<20} {:>8.1} {:<8} left {}",
+                    s.feed,
+                    s.remaining,
+                    s.unit,
+                    match (s.per_day, s.days_left) {
+                        (Some(per_day), Some(days)) => {
+                            format!("{per_day:.1} a day, {days:.0} days left")
+                        }
+                        _ => "not being fed".to_string(),
+                    }
+                )
+            })
+        }
+        Command::AddFeedPurchase {
+            file,
+            feed,
+            quantity,
+            cost,
+            unit,
+            kind,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let purchase = feed::FeedPurchase {
+                date: parse_date(date)?,
+                feed: feed.trim().to_string(),
+                kind,
+                quantity,
+                unit: unit.trim().to_string(),
+                cost,
+            };
+            let errors = validate::check_feed_purchase(&purchase);
+            if !errors.is_empty() {
+                return Err(validate::validation_error(errors));
+            }
+            flok.feed_purchases.push(purchase);
+            flok_file::save(&file, &flok)
+        }
+        Command::AddFeeding {
+            file,
+            group,
+            feed,
+            quantity,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let feeding = feed::Feeding {
+                date: parse_date(date)?,
+                group: group.trim().to_string(),
+                feed: feed.trim().to_string(),
+                quantity,
+            };
+            let errors = validate::check_feeding(&flok, &feeding);
+            if !errors.is_empty() {
+                return Err(validate::validation_error(errors));
+            }
+            flok.feedings.push(feeding);
+            flok_file::save(&file, &flok)
+        }
+        Command::Products { file, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<ProductRow> = flok.products.iter().map(ProductRow::from).collect();
+            print_rows(&rows, format, |p| {
+                format!(
+                    "{:<20} {:<10} meat {:>3} days, milk {:>3} days {}",
+                    p.name,
+                    p.kind,
+                    p.meat_withdrawal,
+                    p.milk_withdrawal,
+                    p.dose_per_kg
+                        .map(|d| format!("{d} ml/kg"))
+                        .unwrap_or_default()
+                )
+            })
+        }
+        Command::AddProduct {
+            file,
+            name,
+            kind,
+            meat,
+            milk,
+            dose_per_kg,
+        } => {
+            let mut flok = load(&file)?;
+            let product = Product {
+                name: name.trim().to_string(),
+                kind,
+                meat_withdrawal: meat,
+                milk_withdrawal: milk,
+                dose_per_kg,
+            };
+            match flok
+                .products
+                .iter_mut()
+                .find(|p| p.name.trim().eq_ignore_ascii_case(&product.name))
+            {
+                Some(p) => *p = product,
+                None => flok.products.push(product),
+            }
+            flok_file::save(&file, &flok)
+        }
+        Command::Withdrawal { file, date, format } => {
+            let flok = load(&file)?;
+            let date = parse_date(date)?;
+            let show = |d: Option<Date>| d.map(|d| d.to_string()).unwrap_or_default();
+            print_rows(&treatment::in_withdrawal(&flok, date), format, |w| {
+                format!(
+                    "{:<10} meat clear {:<12} milk clear {:<12} {}",
+                    w.id,
+                    show(w.meat_clear),
+                    show(w.milk_clear),
+                    w.last_treatment.clone().unwrap_or_default()
+                )
+            })
+        }
+        Command::Protocols { file, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<ProtocolRow> = flok
+                .protocols
+                .iter()
+                .map(|p| ProtocolRow {
+                    name: p.name.clone(),
+                    treatment: p.treatment.clone(),
+                    schedule: p.schedule.describe(),
+                    target: p.target.describe(),
+                    months: p
+                        .months
+                        .iter()
+                        .map(|m| m.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                })
+                .collect();
+            print_rows(&rows, format, |p| {
+                format!(
+                    "{:<20} {:<12} {:<40} {} {}",
+                    p.name, p.treatment, p.schedule, p.target, p.months
+                )
+            })
+        }
+        Command::Due {
+            file,
+            group,
+            date,
+            format,
+        } => {
+            let flok = load(&file)?;
+            let member = member_of(&flok, &group)?;
+            let date = parse_date(date)?;
+            let mut due = health::due(&flok, date);
+            due.retain(|d| member(&d.id));
+            print_rows(&due, format, |d| {
+                format!(
+                    "{:<8} {:<12} {:<10} {:<20} {}",
+                    d.status.name(),
+                    d.due,
+                    d.id,
+                    d.protocol,
+                    d.treatment
+                )
+            })
+        }
+        Command::RecordDue {
+            file,
+            protocol,
+            ids,
+            date,
+        } => {
+            let mut flok = load(&file)?;
+            let date = parse_date(date)?;
+            if !flok
+                .protocols
+                .iter()
+                .any(|p| p.name.eq_ignore_ascii_case(&protocol))
+            {
+                return Err(anyhow!("No protocol {protocol}"));
+            }
+            let due: Vec<health::DueTreatment> = health::due(&flok, date)
+                .into_iter()
+                .filter(|d| d.protocol.eq_ignore_ascii_case(&protocol))
+                .filter(|d| ids.is_empty() || ids.contains(&d.id))
+                .collect();
+            let recorded = health::record(&mut flok, &due, date)?;
+            flok_file::save(&file, &flok)?;
+            println!("Recorded {recorded} treatments");
+            Ok(())
+        }
+        Command::Expose {
+            file,
+            ram,
+            ewes,
+            from,
+            to,
+            name,
+        } => {
+            let mut flok = load(&file)?;
+            for id in ewes.iter().chain([&ram]) {
+                flok.find(id.clone())
+                    .ok_or_else(|| anyhow!("No animal {id}"))?;
+            }
+            let exposure = breeding::Exposure {
+                name,
+                ram,
+                ewes,
+                from: parse_date(from)?,
+                to: to.map(|to| parse_date(Some(to))).transpose()?,
+            };
+            flok.exposures.push(exposure);
+            flok_file::save(&file, &flok)
+        }
+        Command::Lambing { file, format } => {
+            let flok = load(&file)?;
+            let rows: Vec<LambingRow> = breeding::lambings(&flok)
+                .into_iter()
+                .map(|l| LambingRow {
+                    ewe: l.ewe,
+                    expected: l.expected,
+                    earliest: l.earliest,
+                    latest: l.latest,
+                    ram: l.ram.unwrap_or_default(),
+                    lambs: l.lambs,
+                    basis: l.basis.describe(),
+                })
+                .collect();
+            print_rows(&rows, format, |l| {
+                format!(
+                    "{:<10} {:<12} {} to {} {:<10} {:<3} {}",
+                    l.ewe,
+                    l.expected,
+                    l.earliest,
+                    l.latest,
+                    l.ram,
+                    l.lambs.map(|n| n.to_string()).unwrap_or_default(),
+                    l.basis
+                )
+            })
+        }
+        Command::InferSires { file } => {
+            let mut flok = load(&file)?;
+            let lambs = breeding::infer_sires(&mut flok);
+            for lamb in lambs.iter().filter_map(|id| flok.find(id.clone())) {
+                let id = lamb.id.first().cloned().unwrap_or_default();
+                println!("{id} sired by {}", lamb.sire.clone().unwrap_or_default());
+            }
+            flok_file::save(&file, &flok)?;
+            println!("Inferred {} sires", lambs.len());
+            Ok(())
+        }
+        Command::Export {
+            file,
+            format,
+            output,
+            columns,
+            pivot,
+            events,
+            lambing,
+            sales,
+        } => {
+            let flok = load(&file)?;
+            let options = ExportOptions {
+                columns: if columns.is_empty() {
+                    Column::ALL.to_vec()
+                } else {
+                    columns
+                },
+                pivot: if pivot.iter().any(|p| p == "all") {
+                    export::event_names(&flok)
+                } else {
+                    pivot
+                },
+                events,
+                lambing,
+                sales,
+            };
+            // stdout takes one sheet of CSV, or the whole flock as JSON
+            if output.is_none() && (events || lambing || sales) {
+                return Err(anyhow!(
+                    "--events, --lambing and --sales add sheets, which need --output"
+                ));
+            }
+            match (output, format) {
+                (Some(output), _) => export::export(&flok, &options, &output),
+                (None, OutputFormat::Text) => Err(anyhow!(
+                    "Export writes JSON or CSV, or a spreadsheet with --output"
+                )),
+                (None, OutputFormat::Json) => {
+                    serde_json::to_writer_pretty(std::io::stdout(), &flok)?;
+                    println!();
+                    Ok(())
+                }
+                (None, OutputFormat::Csv) => {
+                    export::write_csv(&export::sheets(&flok, &options)[0], std::io::stdout())
+                }
+            }
+        }
+        Command::Report {
+            file,
+            report,
+            output,
+            id,
+        } => {
+            let flok = load(&file)?;
+            let contents = match report {
+                ReportKind::Lineage => report::lineage_svg(&flok)?,
+                ReportKind::Growth => {
+                    let id = id.ok_or_else(|| anyhow!("Which animal? Give --id"))?;
+                    let animal = flok
+                        .find(id.clone())
+                        .ok_or_else(|| anyhow!("No animal {id}"))?;
+                    growth::growth_svg(animal, flok.weight_unit)?
+                }
+                ReportKind::LambingCalendar => breeding::calendar_html(&flok),
+                ReportKind::ProfitAndLoss => ledger::pnl_html(&flok),
+                ReportKind::GrazingTimeline => {
+                    let (from, to) = grazing::year_of(Date::today());
+                    grazing::timeline_svg(&flok, from, to)
+                }
+            };
+            output_to(output)?.write_all(contents.as_bytes())?;
+            Ok(())
+        }
+        Command::Validate { file, format } => {
+            let flok = load(&file)?;
+            let problems: Vec<Problem> = validate::check_flok(&flok)
+                .into_iter()
+                .map(|(id, e)| Problem {
+                    id,
+                    field: e.field.to_string(),
+                    message: e.message,
+                })
+                .collect();
+            print_rows(&problems, format, |p| {
+                format!("{:<10} {:<10} {}", p.id, p.field, p.message)
+            })?;
+            if problems.is_empty() {
+                Ok(())
+            } else {
+                Err(anyhow!("{} problems found", problems.len()))
+            }
+        }
+        Command::Stats { file, format } => {
+            let flok = load(&file)?;
+            print_rows(&stats(&flok), format, |s| {
+                format!("{:<24} {}", s.name, s.count)
+            })
+        }
+        Command::History { file, id, format } => {
+            let entries = journal::read(&file)?;
+            let rows: Vec<HistoryRow> = journal::history(&entries, &id)
+                .into_iter()
+                .map(|e| HistoryRow {
+                    time: e.time,
+                    user: e.user.clone(),
+                    change: e.change.describe().join("; "),
+                })
+                .collect();
+            print_rows(&rows, format, |r| {
+                format!("{:<20} {:<10} {}", r.time, r.user, r.change)
+            })
+        }
+        Command::AsOf { file, date, output } => {
+            let date = Date::parse(&date).ok_or_else(|| anyhow!("\"{date}\" is not a date"))?;
+            let flok = journal::as_of(&journal::read(&file)?, date);
+            serde_json::to_writer_pretty(output_to(output)?, &flok)?;
+            Ok(())
+        }
+        Command::Convert { from, to, decrypt } => storage::convert(&from, &to, decrypt),
+        Command::Merge {
+            base,
+            mine,
+            theirs,
+            output,
+            prefer,
+        } => {
+            let output = output.unwrap_or(mine.clone());
+            let merged = merge::merge(&load(&base)?, &load(&mine)?, &load(&theirs)?);
+            let Some(flok) = resolve(merged, prefer)? else {
+                return Ok(());
+            };
+            flok_file::save(&output, &flok)
+        }
+        Command::Sync {
+            file,
+            server,
+            name,
+            prefer,
+        } => {
+            let mut flok = load(&file)?;
+            if let (Some(server), Some(name)) = (server, name) {
+                sync::share(&mut flok, &server, &name);
+            }
+            loop {
+                let report = sync::sync(&mut flok)?;
+                let Some(unsettled) = report.conflicts else {
+                    flok_file::save(&file, &flok)?;
+                    println!("{}", report.describe());
+                    return Ok(());
+                };
+                let Some(resolved) = resolve(unsettled.merged, prefer)? else {
+                    return Err(anyhow!("Nothing was synced"));
+                };
+                sync::settle(&mut flok, resolved, unsettled.revision);
+            }
+        }
+    }
+}
+
+/// The merged flock with each conflict settled as `prefer` says, `None` if the choice was
+/// cancelled.
+fn resolve(mut merged: merge::Merged, prefer: Prefer) -> Result<Option<Flok>> {
+    for c in &merged.conflicts {
+        eprintln!(
+            "{}: mine \"{}\", theirs \"{}\"",
+            c.label(),
+            c.show(false),
+            c.show(true)
+        );
+    }
+    if prefer == Prefer::Ask && !merged.conflicts.is_empty() {
+        return merge_form::resolve(merged);
+    }
+    for c in &merged.conflicts {
+        c.resolve(&mut merged.flok, prefer == Prefer::Theirs);
+    }
+    Ok(Some(merged.flok))
+}
+
+fn load(file: &str) -> Result<Flok> {
+    let flok = flok_file::load(file)?;
+    crate::date::set_format(flok.date_format);
+    Ok(flok)
+}
+
+/// Whether the animal with an id is in `group`, true for every animal without a group.
+fn member_of<'a>(flok: &'a Flok, group: &Option<String>) -> Result<impl Fn(&Id) -> bool + 'a> {
+    let group = group
+        .as_ref()
+        .map(|name| groups::group(flok, name).ok_or_else(|| anyhow!("No group {name}")))
+        .transpose()?;
+    Ok(move |id: &Id| group.is_none_or(|g| flok.find(id.clone()).is_some_and(|a| g.contains(a))))
+}
+
+/// A date given on the command line, today if none was.
+fn parse_date(date: Option<String>) -> Result<Date> {
+    match date {
+        None => Ok(Date::today()),
+        Some(d) => Date::parse(&d).ok_or_else(|| anyhow!("\"{d}\" is not a date")),
+    }
+}
+
+fn output_to(output: Option<String>) -> Result<Box<dyn Write>> {
+    Ok(match output {
+        Some(file) => Box::new(std::fs::File::create(file)?),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+fn print_rows<T: Serialize>(
+    rows: &[T],
+    format: OutputFormat,
+    text: impl Fn(&T) -> String,
+) -> Result<()> {
+    write_rows(rows, format, text, std::io::stdout())
+}
+
+fn write_rows<T: Serialize>(
+    rows: &[T],
+    format: OutputFormat,
+    text: impl Fn(&T) -> String,
+    mut out: impl Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for r in rows {
+                writeln!(out, "{}", text(r))?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, rows)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(out);
+            for r in rows {
+                csv.serialize(r)?;
+            }
+            csv.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn stats(flok: &Flok) -> Vec<Stat> {
+    let mut stats = vec![
+        Stat {
+            name: "animals".to_string(),
+            count: flok.animals.len(),
+        },
+        Stat {
+            name: "alive".to_string(),
+            count: flok.animals.iter().filter(|a| a.died().is_none()).count(),
+        },
+    ];
+    for sex in Sex::VARIANTS {
+        stats.push(Stat {
+            name: sex.name().to_lowercase(),
+            count: flok.animals.iter().filter(|a| a.sex == *sex).count(),
+        });
+    }
+    stats.push(Stat {
+        name: "events".to_string(),
+        count: flok.animals.iter().map(|a| a.events.len()).sum(),
+    });
+    let mut born = BTreeMap::new();
+    for year in flok.animals.iter().filter_map(|a| a.born) {
+        *born.entry(year.day.format("%Y").to_string()).or_insert(0) += 1;
+    }
+    stats.extend(born.into_iter().map(|(year, count)| Stat {
+        name: format!("born {year}"),
+        count,
+    }));
+    stats
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn csv<T: Serialize>(rows: &[T]) -> String {
+        let mut out = vec![];
+        write_rows(rows, OutputFormat::Csv, |_| String::new(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_columns() {
+        let event = |name: &str, contact: Option<&str>| Event {
+            name: name.to_string(),
+            date: Date::parse("2024-05-01").unwrap(),
+            contact: contact.map(str::to_string),
+            ..Default::default()
+        };
+        let events = [event("bought", Some("Smith")), event("weaned", None)];
+        let rows: Vec<EventRow> = events.iter().map(EventRow::from).collect();
+        assert_eq!(
+            csv(&rows),
+            "name,value,date,notes,contact\n\
+             bought,,2024-05-01,,Smith\n\
+             weaned,,2024-05-01,,\n"
+        );
+
+        let contacts = [
+            Contact {
+                name: "Smith".to_string(),
+                phone: "555-1234".to_string(),
+                ..Default::default()
+            },
+            Contact {
+                name: "Jones".to_string(),
+                farm: "Hill Farm".to_string(),
+                ..Default::default()
+            },
+        ];
+        let rows: Vec<ContactRow> = contacts.iter().map(ContactRow::from).collect();
+        assert_eq!(
+            csv(&rows),
+            "name,farm,address,phone,premises_id\n\
+             Smith,,,555-1234,\n\
+             Jones,Hill Farm,,,\n"
+        );
+
+        let products = [
+            Product {
+                name: "Valbazen".to_string(),
+                meat_withdrawal: 7,
+                dose_per_kg: Some(0.2),
+                ..Default::default()
+            },
+            Product {
+                name: "CD&T".to_string(),
+                kind: ProductKind::Vaccine,
+                meat_withdrawal: 21,
+                ..Default::default()
+            },
+        ];
+        let rows: Vec<ProductRow> = products.iter().map(ProductRow::from).collect();
+        assert_eq!(
+            csv(&rows),
+            "name,kind,meat_withdrawal,milk_withdrawal,dose_per_kg\n\
+             Valbazen,Wormer,7,0,0.2\n\
+             CD&T,Vaccine,21,0,\n"
+        );
+
+        let pastures = [
+            groups::Pasture {
+                name: "North".to_string(),
+                acres: Some(5.0),
+                rest_days: Some(30),
+                ..Default::default()
+            },
+            groups::Pasture {
+                name: "Creek".to_string(),
+                notes: "wet in spring".to_string(),
+                ..Default::default()
+            },
+        ];
+        let rows: Vec<PastureRow> = pastures.iter().map(PastureRow::from).collect();
+        assert_eq!(
+            csv(&rows),
+            "name,notes,acres,rest_days\n\
+             North,,5.0,30\n\
+             Creek,wet in spring,,\n"
+        );
+    }
+}
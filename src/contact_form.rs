@@ -0,0 +1,173 @@
+use fltk::{
+    browser::{Browser, HoldBrowser},
+    button::Button,
+    frame::Frame,
+    group::Flex,
+    input::Input,
+    prelude::{BrowserExt, GroupExt, InputExt, WidgetBase, WidgetExt, WindowExt},
+    widget::Widget,
+    window::Window,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    business_obj::BusinessObject,
+    contacts,
+    flok::*,
+    form::*,
+    list_editor::{show_list_editor, ItemForm},
+};
+
+/// The fields of one contact.
+#[derive(Clone)]
+pub struct ContactForm {
+    pub name: Input,
+    pub farm: Input,
+    pub address: Input,
+    pub phone: Input,
+    pub premises_id: Input,
+}
+
+impl ItemForm<Contact> for ContactForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let form = Self {
+            name: Input::default(),
+            farm: Input::default(),
+            address: Input::default(),
+            phone: Input::default(),
+            premises_id: Input::default(),
+        };
+        let ui = create_form(vec![
+            ("Name", &form.name),
+            ("Farm", &form.farm),
+            ("Address", &form.address),
+            ("Phone", &form.phone),
+            ("Premises ID", &form.premises_id),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, c: &Contact) {
+        self.name.set_value(&c.name);
+        self.farm.set_value(&c.farm);
+        self.address.set_value(&c.address);
+        self.phone.set_value(&c.phone);
+        self.premises_id.set_value(&c.premises_id);
+    }
+
+    fn item(&mut self, flok: &Flok, index: Option<usize>) -> Result<Contact, Vec<FieldError>> {
+        let mut errors = vec![];
+        let name = self.name.value().trim().to_string();
+        if name.is_empty() {
+            errors.push(FieldError::new("Name", "Name is empty"));
+        } else if flok
+            .contacts
+            .iter()
+            .enumerate()
+            .any(|(i, c)| Some(i) != index && c.name.trim().eq_ignore_ascii_case(&name))
+        {
+            errors.push(FieldError::new(
+                "Name",
+                format!("There is already a {name}"),
+            ));
+        } else if let Some(old) = index.and_then(|i| flok.contacts.get(i)) {
+            // events refer to a contact by name
+            if !old.name.trim().eq_ignore_ascii_case(&name)
+                && flok.animals.iter().flat_map(|a| &a.events).any(|e| {
+                    e.contact
+                        .as_deref()
+                        .is_some_and(|c| c.trim().eq_ignore_ascii_case(old.name.trim()))
+                })
+            {
+                errors.push(FieldError::new(
+                    "Name",
+                    format!("Events refer to {}, add a new contact instead", old.name),
+                ));
+            }
+        }
+        highlight(&mut self.name, "Name", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Contact {
+            name,
+            farm: self.farm.value().trim().to_string(),
+            address: self.address.value().trim().to_string(),
+            phone: self.phone.value().trim().to_string(),
+            premises_id: self.premises_id.value().trim().to_string(),
+        })
+    }
+
+    fn replaced(flok: &mut Flok, before: &Contact, after: &Contact) {
+        contacts::rename(flok, &before.name, &after.name);
+    }
+
+    fn in_use(flok: &Flok, contact: &Contact) -> Option<String> {
+        let events = contacts::references(flok, &contact.name);
+        (events > 0).then(|| format!("{} is the contact of {events} events", contact.name))
+    }
+}
+
+/// Edit the people animals are bought from and sold to.
+pub fn show_contacts(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Contact, ContactForm>(
+        "Contacts",
+        flok,
+        |f| &mut f.contacts,
+        |c| {
+            if c.farm.is_empty() {
+                c.name.clone()
+            } else {
+                format!("{} ({})", c.name, c.farm)
+            }
+        },
+    )
+}
+
+/// The contacts beside every animal bought from or sold to the one selected.
+pub fn show_history(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    let mut wind = Window::default()
+        .with_size(650, 350)
+        .with_label("Contact history");
+    let mut page = Flex::default_fill().column();
+    let mut panes = Flex::default().row();
+    let mut list = HoldBrowser::default();
+    panes.fixed(&list, 200);
+    let mut deals = Browser::default();
+    panes.end();
+    let names: Vec<String> = flok.exec(|f| f.contacts.iter().map(|c| c.name.clone()).collect());
+    for name in &names {
+        // '@' starts a formatting code
+        list.add(&name.replace('@', "@@"));
+    }
+
+    let mut buttons = Flex::default().row();
+    buttons.resizable(&Frame::default());
+    {
+        let mut close = Button::default().with_label("Close");
+        let mut wind = wind.clone();
+        close.set_callback(move |_| wind.hide());
+        buttons.fixed(&close, 60);
+    }
+    buttons.end();
+    page.fixed(&buttons, 25);
+    page.end();
+
+    list.set_callback(move |l| {
+        deals.clear();
+        if let Some(name) = names.get((l.value() as usize).wrapping_sub(1)) {
+            let history = flok.exec(|f| contacts::history(f, name));
+            if history.is_empty() {
+                deals.add("No animals bought or sold");
+            }
+            for deal in &history {
+                deals.add(&contacts::describe(deal).replace('@', "@@"));
+            }
+        }
+    });
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+
+use crate::{flok::*, ledger::money};
+
+/// An animal bought from or sold to a contact.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Deal {
+    pub id: Id,
+    /// "bought" or "sold"
+    pub deal: &'static str,
+    pub date: Date,
+    pub price: Option<f64>,
+}
+
+/// Every animal bought from or sold to the contact called `name`, by date.
+pub fn history(flok: &Flok, name: &str) -> Vec<Deal> {
+    let mut deals: Vec<Deal> = vec![];
+    for a in &flok.animals {
+        let id = a.id.first().cloned().unwrap_or_default();
+        for e in a.events.iter().filter(|e| is_contact(e, name)) {
+            let deal = if e.is_sale() {
+                "sold"
+            } else if e.is_purchase() {
+                "bought"
+            } else {
+                continue;
+            };
+            // the sheet keeps the date of a purchase as the value of a "purchase date" event
+            let date = Date::parse(&e.value)
+                .filter(|_| e.name.to_lowercase().contains("date"))
+                .unwrap_or(e.date);
+            match deals.iter_mut().find(|d| d.id == id && d.deal == deal) {
+                Some(d) => {
+                    d.date = d.date.min(date);
+                    d.price = d.price.or(money(&e.value));
+                }
+                None => deals.push(Deal {
+                    id: id.clone(),
+                    deal,
+                    date,
+                    price: money(&e.value),
+                }),
+            }
+        }
+    }
+    deals.sort_by_key(|d| d.date);
+    deals
+}
+
+fn is_contact(e: &Event, name: &str) -> bool {
+    e.contact
+        .as_deref()
+        .is_some_and(|c| c.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// How many events name the contact called `name`.
+pub fn references(flok: &Flok, name: &str) -> usize {
+    flok.animals
+        .iter()
+        .flat_map(|a| &a.events)
+        .filter(|e| is_contact(e, name))
+        .count()
+}
+
+/// Point the events naming the contact called `from` at `to`, after it is renamed.
+pub fn rename(flok: &mut Flok, from: &str, to: &str) {
+    for e in flok.animals.iter_mut().flat_map(|a| &mut a.events) {
+        if is_contact(e, from) {
+            e.contact = Some(to.to_string());
+        }
+    }
+}
+
+/// Point purchase and sale events without a contact at the contact named in their value or
+/// notes, like "Sold as a teaser to Joe Baldwin". Returns how many were linked.
+pub fn link_contacts(flok: &mut Flok) -> usize {
+    let names: Vec<String> = flok
+        .contacts
+        .iter()
+        .map(|c| c.name.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .collect();
+    let mut linked = 0;
+    for e in flok
+        .animals
+        .iter_mut()
+        .flat_map(|a| a.events.iter_mut())
+        .filter(|e| e.contact.is_none() && (e.is_sale() || e.is_purchase()))
+    {
+        let text = format!("{} {}", e.value, e.notes).to_lowercase();
+        if let Some(name) = names.iter().find(|n| text.contains(&n.to_lowercase())) {
+            e.contact = Some(name.clone());
+            linked += 1;
+        }
+    }
+    linked
+}
+
+/// One line describing a deal, for lists.
+pub fn describe(deal: &Deal) -> String {
+    let price = deal
+        .price
+        .map(|p| format!(" for {p:.2}"))
+        .unwrap_or_default();
+    format!("{} {} {}{price}", deal.date, deal.deal, deal.id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(name: &str, value: &str, notes: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            value: value.to_string(),
+            notes: notes.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn link_and_history() {
+        let animal = |id: &str, events| Animal {
+            id: vec![id.to_string()],
+            events,
+            ..Default::default()
+        };
+        let mut flok = Flok {
+            contacts: vec![
+                Contact {
+                    name: "Joe Baldwin".to_string(),
+                    ..Default::default()
+                },
+                Contact {
+                    name: "Gary Janes".to_string(),
+                    ..Default::default()
+                },
+            ],
+            animals: vec![
+                animal(
+                    "1",
+                    vec![
+                        event("purchase price", "$250", "from gary janes", "2025-01-01"),
+                        event("purchase date", "2021-05-01", "gary janes", "2025-01-01"),
+                        event("sold", "", "Sold as a teaser to Joe Baldwin", "2025-06-01"),
+                    ],
+                ),
+                animal(
+                    "2",
+                    vec![
+                        event("sale $", "300", "Joe Baldwin", "2024-10-01"),
+                        event("note", "", "Joe Baldwin liked her", "2024-09-01"),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(link_contacts(&mut flok), 4);
+        assert_eq!(link_contacts(&mut flok), 0);
+        assert_eq!(flok.animals[1].events[1].contact, None);
+
+        let deals: Vec<(String, &str, String, Option<f64>)> = history(&flok, "joe baldwin")
+            .into_iter()
+            .map(|d| (d.id, d.deal, d.date.to_iso(), d.price))
+            .collect();
+        assert_eq!(
+            deals,
+            vec![
+                (
+                    "2".to_string(),
+                    "sold",
+                    "2024-10-01".to_string(),
+                    Some(300.0)
+                ),
+                ("1".to_string(), "sold", "2025-06-01".to_string(), None),
+            ]
+        );
+        let bought = history(&flok, "Gary Janes");
+        assert_eq!(bought.len(), 1);
+        assert_eq!(bought[0].date.to_iso(), "2021-05-01");
+        assert_eq!(bought[0].price, Some(250.0));
+
+        assert_eq!(references(&flok, "Joe Baldwin"), 2);
+        rename(&mut flok, "joe baldwin", "Joseph Baldwin");
+        assert_eq!(references(&flok, "Joe Baldwin"), 0);
+        assert_eq!(history(&flok, "Joseph Baldwin").len(), 2);
+    }
+}
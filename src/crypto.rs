@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{LazyLock, Mutex, OnceLock, RwLock},
+};
+
+/// Encrypted files are MAGIC, a salt for the passphrase, a nonce and ChaCha20-Poly1305 ciphertext.
+/// The key is derived from the passphrase with Argon2id.
+const MAGIC: &[u8] = b"FLOKENC1";
+const SALT: usize = 16;
+const NONCE: usize = 12;
+
+/// The passphrase files are read and written with. Files are saved encrypted while it is set.
+static PASSPHRASE: RwLock<Option<String>> = RwLock::new(None);
+
+type KeyCache = HashMap<(String, [u8; SALT]), Key>;
+
+/// Argon2 is slow on purpose, so derive each key once.
+static KEYS: LazyLock<Mutex<KeyCache>> = LazyLock::new(Default::default);
+
+/// One salt per run, so journal lines share a key.
+static SESSION_SALT: OnceLock<[u8; SALT]> = OnceLock::new();
+
+/// Environment variable the command line reads the passphrase from.
+pub const PASSPHRASE_VAR: &str = "FLOK_PASSPHRASE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassphraseError {
+    Missing,
+    Wrong,
+}
+
+impl Display for PassphraseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PassphraseError::Missing => write!(f, "The file is encrypted, a passphrase is needed"),
+            PassphraseError::Wrong => write!(f, "Wrong passphrase, or the file is damaged"),
+        }
+    }
+}
+
+impl std::error::Error for PassphraseError {}
+
+pub fn set_passphrase(passphrase: Option<String>) {
+    *PASSPHRASE.write().unwrap() = passphrase.filter(|p| !p.is_empty());
+}
+
+pub fn passphrase() -> Option<String> {
+    PASSPHRASE.read().unwrap().clone()
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn key(passphrase: &str, salt: &[u8; SALT]) -> Result<Key> {
+    let mut keys = KEYS.lock().unwrap();
+    if let Some(key) = keys.get(&(passphrase.to_string(), *salt)) {
+        return Ok(*key);
+    }
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Unable to derive key: {e}"))?;
+    keys.insert((passphrase.to_string(), *salt), key);
+    Ok(key)
+}
+
+pub fn encrypt(plain: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let salt = SESSION_SALT.get_or_init(|| {
+        let mut salt = [0; SALT];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    });
+    let cipher = ChaCha20Poly1305::new(&key(passphrase, salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let sealed = cipher
+        .encrypt(&nonce, plain)
+        .map_err(|_| anyhow!("Unable to encrypt"))?;
+    Ok([MAGIC, salt, nonce.as_slice(), &sealed].concat())
+}
+
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if data.len() < MAGIC.len() + SALT + NONCE || !is_encrypted(data) {
+        return Err(anyhow!("Not an encrypted flock"));
+    }
+    let (salt, rest) = data[MAGIC.len()..].split_at(SALT);
+    let (nonce, sealed) = rest.split_at(NONCE);
+    let cipher = ChaCha20Poly1305::new(&key(passphrase, salt.try_into()?)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), sealed)
+        .map_err(|_| PassphraseError::Wrong.into())
+}
+
+/// Decrypt with the current passphrase if `data` is encrypted.
+pub fn open(data: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    match passphrase() {
+        Some(p) => decrypt(&data, &p),
+        None => Err(PassphraseError::Missing.into()),
+    }
+}
+
+/// Encrypt with the current passphrase, if there is one.
+pub fn seal(data: Vec<u8>) -> Result<Vec<u8>> {
+    match passphrase() {
+        Some(p) => encrypt(&data, &p),
+        None => Ok(data),
+    }
+}
+
+/// `encrypt` for one line of text, as hex.
+pub fn encrypt_line(line: &str, passphrase: &str) -> Result<String> {
+    Ok(encrypt(line.as_bytes(), passphrase)?
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// `seal` for one line of text, as hex.
+pub fn seal_line(line: String) -> Result<String> {
+    match passphrase() {
+        Some(p) => encrypt_line(&line, &p),
+        None => Ok(line),
+    }
+}
+
+/// `open` for a line written by `seal_line`.
+pub fn open_line(line: &str) -> Result<String> {
+    let bytes: Option<Vec<u8>> = (0..line.len())
+        .step_by(2)
+        .map(|i| {
+            line.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect();
+    match bytes {
+        Some(bytes) if is_encrypted(&bytes) => Ok(String::from_utf8(open(bytes)?)?),
+        _ => Ok(line.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let plain = br#"{"name":"herd","buyer":"Jones"}"#;
+        let sealed = encrypt(plain, "correct horse").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.windows(5).any(|w| w == b"Jones"));
+        assert_eq!(decrypt(&sealed, "correct horse").unwrap(), plain);
+
+        let wrong = decrypt(&sealed, "battery staple").unwrap_err();
+        assert_eq!(
+            wrong.downcast_ref::<PassphraseError>(),
+            Some(&PassphraseError::Wrong)
+        );
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tampered, "correct horse").is_err());
+    }
+}
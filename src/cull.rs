@@ -0,0 +1,471 @@
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Days};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    breeding::{self, GESTATION_DAYS, GESTATION_SPREAD},
+    flok::*,
+    productivity::WEANING_DAYS,
+    report::{escape, open_in_browser},
+    scores, treatment,
+};
+
+/// The rules written when a flock has none yet, with the same values as `Rules::default()`.
+pub const DEFAULT_RULES: &str = r#"# Cull rules. Each rule adds points to an animal; those with at
+# least `threshold` points are listed, most points first. Set a rule's points to 0
+# to turn it off.
+threshold = 5.0
+
+# Points for each year older than `years`
+[age]
+years = 6
+points_per_year = 5.0
+
+# Ewes averaging fewer lambs than this per lambing
+[lambing]
+min_lambs_per_lambing = 1.5
+points = 5.0
+# For each season she was bred and didn't lamb
+missed_points = 10.0
+
+# For each of a ewe's lambs that died before weaning
+[survival]
+points_per_lamb = 3.0
+
+# For each FAMACHA score of `score` or worse
+[famacha]
+score = 4.0
+points = 3.0
+
+# Dewormed often enough to be flagged by the parasite report
+[deworming]
+points = 10.0
+
+# Treated with catalogue products `count` or more times in the year
+[treatments]
+count = 4
+points = 5.0
+
+# Events whose name, value or notes contain any of `words`, points for each. Replace these to
+# change them; `event = []` turns them all off.
+[[event]]
+words = ["mastitis", "hard bag", "lump"]
+points = 10.0
+
+[[event]]
+words = ["foot rot", "footrot", "foot scald", "lame"]
+points = 5.0
+
+[[event]]
+words = ["cull"]
+points = 5.0
+"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgeRule {
+    pub years: u32,
+    pub points_per_year: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LambingRule {
+    pub min_lambs_per_lambing: f64,
+    pub points: f64,
+    pub missed_points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SurvivalRule {
+    pub points_per_lamb: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FamachaRule {
+    pub score: f64,
+    pub points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DewormingRule {
+    pub points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TreatmentRule {
+    pub count: usize,
+    pub points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventRule {
+    pub words: Vec<String>,
+    pub points: f64,
+}
+
+/// How animals are scored for culling. Rules left out of the file keep their defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Rules {
+    pub threshold: f64,
+    pub age: AgeRule,
+    pub lambing: LambingRule,
+    pub survival: SurvivalRule,
+    pub famacha: FamachaRule,
+    pub deworming: DewormingRule,
+    pub treatments: TreatmentRule,
+    #[serde(rename = "event")]
+    pub events: Vec<EventRule>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+        Self {
+            threshold: 5.0,
+            age: AgeRule {
+                years: 6,
+                points_per_year: 5.0,
+            },
+            lambing: LambingRule {
+                min_lambs_per_lambing: 1.5,
+                points: 5.0,
+                missed_points: 10.0,
+            },
+            survival: SurvivalRule {
+                points_per_lamb: 3.0,
+            },
+            famacha: FamachaRule {
+                score: 4.0,
+                points: 3.0,
+            },
+            deworming: DewormingRule { points: 10.0 },
+            treatments: TreatmentRule {
+                count: 4,
+                points: 5.0,
+            },
+            events: vec![
+                EventRule {
+                    words: words(&["mastitis", "hard bag", "lump"]),
+                    points: 10.0,
+                },
+                EventRule {
+                    words: words(&["foot rot", "footrot", "foot scald", "lame"]),
+                    points: 5.0,
+                },
+                EventRule {
+                    words: words(&["cull"]),
+                    points: 5.0,
+                },
+            ],
+        }
+    }
+}
+
+/// The rules for `file`, "herd.flok" keeps them in "herd.cull.toml".
+pub fn rules_path(file: &str) -> PathBuf {
+    Path::new(file).with_extension("cull.toml")
+}
+
+pub fn parse_rules(text: &str) -> Result<Rules> {
+    toml::from_str(text).map_err(|e| anyhow!("Cull rules: {e}"))
+}
+
+/// The rules at `path`, or the defaults if there is no file.
+pub fn read_rules(path: &Path) -> Result<Rules> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_rules(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Rules::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The text of the rules file at `path`, the defaults if there is none.
+pub fn rules_text(path: &Path) -> Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DEFAULT_RULES.to_string()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Reason {
+    pub points: f64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub id: Id,
+    pub sex: Sex,
+    pub age: Option<i64>,
+    pub points: f64,
+    pub reasons: Vec<Reason>,
+}
+
+/// Every reason `rules` give for culling `animal` on `date`.
+pub fn reasons(flok: &Flok, rules: &Rules, animal: &Animal, date: Date) -> Vec<Reason> {
+    let mut reasons = vec![];
+    let mut add = |points: f64, reason: String| {
+        if points > 0.0 {
+            reasons.push(Reason { points, reason });
+        }
+    };
+    if let Some(age) = age(animal, date) {
+        let over = age - rules.age.years as i64;
+        if over > 0 {
+            add(
+                over as f64 * rules.age.points_per_year,
+                format!("{age} years old"),
+            );
+        }
+    }
+    if animal.sex == Sex::Female {
+        let lambs: Vec<&Animal> = flok
+            .animals
+            .iter()
+            .filter(|a| a.dam.as_ref().is_some_and(|d| animal.id.contains(d)))
+            .collect();
+        let mut seasons: Vec<i32> = lambs
+            .iter()
+            .filter_map(|l| l.born)
+            .map(|b| b.day.year())
+            .collect();
+        seasons.sort();
+        seasons.dedup();
+        if !seasons.is_empty() {
+            let per_lambing = lambs.len() as f64 / seasons.len() as f64;
+            if per_lambing < rules.lambing.min_lambs_per_lambing {
+                add(
+                    rules.lambing.points,
+                    format!("{per_lambing:.1} lambs per lambing"),
+                );
+            }
+        }
+        // seasons she was bred for, long enough ago that she should have lambed
+        let mut bred_for: Vec<i32> = breeding::bred(flok, animal)
+            .iter()
+            .filter(|b| b.day + Days::new(GESTATION_DAYS + GESTATION_SPREAD) < date.day)
+            .map(|b| (b.day + Days::new(GESTATION_DAYS)).year())
+            .collect();
+        bred_for.dedup();
+        for season in bred_for.iter().filter(|s| !seasons.contains(s)) {
+            add(
+                rules.lambing.missed_points,
+                format!("bred but didn't lamb in {season}"),
+            );
+        }
+        let lost = lambs
+            .iter()
+            .filter(|l| match (l.born, l.died()) {
+                (Some(born), Some(died)) => (died.day - born.day).num_days() < WEANING_DAYS,
+                (None, Some(_)) => true,
+                _ => false,
+            })
+            .count();
+        if lost > 0 {
+            add(
+                lost as f64 * rules.survival.points_per_lamb,
+                format!("lost {lost} lambs before weaning"),
+            );
+        }
+    }
+    for rule in &rules.events {
+        for e in &animal.events {
+            let text = format!("{} {} {}", e.name, e.value, e.notes).to_lowercase();
+            if let Some(word) = rule.words.iter().find(|w| text.contains(&w.to_lowercase())) {
+                add(rule.points, format!("{word} on {}", e.date));
+            }
+        }
+    }
+    let poor = animal
+        .scores_of(ScoreKind::Famacha)
+        .iter()
+        .filter(|s| s.value >= rules.famacha.score)
+        .count();
+    if poor > 0 {
+        add(
+            poor as f64 * rules.famacha.points,
+            format!("FAMACHA {} or worse {poor} times", rules.famacha.score),
+        );
+    }
+    if let Some(flagged) =
+        scores::check(flok, animal, date).filter(|f| f.action == scores::Action::Cull)
+    {
+        add(
+            rules.deworming.points,
+            format!("dewormed {} times in a year", flagged.dewormings),
+        );
+    }
+    let year_ago = date.day - Days::new(365);
+    let treated = treatment::treatments(flok, animal)
+        .iter()
+        .filter(|(e, _)| e.date.day > year_ago && e.date.day <= date.day)
+        .count();
+    if rules.treatments.count > 0 && treated >= rules.treatments.count {
+        add(
+            rules.treatments.points,
+            format!("treated {treated} times in a year"),
+        );
+    }
+    reasons
+}
+
+fn age(animal: &Animal, date: Date) -> Option<i64> {
+    animal
+        .born
+        .and_then(|b| date.day.years_since(b.day))
+        .map(|y| y as i64)
+}
+
+/// Ewes and rams in the flock with at least the threshold of points, most points first.
+pub fn candidates(flok: &Flok, rules: &Rules, date: Date) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = flok
+        .animals
+        .iter()
+        .filter(|a| a.in_flock())
+        .filter_map(|a| {
+            let reasons = reasons(flok, rules, a, date);
+            let points: f64 = reasons.iter().map(|r| r.points).sum();
+            (!reasons.is_empty() && points >= rules.threshold).then(|| Candidate {
+                id: a.id.first().cloned().unwrap_or_default(),
+                sex: a.sex,
+                age: age(a, date),
+                points,
+                reasons,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.points.total_cmp(&a.points).then_with(|| a.id.cmp(&b.id)));
+    candidates
+}
+
+pub fn describe(reasons: &[Reason]) -> String {
+    reasons
+        .iter()
+        .map(|r| format!("{} ({})", r.reason, r.points))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn cull_html(flok: &Flok, rules: &Rules, date: Date) -> String {
+    let rows: String = candidates(flok, rules, date)
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                i + 1,
+                escape(&c.id),
+                c.sex.name(),
+                c.age.map(|a| a.to_string()).unwrap_or_default(),
+                c.points,
+                escape(&describe(&c.reasons))
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Cull list</title></head><body>\n<h1>Cull list, {date}</h1>\n\
+         <p>Animals with {} or more points under the cull rules.</p>\n\
+         <table border=\"1\">\n<tr><th>Rank</th><th>Animal</th><th>Sex</th><th>Age</th>\
+         <th>Points</th><th>Why</th></tr>\n{rows}</table>\n</body></html>\n",
+        rules.threshold
+    )
+}
+
+pub fn report_cull(flok: &Flok, rules: &Rules) -> Result<()> {
+    open_in_browser(&cull_html(flok, rules, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(name: &str, value: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            value: value.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_rules_file() {
+        assert_eq!(parse_rules(DEFAULT_RULES).unwrap(), Rules::default());
+        let rules =
+            parse_rules("threshold = 1.0\n[age]\nyears = 3\npoints_per_year = 1.0\n").unwrap();
+        assert_eq!(rules.age.years, 3);
+        assert_eq!(rules.events, Rules::default().events);
+        assert!(parse_rules("threshold = \"high\"").is_err());
+    }
+
+    #[test]
+    fn ranked_with_reasons() {
+        let animal = |id: &str, sex, born: &str, events| Animal {
+            id: vec![id.to_string()],
+            sex,
+            born: Date::parse(born),
+            events,
+            ..Default::default()
+        };
+        let lamb = |dam: &str, born: &str, events| Animal {
+            dam: Some(dam.to_string()),
+            ..animal(&format!("{dam} {born}"), Sex::Male, born, events)
+        };
+        let flok = Flok {
+            animals: vec![
+                animal(
+                    "old",
+                    Sex::Female,
+                    "2015-03-01",
+                    vec![
+                        event("mastitis", "left side", "2024-05-01"),
+                        event("marked", "ram", "2023-10-01"),
+                    ],
+                ),
+                animal("young", Sex::Female, "2022-03-01", vec![]),
+                animal(
+                    "ram",
+                    Sex::Male,
+                    "2020-03-01",
+                    vec![event("trimmed", "foot rot", "2024-06-01")],
+                ),
+                lamb("old", "2023-03-01", vec![event("died", "", "2023-03-02")]),
+                lamb("young", "2024-03-01", vec![]),
+                lamb("young", "2024-03-01", vec![]),
+            ],
+            ..Default::default()
+        };
+        let candidates = candidates(&flok, &Rules::default(), Date::parse("2024-09-01").unwrap());
+        // the reasons' dates are in the display format, so only their first words are compared
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|c| {
+                    let reasons: Vec<(f64, String)> = c
+                        .reasons
+                        .iter()
+                        .map(|r| (r.points, r.reason.split(" on ").next().unwrap().to_string()))
+                        .collect();
+                    (c.id.as_str(), c.points, reasons)
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "old",
+                    43.0,
+                    vec![
+                        (15.0, "9 years old".to_string()),
+                        (5.0, "1.0 lambs per lambing".to_string()),
+                        (10.0, "bred but didn't lamb in 2024".to_string()),
+                        (3.0, "lost 1 lambs before weaning".to_string()),
+                        (10.0, "mastitis".to_string()),
+                    ]
+                ),
+                ("ram", 5.0, vec![(5.0, "foot rot".to_string())]),
+            ]
+        );
+    }
+}
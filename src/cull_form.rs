@@ -0,0 +1,62 @@
+use fltk::{
+    button::Button,
+    enums::Font,
+    frame::Frame,
+    group::Flex,
+    prelude::{DisplayExt, GroupExt, WidgetBase, WidgetExt, WindowExt},
+    text::{TextBuffer, TextEditor},
+    window::Window,
+};
+use std::path::PathBuf;
+
+use crate::{cull, form::display_error};
+
+/// Edit the cull rules kept in `path`. They are checked before they are saved.
+pub fn show_rules(path: PathBuf) -> anyhow::Result<()> {
+    let mut buffer = TextBuffer::default();
+    buffer.set_text(&cull::rules_text(&path)?);
+    let mut wind = Window::default()
+        .with_size(650, 500)
+        .with_label("Cull rules");
+    let mut page = Flex::default_fill().column();
+    let mut editor = TextEditor::default();
+    editor.set_buffer(buffer.clone());
+    editor.set_text_font(Font::Courier);
+
+    let mut buttons = Flex::default().row();
+    {
+        let mut defaults = Button::default().with_label("Defaults");
+        let mut buffer = buffer.clone();
+        defaults.set_callback(move |_| buffer.set_text(cull::DEFAULT_RULES));
+        buttons.fixed(&defaults, 70);
+    }
+    buttons.resizable(&Frame::default());
+    {
+        let mut cancel = Button::default().with_label("Cancel");
+        let mut wind = wind.clone();
+        cancel.set_callback(move |_| wind.hide());
+        buttons.fixed(&cancel, 60);
+    }
+    {
+        let mut save = Button::default().with_label("Save");
+        let mut wind = wind.clone();
+        save.set_callback(move |_| {
+            let text = buffer.text();
+            let saved = cull::parse_rules(&text)
+                .and_then(|_| std::fs::write(&path, &text).map_err(Into::into));
+            if saved.is_ok() {
+                wind.hide();
+            }
+            display_error("Unable to save cull rules", saved);
+        });
+        buttons.fixed(&save, 60);
+    }
+    buttons.end();
+    page.fixed(&buttons, 25);
+    page.end();
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+    Ok(())
+}
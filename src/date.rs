@@ -0,0 +1,197 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt::Display, sync::RwLock};
+
+/// A calendar date, with a time of day only where it matters.
+///
+/// Stored as "2021-04-03" or "2021-04-03T14:30:00". Files written before dates were
+/// date-only hold RFC 3339 timestamps, which are read back as the date and time that was shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub day: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+/// How dates are shown and typed in the UI.
+#[derive(Serialize, Deserialize, Debug, Default, Ordinalize, Clone, Copy, PartialEq, Eq)]
+pub enum DateFormat {
+    #[default]
+    Iso,
+    Us,
+    European,
+    Long,
+}
+
+impl DateFormat {
+    pub fn name(&self) -> &str {
+        match self {
+            DateFormat::Iso => "ISO (2021-04-03)",
+            DateFormat::Us => "US (4/3/2021)",
+            DateFormat::European => "European (3/4/2021)",
+            DateFormat::Long => "Long (Apr 3, 2021)",
+        }
+    }
+    fn pattern(&self) -> &str {
+        match self {
+            DateFormat::Iso => "%Y-%m-%d",
+            DateFormat::Us => "%-m/%-d/%Y",
+            DateFormat::European => "%-d/%-m/%Y",
+            DateFormat::Long => "%b %-d, %Y",
+        }
+    }
+}
+
+static FORMAT: RwLock<DateFormat> = RwLock::new(DateFormat::Iso);
+
+pub fn set_format(format: DateFormat) {
+    *FORMAT.write().unwrap() = format;
+}
+
+pub fn format() -> DateFormat {
+    *FORMAT.read().unwrap()
+}
+
+const TIME: &str = "%H:%M";
+const STORED_DAY: &str = "%Y-%m-%d";
+const STORED_TIME: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+impl Date {
+    pub fn today() -> Self {
+        Local::now().date_naive().into()
+    }
+
+    pub fn now() -> Self {
+        Local::now().naive_local().into()
+    }
+
+    pub fn is_future(&self) -> bool {
+        let now = Local::now().naive_local();
+        self.day > now.date() || (self.day == now.date() && self.time > Some(now.time()))
+    }
+
+    /// Read a date typed by the user, preferring the display format over other guesses.
+    pub fn parse(s: &str) -> Option<Date> {
+        Self::parse_as(s, format())
+    }
+
+    /// Read a date typed by the user, preferring `format` over other guesses.
+    pub fn parse_as(s: &str, format: DateFormat) -> Option<Date> {
+        let s = s.trim();
+        let pattern = format.pattern().replace("%-", "%");
+        for p in [pattern.as_str(), STORED_DAY, "%m/%d/%Y", "%Y/%m/%d"] {
+            if let Ok(day) = NaiveDate::parse_from_str(s, p) {
+                return Some(day.into());
+            }
+            for t in [TIME, "%H:%M:%S"] {
+                if let Ok(dt) = NaiveDateTime::parse_from_str(s, &format!("{p} {t}")) {
+                    return Some(dt.into());
+                }
+            }
+        }
+        Self::parse_stored(s).or_else(|| {
+            dateparser::parse(s)
+                .ok()
+                .map(|d| d.with_timezone(&Local).naive_local().into())
+        })
+    }
+
+    /// How the date is shown in `format`, with the time if it has one.
+    pub fn show(&self, format: DateFormat) -> String {
+        let day = self.day.format(format.pattern());
+        match self.time {
+            None => day.to_string(),
+            Some(time) => format!("{day} {}", time.format(TIME)),
+        }
+    }
+
+    /// The stored form, independent of the display format.
+    pub fn to_iso(&self) -> String {
+        match self.time {
+            None => self.day.format(STORED_DAY).to_string(),
+            Some(time) => self.day.and_time(time).format(STORED_TIME).to_string(),
+        }
+    }
+
+    /// The forms dates are written in, "2021-04-03" or "2021-04-03T14:30:00".
+    pub fn parse_stored(s: &str) -> Option<Date> {
+        if let Ok(day) = NaiveDate::parse_from_str(s, STORED_DAY) {
+            Some(day.into())
+        } else if let Ok(dt) = NaiveDateTime::parse_from_str(s, STORED_TIME) {
+            Some(dt.into())
+        } else {
+            // legacy DateTime<Local>, keep the wall clock time it was entered with
+            DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|dt| dt.naive_local().into())
+        }
+    }
+}
+
+impl From<NaiveDate> for Date {
+    fn from(day: NaiveDate) -> Self {
+        Self { day, time: None }
+    }
+}
+
+/// Midnight is taken to mean "no time".
+impl From<NaiveDateTime> for Date {
+    fn from(dt: NaiveDateTime) -> Self {
+        Self {
+            day: dt.date(),
+            time: Some(dt.time()).filter(|t| *t != NaiveTime::MIN),
+        }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.show(format()))
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_iso())
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Date::parse_stored(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid date: {s}")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn legacy_timestamps() {
+        let born: Date = serde_json::from_str("\"2021-04-03T00:00:00-04:00\"").unwrap();
+        assert_eq!(born, day(2021, 4, 3).into());
+        assert_eq!(serde_json::to_string(&born).unwrap(), "\"2021-04-03\"");
+
+        let event: Date = serde_json::from_str("\"2025-10-18T21:04:05.123456789-04:00\"").unwrap();
+        assert_eq!(event.day, day(2025, 10, 18));
+        assert_eq!(
+            serde_json::from_str::<Date>(&serde_json::to_string(&event).unwrap()).unwrap(),
+            event
+        );
+    }
+
+    #[test]
+    fn display_formats() {
+        let d: Date = day(2021, 4, 3).into();
+        // the format is passed in, setting it would change the dates other tests show
+        for f in DateFormat::VARIANTS {
+            assert_eq!(Date::parse_as(&d.show(*f), *f), Some(d), "{}", f.name());
+        }
+        assert_eq!(d.show(DateFormat::Us), "4/3/2021");
+    }
+}
@@ -0,0 +1,128 @@
+use chrono::{Datelike, Months, NaiveDate};
+use fltk::{
+    button::Button,
+    frame::Frame,
+    group::Flex,
+    input::Input,
+    prelude::{GroupExt, InputExt, WidgetBase, WidgetExt, WindowExt},
+    window::Window,
+};
+
+use crate::{flok::Date, form::FieldError};
+
+/// A text input for dates with a button to pick the date from a calendar.
+#[derive(Clone)]
+pub struct DateInput {
+    pub group: Flex,
+    pub input: Input,
+    pub button: Button,
+}
+
+impl Default for DateInput {
+    fn default() -> Self {
+        let mut group = Flex::default().row();
+        let input = Input::default();
+        let mut button = Button::default().with_label("@>");
+        button.set_tooltip("Pick from calendar");
+        group.fixed(&button, 25);
+        group.end();
+        {
+            let input = input.clone();
+            button.set_callback(move |_| {
+                let month = Date::parse(&input.value()).unwrap_or_else(Date::today).day;
+                show_calendar(input.clone(), month);
+            });
+        }
+        Self {
+            group,
+            input,
+            button,
+        }
+    }
+}
+
+impl DateInput {
+    pub fn get_date(&self) -> Option<Date> {
+        Date::parse(&self.input.value())
+    }
+
+    pub fn set_date(&mut self, date: Option<Date>) {
+        self.input
+            .set_value(&date.map(|d| d.to_string()).unwrap_or_default());
+    }
+
+    /// The date entered, `None` when blank, or an error for `field` when it can't be read.
+    pub fn parse(&self, field: &'static str) -> Result<Option<Date>, FieldError> {
+        if self.input.value().trim().is_empty() {
+            Ok(None)
+        } else {
+            self.get_date().map(Some).ok_or_else(|| {
+                FieldError::new(field, format!("\"{}\" is not a date", self.input.value()))
+            })
+        }
+    }
+}
+
+impl From<DateInput> for Input {
+    fn from(val: DateInput) -> Self {
+        val.input
+    }
+}
+
+const CELL: i32 = 30;
+
+/// Popup month view. Choosing a day writes it into `input`; the arrows reopen it on another month.
+fn show_calendar(input: Input, month: NaiveDate) {
+    let first = month.with_day(1).unwrap();
+    let mut wind = Window::default()
+        .with_size(7 * CELL, 8 * CELL)
+        // leak() because fltk expects statics strings for window titles
+        .with_label(first.format("%B %Y").to_string().leak());
+    {
+        let mut prev = Button::new(0, 0, CELL, CELL, "@<");
+        let mut w = wind.clone();
+        let input = input.clone();
+        prev.set_callback(move |_| {
+            w.hide();
+            show_calendar(input.clone(), first - Months::new(1));
+        });
+    }
+    Frame::new(CELL, 0, 5 * CELL, CELL, None).with_label(&first.format("%B %Y").to_string());
+    {
+        let mut next = Button::new(6 * CELL, 0, CELL, CELL, "@>");
+        let mut w = wind.clone();
+        let input = input.clone();
+        next.set_callback(move |_| {
+            w.hide();
+            show_calendar(input.clone(), first + Months::new(1));
+        });
+    }
+    for (col, name) in ["Su", "Mo", "Tu", "We", "Th", "Fr", "Sa"]
+        .iter()
+        .enumerate()
+    {
+        Frame::new(col as i32 * CELL, CELL, CELL, CELL, None).with_label(name);
+    }
+    let offset = first.weekday().num_days_from_sunday();
+    for day in first.iter_days().take_while(|d| d.month() == first.month()) {
+        let cell = offset + day.day0();
+        let mut b = Button::new(
+            (cell % 7) as i32 * CELL,
+            (2 + cell / 7) as i32 * CELL,
+            CELL,
+            CELL,
+            None,
+        )
+        .with_label(&day.day().to_string());
+        let mut w = wind.clone();
+        let mut input = input.clone();
+        b.set_callback(move |_| {
+            input.set_value(&Date::from(day).to_string());
+            input.do_callback();
+            w.hide();
+        });
+    }
+    wind.end();
+    wind.make_modal(true);
+    wind.show();
+}
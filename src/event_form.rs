@@ -16,10 +16,11 @@ use std::collections::HashMap;
 use anyhow::Result;
 
 use crate::{
-    animal_form::DateInput,
     business_obj::BusinessObject,
+    date_input::DateInput,
     flok::{Animal, Event},
-    form::{create_form, Editor},
+    form::{create_form, display_error, highlight, validation_error, Editor, FieldError},
+    validate::check_event,
 };
 
 #[derive(Default)]
@@ -28,23 +29,29 @@ pub struct EventForm<A: BusinessObject<Type = Event>> {
     pub date: DateInput,
     pub value: Input,
     pub notes: Input,
+    pub contact: Input,
     pub event: A,
 }
 
 impl<A: BusinessObject<Type = Event>> EventForm<A> {
     pub(crate) fn create(event: A) -> Result<(Self, Widget)> {
-        let event_form = Self {
+        let mut event_form = Self {
             name: Input::default(),
             date: DateInput::default(),
             value: Input::default(),
             notes: Input::default(),
+            contact: Input::default(),
             event,
         };
+        event_form
+            .contact
+            .set_tooltip("Who the animal was bought from or sold to");
         let ui = create_form(vec![
             ("Name", &event_form.name),
-            ("Date", &event_form.date.input),
+            ("Date", &event_form.date.group),
             ("Value", &event_form.value),
             ("Notes", &event_form.notes),
+            ("Contact", &event_form.contact),
         ])?;
         Ok((event_form, ui))
     }
@@ -53,20 +60,36 @@ impl<A: BusinessObject<Type = Event>> EventForm<A> {
 impl<A: BusinessObject<Type = Event>> Editor<A> for EventForm<A> {
     fn set_value(&mut self, event: &A) {
         self.name.set_value(&event.exec(|e| e.name.clone()));
-        self.date
-            .input
-            .set_value(&event.exec(|e| e.date.to_string()));
+        self.date.set_date(Some(event.exec(|e| e.date)));
         self.value.set_value(&event.exec(|e| e.value.clone()));
         self.notes.set_value(&event.exec(|e| e.notes.clone()));
+        self.contact
+            .set_value(&event.exec(|e| e.contact.clone().unwrap_or_default()));
     }
 
-    fn commit(&mut self) {
-        self.event.exec(|e| {
-            e.name = self.name.value();
-            e.date = self.date.get_date().unwrap_or_else(chrono::Local::now);
-            e.value = self.value.value();
-            e.notes = self.notes.value();
-        });
+    fn commit(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        let mut event = self.event.exec(|e| e.clone());
+        event.name = self.name.value();
+        match self.date.parse("Date") {
+            Ok(Some(date)) => event.date = date,
+            Ok(None) => errors.push(FieldError::new("Date", "Date is required")),
+            Err(e) => errors.push(e),
+        }
+        event.value = self.value.value();
+        event.notes = self.notes.value();
+        event.contact = Some(self.contact.value().trim().to_string()).filter(|c| !c.is_empty());
+
+        errors.extend(check_event(&event));
+        highlight(&mut self.name, "Name", &errors);
+        highlight(&mut self.date.input, "Date", &errors);
+
+        if errors.is_empty() {
+            self.event.exec(|e| *e = event.clone());
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 }
 
@@ -143,7 +166,6 @@ impl<A: 'static + BusinessObject<Type = Animal>> SimpleModel for EventTableModel
                     let name = format!("Edit {}", event.exec(|a| a.name.to_string()));
                     let mut b = Button::default().with_size(30, 20).with_label(&name);
                     b.set_callback(move |_| {
-
                         let mut wind = Window::default().with_size(600, 600).with_label(
                             // leak() because fltk expects statics strings for window titles
                             format!("Edit {}", event.exec(|a| a.name.to_string())).leak(),
@@ -174,10 +196,12 @@ impl<A: 'static + BusinessObject<Type = Animal>> SimpleModel for EventTableModel
                         {
                             let mut save = Button::default().size_of_parent().with_label("Save");
                             let mut wind = wind.clone();
-                            save.set_callback(move |_b| {
-                                form.commit();
-
-                                wind.hide();
+                            save.set_callback(move |_b| match form.commit() {
+                                Ok(()) => wind.hide(),
+                                Err(errors) => display_error::<()>(
+                                    "Unable to save event",
+                                    Err(validation_error(errors)),
+                                ),
                             });
                             buttons.fixed(&save, 60);
                         }
@@ -0,0 +1,439 @@
+use anyhow::{anyhow, Result};
+use enum_ordinalize::Ordinalize;
+use rust_xlsxwriter::{Format, Workbook};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::Write,
+    path::Path,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::flok::*;
+
+/// Per animal columns of the animals sheet.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    /// One "id:" column per extra tag
+    Tags,
+    Sex,
+    Born,
+    Sire,
+    Dam,
+    Description,
+    /// One "weight" column per weighing date
+    Weights,
+}
+
+impl Column {
+    pub const ALL: [Column; 8] = [
+        Column::Id,
+        Column::Tags,
+        Column::Sex,
+        Column::Born,
+        Column::Sire,
+        Column::Dam,
+        Column::Description,
+        Column::Weights,
+    ];
+}
+
+/// What to put in an export. The animals sheet is always written.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub columns: Vec<Column>,
+    /// Event names that become columns of the animals sheet, as many as the most any animal has
+    pub pivot: Vec<String>,
+    /// One row per event
+    pub events: bool,
+    /// One row per lamb, by dam
+    pub lambing: bool,
+    /// One row per sale event
+    pub sales: bool,
+}
+
+impl ExportOptions {
+    /// Everything, laid out like the original workbook.
+    pub fn all(flok: &Flok) -> Self {
+        Self {
+            columns: Column::ALL.to_vec(),
+            pivot: event_names(flok),
+            events: true,
+            lambing: true,
+            sales: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Date(Date),
+}
+
+impl From<String> for Cell {
+    fn from(s: String) -> Self {
+        if s.is_empty() {
+            Cell::Empty
+        } else {
+            Cell::Text(s)
+        }
+    }
+}
+
+impl From<Option<Date>> for Cell {
+    fn from(d: Option<Date>) -> Self {
+        d.map(Cell::Date).unwrap_or(Cell::Empty)
+    }
+}
+
+impl Cell {
+    fn text(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Text(s) => s.clone(),
+            // ISO, so it reads back the same whatever the display format
+            Cell::Date(d) => d.to_iso(),
+        }
+    }
+}
+
+pub struct Sheet {
+    pub name: String,
+    /// The first row is the header
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// Every event name in the flock, in the order first seen.
+pub fn event_names(flok: &Flok) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    flok.animals
+        .iter()
+        .flat_map(|a| a.events.iter())
+        .filter(|e| seen.insert(e.name.clone()))
+        .map(|e| e.name.clone())
+        .collect()
+}
+
+/// Columns for the `pivot` events: the nth event of a name goes in the nth column with that name.
+/// Columns are ordered so each animal's events read back in the order they were recorded.
+fn pivot_columns(flok: &Flok, pivot: &[String]) -> Vec<(String, usize)> {
+    let mut columns: Vec<(String, usize)> = vec![];
+    for a in &flok.animals {
+        let mut counts = BTreeMap::new();
+        let mut position = 0;
+        for e in a.events.iter().filter(|e| pivot.contains(&e.name)) {
+            let n = counts.entry(&e.name).or_insert(0);
+            let column = (e.name.clone(), *n);
+            *n += 1;
+            match columns.iter().position(|c| *c == column) {
+                Some(i) => position = i + 1,
+                None => {
+                    columns.insert(position, column);
+                    position += 1;
+                }
+            }
+        }
+    }
+    columns
+}
+
+/// Columns for weights, by day and unit, the nth weighing of a day in the nth column.
+fn weight_columns(flok: &Flok) -> Vec<(Date, WeightUnit, usize)> {
+    let mut columns = BTreeSet::new();
+    for a in &flok.animals {
+        let mut counts = BTreeMap::new();
+        for w in &a.weights {
+            let n = counts.entry((w.date.day, w.unit.ordinal())).or_insert(0);
+            columns.insert((w.date.day, w.unit.ordinal(), *n));
+            *n += 1;
+        }
+    }
+    columns
+        .into_iter()
+        .map(|(day, unit, n)| (day.into(), WeightUnit::from_ordinal(unit).unwrap(), n))
+        .collect()
+}
+
+pub fn sheets(flok: &Flok, options: &ExportOptions) -> Vec<Sheet> {
+    let mut sheets = vec![animals_sheet(flok, options)];
+    if options.events {
+        sheets.push(events_sheet("Events", flok, |_| true));
+    }
+    if options.lambing {
+        sheets.push(lambing_sheet(flok));
+    }
+    if options.sales {
+        sheets.push(events_sheet("Sales", flok, Event::is_sale));
+    }
+    sheets
+}
+
+fn animals_sheet(flok: &Flok, options: &ExportOptions) -> Sheet {
+    let tags = flok
+        .animals
+        .iter()
+        .map(|a| a.id.len().saturating_sub(1))
+        .max()
+        .unwrap_or(0);
+    let weights = weight_columns(flok);
+    let mut header = vec![];
+    for c in &options.columns {
+        match c {
+            Column::Id => header.push("id".to_string()),
+            Column::Tags => header.extend((1..=tags).map(|i| format!("id:{i}"))),
+            Column::Sex => header.push("sex".to_string()),
+            Column::Born => header.push("born".to_string()),
+            Column::Sire => header.push("sire".to_string()),
+            Column::Dam => header.push("dam".to_string()),
+            Column::Description => header.push("description".to_string()),
+            Column::Weights => header.extend(
+                weights
+                    .iter()
+                    .map(|(day, unit, _)| format!("weight {} {}", unit.name(), day.to_iso())),
+            ),
+        }
+    }
+    let pivot = pivot_columns(flok, &options.pivot);
+    header.extend(pivot.iter().map(|(name, _)| name.clone()));
+
+    let mut rows = vec![header.into_iter().map(Cell::Text).collect()];
+    for a in &flok.animals {
+        let mut row = vec![];
+        for c in &options.columns {
+            match c {
+                Column::Id => row.push(a.id.first().cloned().unwrap_or_default().into()),
+                Column::Tags => {
+                    row.extend((1..=tags).map(|i| a.id.get(i).cloned().unwrap_or_default().into()))
+                }
+                Column::Sex => row.push(a.sex.name().to_string().into()),
+                Column::Born => row.push(a.born.into()),
+                Column::Sire => row.push(a.sire.clone().unwrap_or_default().into()),
+                Column::Dam => row.push(a.dam.clone().unwrap_or_default().into()),
+                Column::Description => row.push(a.description.clone().into()),
+                Column::Weights => row.extend(weights.iter().map(|(day, unit, n)| {
+                    a.weights
+                        .iter()
+                        .filter(|w| w.date.day == day.day && w.unit == *unit)
+                        .nth(*n)
+                        .map(|w| w.value.to_string())
+                        .unwrap_or_default()
+                        .into()
+                })),
+            }
+        }
+        for (name, n) in &pivot {
+            let value = a
+                .events
+                .iter()
+                .filter(|e| &e.name == name)
+                .nth(*n)
+                .map(|e| e.value.clone())
+                .unwrap_or_default();
+            row.push(value.into());
+        }
+        rows.push(row);
+    }
+    Sheet {
+        name: "Animals".to_string(),
+        rows,
+    }
+}
+
+fn events_sheet(name: &str, flok: &Flok, filter: impl Fn(&Event) -> bool) -> Sheet {
+    let mut rows = vec![["animal", "event", "date", "value", "notes"]
+        .map(|h| Cell::Text(h.to_string()))
+        .to_vec()];
+    for a in &flok.animals {
+        let id = a.id.first().cloned().unwrap_or_default();
+        for e in a.events.iter().filter(|e| filter(e)) {
+            rows.push(vec![
+                id.clone().into(),
+                e.name.clone().into(),
+                Cell::Date(e.date),
+                e.value.clone().into(),
+                e.notes.clone().into(),
+            ]);
+        }
+    }
+    Sheet {
+        name: name.to_string(),
+        rows,
+    }
+}
+
+fn lambing_sheet(flok: &Flok) -> Sheet {
+    let mut lambs: Vec<&Animal> = flok.animals.iter().filter(|a| a.dam.is_some()).collect();
+    lambs.sort_by_key(|a| (a.dam.clone(), a.born));
+    let mut rows = vec![["dam", "lamb", "born", "sex", "sire", "died"]
+        .map(|h| Cell::Text(h.to_string()))
+        .to_vec()];
+    for a in lambs {
+        rows.push(vec![
+            a.dam.clone().unwrap_or_default().into(),
+            a.id.first().cloned().unwrap_or_default().into(),
+            a.born.into(),
+            a.sex.name().to_string().into(),
+            a.sire.clone().unwrap_or_default().into(),
+            a.died().into(),
+        ]);
+    }
+    Sheet {
+        name: "Lambing".to_string(),
+        rows,
+    }
+}
+
+/// Write the sheets to `file`, in the format given by its extension.
+pub fn export(flok: &Flok, options: &ExportOptions, file: &str) -> Result<()> {
+    let sheets = sheets(flok, options);
+    let path = Path::new(file);
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("xlsx") => write_xlsx(&sheets, path),
+        Some("ods") => write_ods(&sheets, path),
+        Some("csv") => {
+            // one file per sheet: flock.csv, flock-events.csv, ...
+            for (i, sheet) in sheets.iter().enumerate() {
+                let file = if i == 0 {
+                    path.to_path_buf()
+                } else {
+                    path.with_file_name(format!(
+                        "{}-{}.csv",
+                        path.file_stem().unwrap_or_default().to_string_lossy(),
+                        sheet.name.to_lowercase()
+                    ))
+                };
+                write_csv(sheet, File::create(file)?)?;
+            }
+            Ok(())
+        }
+        _ => Err(anyhow!("Unknown export format for {file}")),
+    }
+}
+
+pub fn write_csv(sheet: &Sheet, out: impl Write) -> Result<()> {
+    let mut csv = csv::Writer::from_writer(out);
+    for row in &sheet.rows {
+        csv.write_record(row.iter().map(Cell::text))?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn write_xlsx(sheets: &[Sheet], path: &Path) -> Result<()> {
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let date = Format::new().set_num_format("yyyy-mm-dd");
+    let date_time = Format::new().set_num_format("yyyy-mm-dd hh:mm");
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&sheet.name)?;
+        for (r, row) in sheet.rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                let (r, c) = (r as u32, c as u16);
+                match cell {
+                    Cell::Empty => {}
+                    Cell::Text(s) if r == 0 => {
+                        worksheet.write_string_with_format(r, c, s, &bold)?;
+                    }
+                    Cell::Text(s) => {
+                        worksheet.write_string(r, c, s)?;
+                    }
+                    Cell::Date(d) => match d.time {
+                        None => {
+                            worksheet.write_datetime_with_format(r, c, d.day, &date)?;
+                        }
+                        Some(t) => {
+                            worksheet.write_datetime_with_format(
+                                r,
+                                c,
+                                d.day.and_time(t),
+                                &date_time,
+                            )?;
+                        }
+                    },
+                }
+            }
+        }
+    }
+    workbook.save(path)?;
+    Ok(())
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const ODS_MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+/// A minimal OpenDocument spreadsheet: just the cells, no styles.
+fn write_ods(sheets: &[Sheet], path: &Path) -> Result<()> {
+    let mut content = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content
+ xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0"
+ xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0"
+ xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" office:version="1.2">
+<office:body><office:spreadsheet>
+"#,
+    );
+    for sheet in sheets {
+        content += &format!("<table:table table:name=\"{}\">\n", xml_escape(&sheet.name));
+        for row in &sheet.rows {
+            content += "<table:table-row>";
+            for cell in row {
+                content += &match cell {
+                    Cell::Empty => "<table:table-cell/>".to_string(),
+                    Cell::Text(s) => format!(
+                        "<table:table-cell office:value-type=\"string\"><text:p>{}</text:p>\
+                         </table:table-cell>",
+                        xml_escape(s)
+                    ),
+                    Cell::Date(d) => format!(
+                        "<table:table-cell office:value-type=\"date\" office:date-value=\"{0}\">\
+                         <text:p>{0}</text:p></table:table-cell>",
+                        d.to_iso()
+                    ),
+                };
+            }
+            content += "</table:table-row>\n";
+        }
+        content += "</table:table>\n";
+    }
+    content += "</office:spreadsheet></office:body></office:document-content>\n";
+
+    let manifest = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0"
+ manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2"
+  manifest:media-type="{ODS_MIMETYPE}"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#
+    );
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    // the mimetype must come first and be uncompressed
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(ODS_MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/manifest.xml", SimpleFileOptions::default())?;
+    zip.write_all(manifest.as_bytes())?;
+    zip.start_file("content.xml", SimpleFileOptions::default())?;
+    zip.write_all(content.as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
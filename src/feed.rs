@@ -0,0 +1,407 @@
+use anyhow::Result;
+use chrono::{Datelike, Days};
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flok::*,
+    groups, ledger,
+    report::{escape, open_in_browser},
+};
+
+/// The feeding rate is what was fed over this many days.
+pub const RATE_DAYS: u64 = 14;
+
+#[derive(
+    Serialize, Deserialize, Debug, Default, Ordinalize, clap::ValueEnum, Clone, Copy, PartialEq, Eq,
+)]
+pub enum FeedKind {
+    #[default]
+    Hay,
+    Grain,
+    Mineral,
+    Other,
+}
+
+impl FeedKind {
+    pub fn name(&self) -> &str {
+        match self {
+            FeedKind::Hay => "Hay",
+            FeedKind::Grain => "Grain",
+            FeedKind::Mineral => "Mineral",
+            FeedKind::Other => "Other",
+        }
+    }
+
+    /// Where purchases of it go in the profit and loss.
+    pub fn category(&self) -> Category {
+        match self {
+            FeedKind::Hay => Category::Hay,
+            _ => Category::Feed,
+        }
+    }
+}
+
+/// Feed bought, like 40 bales of hay.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct FeedPurchase {
+    pub date: Date,
+    /// Purchases and feedings with the same name are the same feed
+    pub feed: String,
+    pub kind: FeedKind,
+    pub quantity: f64,
+    /// Like "bales" or "lb", feedings are in the same units
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub unit: String,
+    /// For the whole quantity
+    pub cost: f64,
+}
+
+/// Feed given to a group.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Feeding {
+    pub date: Date,
+    pub group: String,
+    pub feed: String,
+    pub quantity: f64,
+}
+
+fn same(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// The purchases of `feed`.
+pub fn purchases<'a>(flok: &'a Flok, feed: &'a str) -> impl Iterator<Item = &'a FeedPurchase> {
+    flok.feed_purchases
+        .iter()
+        .filter(move |p| same(&p.feed, feed))
+}
+
+/// The average cost of one unit of `feed` over everything bought, `None` if none was.
+pub fn unit_cost(flok: &Flok, feed: &str) -> Option<f64> {
+    let quantity: f64 = purchases(flok, feed).map(|p| p.quantity).sum();
+    let cost: f64 = purchases(flok, feed).map(|p| p.cost).sum();
+    (quantity > 0.0).then(|| cost / quantity)
+}
+
+/// How much of one feed is left.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Stock {
+    pub feed: String,
+    pub kind: FeedKind,
+    pub unit: String,
+    pub bought: f64,
+    pub fed: f64,
+    pub remaining: f64,
+    /// Fed each day lately, `None` if none was
+    pub per_day: Option<f64>,
+    /// `None` while it isn't being fed
+    pub days_left: Option<f64>,
+    pub unit_cost: Option<f64>,
+}
+
+/// Each feed bought or fed by `date`, in the order first bought.
+pub fn inventory(flok: &Flok, date: Date) -> Vec<Stock> {
+    let mut feeds: Vec<&str> = vec![];
+    let names = flok
+        .feed_purchases
+        .iter()
+        .filter(|p| p.date.day <= date.day)
+        .map(|p| p.feed.trim())
+        .chain(
+            flok.feedings
+                .iter()
+                .filter(|f| f.date.day <= date.day)
+                .map(|f| f.feed.trim()),
+        );
+    for name in names {
+        if !feeds.iter().any(|f| same(f, name)) {
+            feeds.push(name);
+        }
+    }
+    let window = date.day - Days::new(RATE_DAYS - 1);
+    feeds
+        .into_iter()
+        .map(|feed| {
+            let bought: Vec<&FeedPurchase> = purchases(flok, feed)
+                .filter(|p| p.date.day <= date.day)
+                .collect();
+            let fed: Vec<&Feeding> = flok
+                .feedings
+                .iter()
+                .filter(|f| same(&f.feed, feed) && f.date.day <= date.day)
+                .collect();
+            let recent: Vec<&&Feeding> = fed.iter().filter(|f| f.date.day >= window).collect();
+            // a feed first given part way through the window is fed from then
+            let since = if fed.iter().any(|f| f.date.day < window) {
+                Some(window)
+            } else {
+                recent.iter().map(|f| f.date.day).min()
+            };
+            let per_day = since.map(|since| {
+                recent.iter().map(|f| f.quantity).sum::<f64>()
+                    / ((date.day - since).num_days() + 1) as f64
+            });
+            let bought_quantity: f64 = bought.iter().map(|p| p.quantity).sum();
+            let fed_quantity: f64 = fed.iter().map(|f| f.quantity).sum();
+            let remaining = bought_quantity - fed_quantity;
+            Stock {
+                feed: feed.to_string(),
+                kind: bought.first().map(|p| p.kind).unwrap_or_default(),
+                unit: bought.first().map(|p| p.unit.clone()).unwrap_or_default(),
+                bought: bought_quantity,
+                fed: fed_quantity,
+                remaining,
+                per_day,
+                days_left: per_day
+                    .filter(|r| *r > 0.0)
+                    .map(|r| (remaining / r).max(0.0)),
+                unit_cost: unit_cost(flok, feed),
+            }
+        })
+        .collect()
+}
+
+/// What one feeding cost, at the feed's average cost.
+pub fn cost(flok: &Flok, feeding: &Feeding) -> f64 {
+    unit_cost(flok, &feeding.feed).unwrap_or(0.0) * feeding.quantity
+}
+
+/// Whether ledger `entry` is a feed purchase also recorded in the feed inventory: flock-wide, on
+/// the same day, in the same category and for the same amount. The inventory counts it.
+pub fn is_purchase(flok: &Flok, entry: &LedgerEntry) -> bool {
+    entry.animal.is_none()
+        && flok.feed_purchases.iter().any(|p| {
+            p.date.day == entry.date.day
+                && p.kind.category() == entry.category
+                && (p.cost - entry.amount).abs() < 0.005
+        })
+}
+
+/// What the feedings in a year cost the animals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedCosts {
+    /// For each animal in the flock
+    pub animals: Vec<f64>,
+    /// Fed to a group with no members on hand, or to one that no longer exists
+    pub unallocated: f64,
+}
+
+/// The cost of the feedings in `year`, or all of them: each feeding is shared equally by the
+/// members of its group on hand that day.
+pub fn feed_costs(flok: &Flok, year: Option<i32>) -> FeedCosts {
+    let mut costs = FeedCosts {
+        animals: vec![0.0; flok.animals.len()],
+        unallocated: 0.0,
+    };
+    for f in flok
+        .feedings
+        .iter()
+        .filter(|f| year.is_none_or(|y| f.date.day.year() == y))
+    {
+        let eating: Vec<usize> = match groups::group(flok, &f.group) {
+            Some(group) => (0..flok.animals.len())
+                .filter(|i| {
+                    let a = &flok.animals[*i];
+                    group.contains(a) && ledger::on_hand(a, f.date)
+                })
+                .collect(),
+            None => vec![],
+        };
+        if eating.is_empty() {
+            costs.unallocated += cost(flok, f);
+        }
+        for i in &eating {
+            costs.animals[*i] += cost(flok, f) / eating.len() as f64;
+        }
+    }
+    costs
+}
+
+/// Feed left and how long it will last on `date`, and what each group was fed this year.
+pub fn feed_html(flok: &Flok, date: Date) -> String {
+    let number = |n: Option<f64>| n.map(|n| format!("{n:.1}")).unwrap_or_default();
+    let stock: String = inventory(flok, date)
+        .iter()
+        .map(|s| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td>\
+                 <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&s.feed),
+                s.kind.name(),
+                escape(&s.unit),
+                s.bought,
+                s.fed,
+                if s.remaining < 0.0 {
+                    format!("<b style=\"color:red\">{:.1}</b>", s.remaining)
+                } else {
+                    format!("{:.1}", s.remaining)
+                },
+                number(s.per_day),
+                number(s.days_left),
+                s.unit_cost.map(|c| format!("{c:.2}")).unwrap_or_default()
+            )
+        })
+        .collect();
+    let year = date.day.year();
+    let groups: String = flok
+        .groups
+        .iter()
+        .map(|g| {
+            let fed: Vec<&Feeding> = flok
+                .feedings
+                .iter()
+                .filter(|f| same(&f.group, &g.name) && f.date.day.year() == year)
+                .collect();
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{:.2}</td></tr>\n",
+                escape(&g.name),
+                fed.len(),
+                fed.iter().map(|f| cost(flok, f)).sum::<f64>()
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Feed</title></head><body>\n<h1>Feed on {date}</h1>\n\
+         <table border=\"1\">\n<tr><th>Feed</th><th>Kind</th><th>Unit</th><th>Bought</th>\
+         <th>Fed</th><th>Left</th><th>Per day</th><th>Days left</th><th>Cost per unit</th></tr>\n\
+         {stock}</table>\n<p>Per day is what was fed over the last {RATE_DAYS} days.</p>\n\
+         <h2>Fed in {year}</h2>\n\
+         <table border=\"1\">\n<tr><th>Group</th><th>Feedings</th><th>Cost</th></tr>\n\
+         {groups}</table>\n</body></html>\n"
+    )
+}
+
+pub fn report_feed(flok: &Flok) -> Result<()> {
+    open_in_browser(&feed_html(flok, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::groups::Group;
+
+    fn date(date: &str) -> Date {
+        Date::parse(date).unwrap()
+    }
+
+    fn feeding(on: &str, group: &str, quantity: f64) -> Feeding {
+        Feeding {
+            date: date(on),
+            group: group.to_string(),
+            feed: "grass hay".to_string(),
+            quantity,
+        }
+    }
+
+    #[test]
+    fn inventory_and_costs() {
+        let animal = |id: &str, born: &str| Animal {
+            id: vec![id.to_string()],
+            born: Date::parse(born),
+            ..Default::default()
+        };
+        let flok = Flok {
+            animals: vec![
+                animal("1", "2020-03-01"),
+                animal("2", "2020-03-01"),
+                // born after the first feeding
+                animal("3", "2025-01-03"),
+                animal("4", "2020-03-01"),
+            ],
+            groups: vec![
+                Group {
+                    name: "Ewes".to_string(),
+                    members: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+                },
+                Group {
+                    name: "Rams".to_string(),
+                    members: vec!["4".to_string()],
+                },
+            ],
+            feed_purchases: vec![
+                FeedPurchase {
+                    date: date("2024-12-01"),
+                    feed: "Grass hay".to_string(),
+                    quantity: 40.0,
+                    unit: "bales".to_string(),
+                    cost: 300.0,
+                    ..Default::default()
+                },
+                FeedPurchase {
+                    date: date("2024-12-15"),
+                    feed: "grass hay".to_string(),
+                    quantity: 20.0,
+                    unit: "bales".to_string(),
+                    cost: 240.0,
+                    ..Default::default()
+                },
+                FeedPurchase {
+                    date: date("2024-12-15"),
+                    feed: "Sheep mineral".to_string(),
+                    kind: FeedKind::Mineral,
+                    quantity: 50.0,
+                    unit: "lb".to_string(),
+                    cost: 35.0,
+                },
+            ],
+            feedings: vec![
+                feeding("2025-01-01", "Ewes", 2.0),
+                feeding("2025-01-05", "Ewes", 3.0),
+                feeding("2025-01-05", "Rams", 1.0),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(unit_cost(&flok, "GRASS HAY"), Some(9.0));
+
+        let stock = inventory(&flok, date("2025-01-10"));
+        assert_eq!(stock.len(), 2);
+        assert_eq!(
+            (stock[0].bought, stock[0].fed, stock[0].remaining),
+            (60.0, 6.0, 54.0)
+        );
+        // 6 bales over the 10 days since feeding began
+        assert_eq!(stock[0].per_day, Some(0.6));
+        assert_eq!(stock[0].days_left.map(|d| d.round()), Some(90.0));
+        assert_eq!(stock[1].per_day, None);
+        assert_eq!(stock[1].days_left, None);
+        // once feeding started before the window, the rate is over the whole window
+        let later = inventory(&flok, date("2025-01-15"));
+        assert_eq!(later[0].per_day, Some(4.0 / RATE_DAYS as f64));
+
+        // 18 shared by 1 and 2, then 27 by 1, 2 and 3
+        let costs = feed_costs(&flok, Some(2025));
+        assert_eq!(costs.animals, vec![18.0, 18.0, 9.0, 9.0]);
+        assert_eq!(costs.unallocated, 0.0);
+        assert_eq!(feed_costs(&flok, Some(2024)).animals, vec![0.0; 4]);
+        let pnl = ledger::animal_pnl(&flok, Some(2025));
+        assert_eq!(pnl[0].feed, 18.0);
+        assert_eq!(pnl[0].profit, -18.0);
+        let years = ledger::year_pnl(&flok);
+        assert_eq!(years[0].expenses, 575.0);
+
+        // the hay bought in December, also entered in the ledger, is counted once
+        let mut flok = flok;
+        flok.ledger.push(LedgerEntry {
+            date: date("2024-12-01"),
+            category: Category::Hay,
+            amount: 300.0,
+            animal: None,
+            description: "Grass hay".to_string(),
+        });
+        assert_eq!(ledger::year_pnl(&flok)[0].expenses, 575.0);
+        assert_eq!(ledger::animal_pnl(&flok, Some(2024))[0].allocated, 0.0);
+
+        // fed to the rams after the only ram was sold
+        flok.animals[3].events.push(Event {
+            date: date("2025-01-02"),
+            name: "Sold".to_string(),
+            ..Default::default()
+        });
+        let costs = feed_costs(&flok, Some(2025));
+        assert_eq!(costs.animals, vec![18.0, 18.0, 9.0, 0.0]);
+        assert_eq!(costs.unallocated, 9.0);
+        assert!(ledger::pnl_html(&flok).contains("not shared by any animal: 9.00"));
+        assert!(feed_html(&flok, date("2025-01-10")).contains("Sheep mineral"));
+    }
+}
@@ -0,0 +1,239 @@
+use enum_ordinalize::Ordinalize;
+use fltk::{
+    input::{FloatInput, Input},
+    menu::Choice,
+    prelude::{InputExt, MenuExt, WidgetExt},
+    widget::Widget,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    date_input::DateInput,
+    feed::{FeedKind, FeedPurchase, Feeding},
+    flok::*,
+    form::*,
+    list_editor::{show_list_editor, ItemForm},
+    validate::{check_feed_purchase, check_feeding},
+};
+
+/// A quantity or cost, 0 if blank or not a number so the checks report it.
+fn number(input: &FloatInput, field: &'static str, errors: &mut Vec<FieldError>) -> f64 {
+    let text = input.value();
+    text.trim()
+        .trim_start_matches('$')
+        .replace(',', "")
+        .parse()
+        .unwrap_or_else(|_| {
+            errors.push(FieldError::new(
+                field,
+                format!("\"{text}\" is not a number"),
+            ));
+            0.0
+        })
+}
+
+fn date(input: &DateInput, errors: &mut Vec<FieldError>) -> Date {
+    match input.parse("Date") {
+        Ok(Some(date)) => date,
+        Ok(None) => {
+            errors.push(FieldError::new("Date", "Date is required"));
+            Date::today()
+        }
+        Err(e) => {
+            errors.push(e);
+            Date::today()
+        }
+    }
+}
+
+/// The fields of one feed purchase.
+#[derive(Clone)]
+pub struct FeedPurchaseForm {
+    pub date: DateInput,
+    pub feed: Input,
+    pub kind: Choice,
+    pub quantity: FloatInput,
+    pub unit: Input,
+    pub cost: FloatInput,
+}
+
+impl ItemForm<FeedPurchase> for FeedPurchaseForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let mut form = Self {
+            date: DateInput::default(),
+            feed: Input::default(),
+            kind: {
+                let mut kind = Choice::default();
+                for k in FeedKind::VARIANTS {
+                    kind.add_choice(k.name());
+                }
+                kind
+            },
+            quantity: FloatInput::default(),
+            unit: Input::default(),
+            cost: FloatInput::default(),
+        };
+        form.feed
+            .set_tooltip("Purchases and feedings with the same name are the same feed");
+        form.unit
+            .set_tooltip("Like bales or lb, feedings are recorded in the same units");
+        form.cost.set_tooltip("For the whole quantity");
+        let ui = create_form(vec![
+            ("Date", &form.date.group),
+            ("Feed", &form.feed),
+            ("Kind", &form.kind),
+            ("Quantity", &form.quantity),
+            ("Unit", &form.unit),
+            ("Cost", &form.cost),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, p: &FeedPurchase) {
+        // a new purchase is dated today
+        self.date.set_date(Some(if p.quantity == 0.0 {
+            Date::today()
+        } else {
+            p.date
+        }));
+        self.feed.set_value(&p.feed);
+        self.kind.set_value(p.kind.ordinal() as i32);
+        self.quantity.set_value(&if p.quantity == 0.0 {
+            String::new()
+        } else {
+            p.quantity.to_string()
+        });
+        self.unit.set_value(&p.unit);
+        self.cost.set_value(&if p.quantity == 0.0 {
+            String::new()
+        } else {
+            format!("{:.2}", p.cost)
+        });
+    }
+
+    fn item(
+        &mut self,
+        _flok: &Flok,
+        _index: Option<usize>,
+    ) -> Result<FeedPurchase, Vec<FieldError>> {
+        let mut errors = vec![];
+        let purchase = FeedPurchase {
+            date: date(&self.date, &mut errors),
+            feed: self.feed.value().trim().to_string(),
+            kind: FeedKind::from_ordinal(self.kind.value() as i8).unwrap_or_default(),
+            quantity: number(&self.quantity, "Quantity", &mut errors),
+            unit: self.unit.value().trim().to_string(),
+            cost: number(&self.cost, "Cost", &mut errors),
+        };
+        // a field that didn't parse is already reported
+        let checked: Vec<FieldError> = check_feed_purchase(&purchase)
+            .into_iter()
+            .filter(|e| !errors.iter().any(|f| f.field == e.field))
+            .collect();
+        errors.extend(checked);
+
+        highlight(&mut self.date.input, "Date", &errors);
+        highlight(&mut self.feed, "Feed", &errors);
+        highlight(&mut self.quantity, "Quantity", &errors);
+        highlight(&mut self.cost, "Cost", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(purchase)
+    }
+}
+
+/// Edit the hay, grain and minerals bought.
+pub fn show_feed_purchases(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<FeedPurchase, FeedPurchaseForm>(
+        "Feed purchases",
+        flok,
+        |f| &mut f.feed_purchases,
+        |p| {
+            format!(
+                "{} {} {} {} {:.2}",
+                p.date, p.quantity, p.unit, p.feed, p.cost
+            )
+        },
+    )
+}
+
+/// The fields of one feeding.
+#[derive(Clone)]
+pub struct FeedingForm {
+    pub date: DateInput,
+    pub group: Input,
+    pub feed: Input,
+    pub quantity: FloatInput,
+}
+
+impl ItemForm<Feeding> for FeedingForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let mut form = Self {
+            date: DateInput::default(),
+            group: Input::default(),
+            feed: Input::default(),
+            quantity: FloatInput::default(),
+        };
+        form.feed.set_tooltip("A feed that has been bought");
+        form.quantity
+            .set_tooltip("In the units the feed was bought in");
+        let ui = create_form(vec![
+            ("Date", &form.date.group),
+            ("Group", &form.group),
+            ("Feed", &form.feed),
+            ("Quantity", &form.quantity),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, f: &Feeding) {
+        // a new feeding is dated today
+        self.date.set_date(Some(if f.quantity == 0.0 {
+            Date::today()
+        } else {
+            f.date
+        }));
+        self.group.set_value(&f.group);
+        self.feed.set_value(&f.feed);
+        self.quantity.set_value(&if f.quantity == 0.0 {
+            String::new()
+        } else {
+            f.quantity.to_string()
+        });
+    }
+
+    fn item(&mut self, flok: &Flok, _index: Option<usize>) -> Result<Feeding, Vec<FieldError>> {
+        let mut errors = vec![];
+        let feeding = Feeding {
+            date: date(&self.date, &mut errors),
+            group: self.group.value().trim().to_string(),
+            feed: self.feed.value().trim().to_string(),
+            quantity: number(&self.quantity, "Quantity", &mut errors),
+        };
+        let checked: Vec<FieldError> = check_feeding(flok, &feeding)
+            .into_iter()
+            .filter(|e| !errors.iter().any(|f| f.field == e.field))
+            .collect();
+        errors.extend(checked);
+
+        highlight(&mut self.date.input, "Date", &errors);
+        highlight(&mut self.group, "Group", &errors);
+        highlight(&mut self.feed, "Feed", &errors);
+        highlight(&mut self.quantity, "Quantity", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(feeding)
+    }
+}
+
+/// Edit the feed given to each group.
+pub fn show_feedings(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Feeding, FeedingForm>(
+        "Feedings",
+        flok,
+        |f| &mut f.feedings,
+        |f| format!("{} {} {} to {}", f.date, f.quantity, f.feed, f.group),
+    )
+}
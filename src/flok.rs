@@ -1,11 +1,11 @@
-use chrono::{DateTime, Local};
 use enum_ordinalize::Ordinalize;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 pub type Id = String;
-pub type Date = DateTime<Local>;
+pub use crate::date::{Date, DateFormat};
 
-#[derive(Serialize, Deserialize, Debug, Default, Ordinalize, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Ordinalize, Clone, Copy, PartialEq, Eq)]
 pub enum Sex {
     Male,
     #[default]
@@ -22,21 +22,227 @@ impl Sex {
 impl From<String> for Sex {
     fn from(s: String) -> Self {
         match s.to_lowercase().as_str() {
-            "m"| "male" => Sex::Male,
+            "m" | "male" => Sex::Male,
             _ => Sex::Female,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Event {
     pub name: String,
     pub value: String,
     pub date: Date,
     pub notes: String,
+    /// Name of the contact an animal was bought from or sold to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+impl Event {
+    /// Sales are events named like "sale", "sold" or "sale $".
+    pub fn is_sale(&self) -> bool {
+        let name = self.name.to_lowercase();
+        name.contains("sale") || name.contains("sold")
+    }
+    /// Purchases are events named like "purchase" or "bought".
+    pub fn is_purchase(&self) -> bool {
+        let name = self.name.to_lowercase();
+        name.contains("purchase") || name.contains("bought")
+    }
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Default, Ordinalize, clap::ValueEnum, Clone, Copy, PartialEq, Eq,
+)]
+pub enum WeightUnit {
+    #[default]
+    Lb,
+    Kg,
+}
+
+impl WeightUnit {
+    pub fn name(&self) -> &str {
+        match self {
+            WeightUnit::Lb => "lb",
+            WeightUnit::Kg => "kg",
+        }
+    }
+
+    /// `value` in this unit converted to `to`.
+    pub fn convert(&self, value: f64, to: WeightUnit) -> f64 {
+        const LB_PER_KG: f64 = 2.204_622_6;
+        match (self, to) {
+            (WeightUnit::Lb, WeightUnit::Kg) => value / LB_PER_KG,
+            (WeightUnit::Kg, WeightUnit::Lb) => value * LB_PER_KG,
+            _ => value,
+        }
+    }
+}
+
+/// A weighing. Weights used to be events with the number in `value`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Weight {
+    pub date: Date,
+    pub value: f64,
+    pub unit: WeightUnit,
+}
+
+impl Weight {
+    pub fn value_in(&self, unit: WeightUnit) -> f64 {
+        self.unit.convert(self.value, unit)
+    }
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Default, Ordinalize, clap::ValueEnum, Clone, Copy, PartialEq, Eq,
+)]
+pub enum ScoreKind {
+    /// Eye lid colour for anaemia from barber pole worm, 1 (red) to 5 (white). Higher is worse.
+    #[default]
+    Famacha,
+    /// Body condition, 1 (emaciated) to 5 (obese) in half steps. Lower is worse.
+    Bcs,
+}
+
+impl ScoreKind {
+    pub fn name(&self) -> &str {
+        match self {
+            ScoreKind::Famacha => "FAMACHA",
+            ScoreKind::Bcs => "BCS",
+        }
+    }
+
+    /// The smallest change between scores.
+    pub fn step(&self) -> f64 {
+        match self {
+            ScoreKind::Famacha => 1.0,
+            ScoreKind::Bcs => 0.5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Score {
+    pub date: Date,
+    pub kind: ScoreKind,
+    pub value: f64,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Default, Ordinalize, clap::ValueEnum, Clone, Copy, PartialEq, Eq,
+)]
+pub enum ProductKind {
+    #[default]
+    Wormer,
+    Vaccine,
+    Antibiotic,
+    Other,
+}
+
+impl ProductKind {
+    pub fn name(&self) -> &str {
+        match self {
+            ProductKind::Wormer => "Wormer",
+            ProductKind::Vaccine => "Vaccine",
+            ProductKind::Antibiotic => "Antibiotic",
+            ProductKind::Other => "Other",
+        }
+    }
+}
+
+/// A drug or vaccine. Events named after a product are treatments with it, the dose in `value`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Product {
+    pub name: String,
+    pub kind: ProductKind,
+    /// Days after a treatment before the animal can be slaughtered
+    pub meat_withdrawal: u32,
+    /// Days after a treatment before milk can be used
+    pub milk_withdrawal: u32,
+    /// Dose in ml for each kg the animal weighs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dose_per_kg: Option<f64>,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Default, Ordinalize, clap::ValueEnum, Clone, Copy, PartialEq, Eq,
+)]
+pub enum Category {
+    #[default]
+    Sale,
+    Wool,
+    OtherIncome,
+    Purchase,
+    Feed,
+    Hay,
+    Vet,
+    Supplies,
+    OtherExpense,
+}
+
+impl Category {
+    pub fn name(&self) -> &str {
+        match self {
+            Category::Sale => "Sale",
+            Category::Wool => "Wool",
+            Category::OtherIncome => "Other income",
+            Category::Purchase => "Purchase",
+            Category::Feed => "Feed",
+            Category::Hay => "Hay",
+            Category::Vet => "Vet",
+            Category::Supplies => "Supplies",
+            Category::OtherExpense => "Other expense",
+        }
+    }
+
+    pub fn is_income(&self) -> bool {
+        matches!(
+            self,
+            Category::Sale | Category::Wool | Category::OtherIncome
+        )
+    }
+}
+
+/// Someone animals are bought from or sold to.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Contact {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub farm: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub address: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub phone: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub premises_id: String,
+}
+
+/// Money in or out, for one animal or, without one, the whole flock.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub date: Date,
+    pub category: Category,
+    /// Always positive, the category says whether it is income or an expense
+    pub amount: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animal: Option<Id>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+impl LedgerEntry {
+    /// The amount, negative for an expense.
+    pub fn signed(&self) -> f64 {
+        if self.category.is_income() {
+            self.amount
+        } else {
+            -self.amount
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Animal {
     // first is most recent
     pub id: Vec<Id>,
@@ -46,19 +252,155 @@ pub struct Animal {
     pub description: String,
     pub events: Vec<Event>,
     pub sex: Sex,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weights: Vec<Weight>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scores: Vec<Score>,
+}
+
+impl Animal {
+    /// Neither dead nor sold.
+    pub fn in_flock(&self) -> bool {
+        self.died().is_none() && !self.events.iter().any(|e| e.is_sale())
+    }
+
+    /// Scores of one kind in date order.
+    pub fn scores_of(&self, kind: ScoreKind) -> Vec<Score> {
+        let mut scores: Vec<Score> = self
+            .scores
+            .iter()
+            .filter(|s| s.kind == kind)
+            .copied()
+            .collect();
+        scores.sort_by_key(|s| s.date);
+        scores
+    }
+
+    /// The first event recording this animal's death, the cause in its value or notes.
+    pub fn death(&self) -> Option<&Event> {
+        self.events
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.name.trim().to_lowercase().as_str(),
+                    "died" | "death" | "dead" | "deceased"
+                )
+            })
+            .min_by_key(|e| e.date)
+    }
+
+    /// Date of the first event recording this animal's death.
+    pub fn died(&self) -> Option<Date> {
+        self.death().map(|e| e.date)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Flok {
     pub name: String,
     pub animals: Vec<Animal>,
+    #[serde(default)]
+    pub date_format: DateFormat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncState>,
+    /// Unit weights are shown and compared in
+    #[serde(default)]
+    pub weight_unit: WeightUnit,
+    /// How many backups to keep next to the file, `None` for the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backups: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub products: Vec<Product>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protocols: Vec<crate::health::Protocol>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exposures: Vec<crate::breeding::Exposure>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ledger: Vec<LedgerEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contacts: Vec<Contact>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<crate::groups::Group>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pastures: Vec<crate::groups::Pasture>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feed_purchases: Vec<crate::feed::FeedPurchase>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feedings: Vec<crate::feed::Feeding>,
+    /// The farm's own premises ID, for movement records
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub premises_id: String,
+}
+
+/// Where a flock is shared, and the server revision it was last synced with.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct SyncState {
+    pub server: String,
+    pub name: String,
+    pub revision: u64,
 }
 
 impl Flok {
+    pub const DEFAULT_BACKUPS: usize = 10;
+
+    pub fn backups(&self) -> usize {
+        self.backups.unwrap_or(Self::DEFAULT_BACKUPS)
+    }
     pub fn find(&self, id: Id) -> Option<&Animal> {
         self.animals.iter().find(|a| a.id.contains(&id))
     }
+    /// The product an event named `name` is a treatment with.
+    pub fn product(&self, name: &str) -> Option<&Product> {
+        let name = name.trim();
+        self.products
+            .iter()
+            .find(|p| p.name.trim().eq_ignore_ascii_case(name))
+    }
+    /// The contact called `name`.
+    pub fn contact(&self, name: &str) -> Option<&Contact> {
+        let name = name.trim();
+        self.contacts
+            .iter()
+            .find(|c| c.name.trim().eq_ignore_ascii_case(name))
+    }
     pub fn dam(&self, id: Id) -> Option<Id> {
         self.find(id).and_then(|a| a.dam.clone())
     }
+    pub fn sire(&self, id: Id) -> Option<Id> {
+        self.find(id).and_then(|a| a.sire.clone())
+    }
+
+    /// Ids of animals that could be the `sex` parent of an animal born on `born`.
+    /// Animals with an unknown birth date are included, as are all animals when `born` is unknown.
+    pub fn parent_candidates(&self, sex: Sex, born: Option<Date>) -> Vec<Id> {
+        self.animals
+            .iter()
+            .filter(|a| a.sex == sex)
+            .filter(|a| match (a.born, born) {
+                (Some(parent), Some(child)) => parent < child,
+                _ => true,
+            })
+            .filter_map(|a| a.id.first().cloned())
+            .collect()
+    }
+
+    /// Every id reachable from `id` through sire and dam links, including all tags of each
+    /// ancestor.
+    pub fn ancestors(&self, id: Id) -> HashSet<Id> {
+        let mut result = HashSet::new();
+        let mut todo = vec![id];
+        while let Some(id) = todo.pop() {
+            if let Some(animal) = self.find(id) {
+                for parent in animal.sire.iter().chain(animal.dam.iter()) {
+                    if result.insert(parent.clone()) {
+                        if let Some(p) = self.find(parent.clone()) {
+                            result.extend(p.id.iter().cloned());
+                        }
+                        todo.push(parent.clone());
+                    }
+                }
+            }
+        }
+        result
+    }
 }
@@ -0,0 +1,20 @@
+use anyhow::Result;
+
+use crate::{backup, crypto, flok::*, journal, storage};
+
+pub fn load(file: &str) -> Result<Flok> {
+    storage::open(file)?.load()
+}
+
+/// Back up the previous save, write the flock and append what changed to its journal. With a
+/// passphrase, the journal and backups written before it was set are encrypted too.
+pub fn save(file: &str, flok: &Flok) -> Result<()> {
+    backup::backup(file, flok.backups())?;
+    storage::open(file)?.save(flok)?;
+    journal::record(file, flok)?;
+    if let Some(passphrase) = crypto::passphrase() {
+        journal::reseal(file, &passphrase)?;
+        backup::reseal(file, &passphrase)?;
+    }
+    Ok(())
+}
@@ -15,19 +15,25 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::{animal_form::*, business_obj::*, flok::*, form::*};
+use crate::{animal_form::*, business_obj::*, flok::*, form::*, groups, journal};
 
 pub struct FlokForm {
     pub pack: Pack,
     pub name: Input,
     pub table: JoeTable<FlokTableModel>,
     pub flok: Arc<Mutex<Flok>>,
+    /// Where the flock was loaded from or last saved to
+    pub file: Arc<Mutex<Option<String>>>,
+    /// The group shown in the table, `None` for every animal
+    pub group: Arc<Mutex<Option<String>>>,
 }
 
 impl FlokForm {
     pub fn create(flok: Flok) -> Self {
         let flok = Arc::new(Mutex::new(flok));
-        let model = FlokTableModel::new(flok.clone());
+        let file = Arc::new(Mutex::new(None));
+        let group = Arc::new(Mutex::new(None));
+        let model = FlokTableModel::new(flok.clone(), file.clone(), group.clone());
         let pack = Pack::default_fill().with_type(PackType::Vertical);
         let name = Default::default();
         let table = JoeTable::new(model);
@@ -38,6 +44,8 @@ impl FlokForm {
             name,
             table,
             flok,
+            file,
+            group,
         }
     }
     pub fn update(&mut self) {
@@ -47,27 +55,51 @@ impl FlokForm {
 
 impl Editor<Flok> for FlokForm {
     fn set_value(&mut self, flok: &Flok) {
+        crate::date::set_format(flok.date_format);
         (*self.flok.lock().unwrap()) = flok.clone();
         self.update();
     }
-    fn commit(&mut self) {
+    fn commit(&mut self) -> Result<(), Vec<FieldError>> {
         self.flok.exec(|f| f.name = self.name.value());
+        Ok(())
     }
 }
 
 #[derive(Default)]
 pub struct FlokTableModel {
+    /// By the index of the animal in the flock, which differs from the row when filtered
     pub edit_buttons: HashMap<i32, Widget>,
     pub flok: Arc<Mutex<Flok>>,
+    pub file: Arc<Mutex<Option<String>>>,
+    pub group: Arc<Mutex<Option<String>>>,
 }
 
 impl FlokTableModel {
-    pub fn new(flok: Arc<Mutex<Flok>>) -> Self {
+    pub fn new(
+        flok: Arc<Mutex<Flok>>,
+        file: Arc<Mutex<Option<String>>>,
+        group: Arc<Mutex<Option<String>>>,
+    ) -> Self {
         Self {
             edit_buttons: Default::default(),
             flok,
+            file,
+            group,
         }
     }
+
+    /// The index in the flock of the animal on each row.
+    fn shown(&self) -> Vec<usize> {
+        let group = self.group.lock().unwrap().clone();
+        self.flok.exec(|f| {
+            f.animals
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| group.as_ref().is_none_or(|g| groups::in_group(f, g, a)))
+                .map(|(i, _)| i)
+                .collect()
+        })
+    }
 }
 
 pub const COLUMNS: [(&str, u32); 5] = [
@@ -80,7 +112,7 @@ pub const COLUMNS: [(&str, u32); 5] = [
 
 impl SimpleModel for FlokTableModel {
     fn row_count(&mut self) -> usize {
-        self.flok.exec(|f| f.animals.len())
+        self.shown().len()
     }
 
     fn column_count(&mut self) -> usize {
@@ -96,13 +128,13 @@ impl SimpleModel for FlokTableModel {
     }
 
     fn cell(&mut self, row: i32, col: i32) -> Option<String> {
-        let row = row as usize;
+        let shown = self.shown();
         // column 4 uses a widget
-        if row >= self.row_count() || col == 4 || col >= COLUMNS.len() as i32 {
+        if row as usize >= shown.len() || col == 4 || col >= COLUMNS.len() as i32 {
             None
         } else {
             self.flok.exec(|f| {
-                let animal = &f.animals[row];
+                let animal = &f.animals[shown[row as usize]];
                 let r = match col {
                     0 => animal.id[0].clone(),
                     1 => animal.born.map(|b| b.to_string()).unwrap_or_default(),
@@ -116,14 +148,13 @@ impl SimpleModel for FlokTableModel {
     }
 
     fn cell_widget(&mut self, row_index: i32, _col: i32) -> Option<Widget> {
-        let urow = row_index as usize;
-        if urow >= self.flok.exec(|f| f.animals.len()) {
-            return None;
-        }
+        let urow = *self.shown().get(row_index as usize)?;
         let a = self.flok.clone().map(move |f| &mut f.animals[urow]);
+        let flok = self.flok.clone();
+        let file = self.file.clone();
         Some(
             self.edit_buttons
-                .entry(row_index)
+                .entry(urow as i32)
                 .or_insert_with(|| {
                     let mut b = Button::default().with_size(30, 20).with_label("Edit");
                     b.set_callback(move |_| {
@@ -137,7 +168,8 @@ impl SimpleModel for FlokTableModel {
                             .with_type(FlexType::Column);
 
                         let (mut form, ui) =
-                            AnimalForm::create(a.clone()).expect("Unable to create Animal Form");
+                            AnimalForm::create(a.clone(), flok.clone(), Some(urow))
+                                .expect("Unable to create Animal Form");
 
                         form.set_value(&a);
                         page.fixed(&ui, ui.height());
@@ -147,6 +179,25 @@ impl SimpleModel for FlokTableModel {
                             .with_align(Align::Right)
                             .size_of_parent();
                         buttons.resizable(&Frame::default());
+                        {
+                            let mut history =
+                                Button::default().size_of_parent().with_label("History");
+                            let a = a.clone();
+                            let file = file.clone();
+                            history.set_callback(move |_b| {
+                                let id = a.exec(|a| a.id.first().cloned().unwrap_or_default());
+                                match file.lock().unwrap().as_ref() {
+                                    Some(file) => display_error(
+                                        "Unable to show history",
+                                        journal::report_history(file, &id),
+                                    ),
+                                    None => fltk::dialog::message_default(
+                                        "History is kept once the flock is saved",
+                                    ),
+                                }
+                            });
+                            buttons.fixed(&history, 60);
+                        }
                         {
                             let mut cancel =
                                 Button::default().size_of_parent().with_label("Cancel");
@@ -157,10 +208,12 @@ impl SimpleModel for FlokTableModel {
                         {
                             let mut save = Button::default().size_of_parent().with_label("Save");
                             let mut wind = wind.clone();
-                            save.set_callback(move |_b| {
-                                form.commit();
-
-                                wind.hide();
+                            save.set_callback(move |_b| match form.commit() {
+                                Ok(()) => wind.hide(),
+                                Err(errors) => display_error::<()>(
+                                    "Unable to save animal",
+                                    Err(validation_error(errors)),
+                                ),
                             });
                             buttons.fixed(&save, 60);
                         }
@@ -1,3 +1,4 @@
+use fltk::enums::Color;
 use fltk::prelude::{GroupExt, WidgetBase, WidgetExt};
 
 use fltk::frame::Frame;
@@ -8,6 +9,8 @@ use fltk::widget::Widget;
 
 use anyhow::Result;
 
+pub use crate::validate::{validation_error, FieldError};
+
 const SIZE: i32 = 20;
 
 pub fn create_form(fields: Vec<(&str, &dyn FromWidget)>) -> Result<Widget> {
@@ -46,7 +49,25 @@ impl<W: WidgetBase> FromWidget for W {
 /// This doesn't add value, but keeps us honest
 pub trait Editor<T> {
     fn set_value(&mut self, value: &T);
-    fn commit(&mut self);
+    /// Copy the UI into the value, or leave the value alone and report every invalid field.
+    fn commit(&mut self) -> Result<(), Vec<FieldError>>;
+}
+
+/// Mark `widget` as invalid, with the reasons in its tooltip, if `errors` mention `field`.
+pub fn highlight<W: WidgetExt>(widget: &mut W, field: &str, errors: &[FieldError]) {
+    let messages: Vec<&str> = errors
+        .iter()
+        .filter(|e| e.field == field)
+        .map(|e| e.message.as_str())
+        .collect();
+    if messages.is_empty() {
+        widget.set_color(Color::Background2);
+        widget.set_tooltip("");
+    } else {
+        widget.set_color(Color::from_rgb(255, 200, 200));
+        widget.set_tooltip(&messages.join("\n"));
+    }
+    widget.redraw();
 }
 
 pub fn display_error<T>(action: &str, result: Result<T>) {
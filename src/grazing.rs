@@ -0,0 +1,475 @@
+use anyhow::Result;
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Serialize;
+
+use crate::{
+    flok::*,
+    groups,
+    report::{escape, open_in_browser},
+};
+
+/// An animal unit is 1000 lb of animal; a ewe whose weight isn't known counts as a fifth.
+pub const LB_PER_ANIMAL_UNIT: f64 = 1000.0;
+pub const SHEEP_ANIMAL_UNITS: f64 = 0.2;
+
+const WIDTH: f64 = 900.0;
+const LANE: f64 = 30.0;
+const LEFT: f64 = 140.0;
+const TOP: f64 = 50.0;
+
+/// One animal in one pasture from `from` until the day it moved on or left the flock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stay {
+    pub id: Id,
+    pub pasture: String,
+    pub from: Date,
+    /// `None` while it is still there
+    pub to: Option<Date>,
+    pub animal_units: f64,
+}
+
+/// The animal's size in animal units on `date`, from its last weighing by then.
+pub fn animal_units(animal: &Animal, date: Date) -> f64 {
+    animal
+        .weights
+        .iter()
+        .filter(|w| w.date.day <= date.day)
+        .max_by_key(|w| w.date)
+        .map(|w| w.value_in(WeightUnit::Lb) / LB_PER_ANIMAL_UNIT)
+        .unwrap_or(SHEEP_ANIMAL_UNITS)
+}
+
+/// Every animal's time in each pasture, from its "moved to" events.
+pub fn stays(flok: &Flok) -> Vec<Stay> {
+    let mut stays = vec![];
+    for a in &flok.animals {
+        let id = a.id.first().cloned().unwrap_or_default();
+        let mut moves: Vec<&Event> = a.events.iter().filter(|e| groups::is_move(e)).collect();
+        moves.sort_by_key(|e| e.date);
+        let left = a
+            .events
+            .iter()
+            .filter(|e| e.is_sale())
+            .map(|e| e.date)
+            .chain(a.died())
+            .min();
+        for (i, m) in moves.iter().enumerate() {
+            let to = moves.get(i + 1).map(|n| n.date).or(left);
+            if to.is_some_and(|to| to.day <= m.date.day) {
+                continue;
+            }
+            stays.push(Stay {
+                id: id.clone(),
+                pasture: m.value.trim().to_string(),
+                from: m.date,
+                to,
+                animal_units: animal_units(a, m.date),
+            });
+        }
+    }
+    stays
+}
+
+/// The days of `stay` from `from` up to but not including `until`.
+fn clip(stay: &Stay, from: NaiveDate, until: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let start = stay.from.day.max(from);
+    let end = stay.to.map_or(until, |to| to.day.min(until));
+    (start < end).then_some((start, end))
+}
+
+/// Join overlapping spans, in date order.
+fn merge(mut spans: Vec<(NaiveDate, NaiveDate)>) -> Vec<(NaiveDate, NaiveDate)> {
+    spans.sort();
+    let mut merged: Vec<(NaiveDate, NaiveDate)> = vec![];
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// How one pasture has been grazed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Grazing {
+    pub pasture: String,
+    pub acres: Option<f64>,
+    pub rest_target: u32,
+    pub animals_now: usize,
+    /// Days in the period with animals on it
+    pub occupied_days: i64,
+    pub animal_unit_days: f64,
+    pub aud_per_acre: Option<f64>,
+    /// Days the animals there now have been in
+    pub grazing_for: Option<i64>,
+    /// The last day it had animals on it
+    pub last_grazed: Option<Date>,
+    /// Days since it was last grazed, 0 while grazed, `None` if never grazed
+    pub rested: Option<i64>,
+}
+
+impl Grazing {
+    /// Days until it has rested for its target, 0 when it has.
+    pub fn ready_in(&self) -> i64 {
+        self.rested
+            .map_or(0, |r| (self.rest_target as i64 - r).max(0))
+    }
+}
+
+/// Each pasture's grazing from `from` to `date`, and how it stands on `date`.
+pub fn grazing(flok: &Flok, from: Date, date: Date) -> Vec<Grazing> {
+    let stays = stays(flok);
+    let until = date.day + Days::new(1);
+    flok.pastures
+        .iter()
+        .map(|p| {
+            let here: Vec<&Stay> = stays
+                .iter()
+                .filter(|s| s.pasture.eq_ignore_ascii_case(p.name.trim()))
+                .collect();
+            let spans: Vec<(NaiveDate, NaiveDate)> = here
+                .iter()
+                .filter_map(|s| clip(s, from.day, until))
+                .collect();
+            // an empty f64 sum is -0
+            let animal_unit_days = here
+                .iter()
+                .filter_map(|s| {
+                    clip(s, from.day, until)
+                        .map(|(start, end)| (end - start).num_days() as f64 * s.animal_units)
+                })
+                .fold(0.0, |total, aud| total + aud);
+            let occupied = merge(spans);
+            let ever = merge(
+                here.iter()
+                    .filter_map(|s| clip(s, NaiveDate::MIN, until))
+                    .collect(),
+            );
+            let now = here
+                .iter()
+                .filter(|s| clip(s, date.day, until).is_some())
+                .count();
+            let last = ever.last();
+            Grazing {
+                pasture: p.name.clone(),
+                acres: p.acres,
+                rest_target: p.rest_days(),
+                animals_now: now,
+                occupied_days: occupied.iter().map(|(s, e)| (*e - *s).num_days()).sum(),
+                animal_unit_days,
+                aud_per_acre: p.acres.filter(|a| *a > 0.0).map(|a| animal_unit_days / a),
+                grazing_for: last
+                    .filter(|_| now > 0)
+                    .map(|(start, _)| (until - *start).num_days()),
+                last_grazed: last.map(|(_, end)| (*end - Days::new(1)).into()),
+                rested: last.map(|(_, end)| (until - *end).num_days()),
+            }
+        })
+        .collect()
+}
+
+/// Where to move the animals that have been in one pasture longest.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// `None` when no pasture is grazed
+    pub from: Option<String>,
+    pub to: String,
+    /// Days until `to` has had its rest, 0 when it has
+    pub ready_in: i64,
+}
+
+impl Suggestion {
+    pub fn describe(&self, grazing: &[Grazing]) -> String {
+        let to = grazing.iter().find(|g| g.pasture == self.to);
+        let rested = match to.and_then(|g| g.rested) {
+            Some(days) => format!("rested {days} days"),
+            None => "not grazed yet".to_string(),
+        };
+        let ready = if self.ready_in > 0 {
+            format!(", ready in {} days", self.ready_in)
+        } else {
+            String::new()
+        };
+        match &self.from {
+            Some(from) => {
+                let from = grazing.iter().find(|g| &g.pasture == from);
+                format!(
+                    "Move the {} animals in {} ({} days) to {} ({rested}{ready})",
+                    from.map_or(0, |g| g.animals_now),
+                    from.map_or("", |g| &g.pasture),
+                    from.and_then(|g| g.grazing_for).unwrap_or(0),
+                    self.to
+                )
+            }
+            None => format!("Next to graze: {} ({rested}{ready})", self.to),
+        }
+    }
+}
+
+/// The empty pasture to move to next: the most rested of those that have had their rest, else
+/// the one ready soonest.
+pub fn suggest(grazing: &[Grazing]) -> Option<Suggestion> {
+    let from = grazing
+        .iter()
+        .filter(|g| g.animals_now > 0)
+        .max_by_key(|g| g.grazing_for)
+        .map(|g| g.pasture.clone());
+    grazing
+        .iter()
+        .filter(|g| g.animals_now == 0)
+        .min_by_key(|g| {
+            (
+                g.ready_in(),
+                std::cmp::Reverse(g.rested.unwrap_or(i64::MAX)),
+            )
+        })
+        .map(|g| Suggestion {
+            from,
+            to: g.pasture.clone(),
+            ready_in: g.ready_in(),
+        })
+}
+
+/// A lane per pasture from `from` to `to` with a bar for each time it was grazed, shaded while
+/// it rests for its target afterwards.
+pub fn timeline_svg(flok: &Flok, from: Date, to: Date) -> String {
+    let stays = stays(flok);
+    let until = to.day + Days::new(1);
+    let days = (until - from.day).num_days().max(1) as f64;
+    let x =
+        |day: NaiveDate| LEFT + (day - from.day).num_days() as f64 / days * (WIDTH - LEFT - 10.0);
+    let height = TOP + LANE * flok.pastures.len() as f64 + 20.0;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{height}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\n\
+         <text x=\"10\" y=\"20\" font-size=\"16\">Grazing {from} to {to}</text>\n"
+    );
+    // a line at the start of each month
+    let mut month = NaiveDate::from_ymd_opt(from.day.year(), from.day.month(), 1).unwrap();
+    while month < until {
+        if month >= from.day {
+            svg += &format!(
+                "<line x1=\"{x:.1}\" y1=\"{TOP}\" x2=\"{x:.1}\" y2=\"{b}\" stroke=\"lightgray\"/>\n\
+                 <text x=\"{x:.1}\" y=\"{t}\">{}</text>\n",
+                month.format("%b"),
+                x = x(month),
+                b = height - 20.0,
+                t = TOP - 5.0,
+            );
+        }
+        month = month
+            .checked_add_months(chrono::Months::new(1))
+            .unwrap_or(until);
+    }
+    for (i, p) in flok.pastures.iter().enumerate() {
+        let y = TOP + LANE * i as f64;
+        svg += &format!(
+            "<text x=\"10\" y=\"{:.1}\">{}</text>\n",
+            y + LANE / 2.0 + 4.0,
+            escape(&p.name)
+        );
+        let here: Vec<&Stay> = stays
+            .iter()
+            .filter(|s| s.pasture.eq_ignore_ascii_case(p.name.trim()))
+            .collect();
+        for (start, end) in merge(
+            here.iter()
+                .filter_map(|s| clip(s, from.day, until))
+                .collect(),
+        ) {
+            let rest_end = (end + Days::new(p.rest_days() as u64)).min(until);
+            let animals = here
+                .iter()
+                .filter(|s| clip(s, start, end).is_some())
+                .count();
+            svg += &format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                 fill=\"#d8ecd0\"/>\n\
+                 <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                 fill=\"steelblue\"/>\n\
+                 <text x=\"{:.1}\" y=\"{:.1}\" fill=\"white\">{animals}</text>\n",
+                x(end),
+                y + 4.0,
+                (x(rest_end) - x(end)).max(0.0),
+                LANE - 8.0,
+                x(start),
+                y + 4.0,
+                (x(end) - x(start)).max(1.0),
+                LANE - 8.0,
+                x(start) + 3.0,
+                y + LANE / 2.0 + 4.0,
+            );
+        }
+    }
+    let today = Date::today().day;
+    if today >= from.day && today < until {
+        svg += &format!(
+            "<line x1=\"{x:.1}\" y1=\"{TOP}\" x2=\"{x:.1}\" y2=\"{b}\" stroke=\"red\"/>\n",
+            x = x(today),
+            b = height - 20.0
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+/// The first and last days of the year `date` is in.
+pub fn year_of(date: Date) -> (Date, Date) {
+    let year = date.day.year();
+    (
+        NaiveDate::from_ymd_opt(year, 1, 1).unwrap().into(),
+        NaiveDate::from_ymd_opt(year, 12, 31).unwrap().into(),
+    )
+}
+
+/// This year's grazing to `date` with the suggested next move and the timeline.
+pub fn grazing_html(flok: &Flok, date: Date) -> String {
+    let (from, to) = year_of(date);
+    let grazing = grazing(flok, from, date);
+    let suggestion = suggest(&grazing)
+        .map(|s| format!("<p><b>{}</b></p>\n", escape(&s.describe(&grazing))))
+        .unwrap_or_default();
+    let number = |n: Option<f64>| n.map(|n| format!("{n:.1}")).unwrap_or_default();
+    let rows: String = grazing
+        .iter()
+        .map(|g| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td>\
+                 <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&g.pasture),
+                number(g.acres),
+                g.animals_now,
+                g.grazing_for.map(|d| d.to_string()).unwrap_or_default(),
+                g.occupied_days,
+                g.animal_unit_days,
+                number(g.aud_per_acre),
+                g.last_grazed.map(|d| d.to_string()).unwrap_or_default(),
+                g.rested
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| "never grazed".to_string()),
+                g.rest_target
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Grazing</title></head><body>\n<h1>Grazing on {date}</h1>\n\
+         {suggestion}<table border=\"1\">\n<tr><th>Pasture</th><th>Acres</th><th>Animals now</th>\
+         <th>In for (days)</th><th>Days grazed this year</th><th>Animal unit days</th>\
+         <th>AUD per acre</th><th>Last grazed</th><th>Rested (days)</th><th>Rest target</th></tr>\n\
+         {rows}</table>\n<p>Animal units are from each animal's last weight, 1000 lb to the unit, \
+         or {SHEEP_ANIMAL_UNITS} for an animal not weighed.</p>\n{}</body></html>\n",
+        timeline_svg(flok, from, to)
+    )
+}
+
+pub fn report_grazing(flok: &Flok) -> Result<()> {
+    open_in_browser(&grazing_html(flok, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::groups::{Pasture, MOVE_EVENT};
+
+    fn moved(pasture: &str, date: &str) -> Event {
+        Event {
+            name: MOVE_EVENT.to_string(),
+            value: pasture.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rotation() {
+        let pasture = |name: &str, acres, rest_days| Pasture {
+            name: name.to_string(),
+            acres,
+            rest_days,
+            ..Default::default()
+        };
+        let animal = |id: &str, lb: Option<f64>, events| Animal {
+            id: vec![id.to_string()],
+            weights: lb
+                .map(|value| Weight {
+                    date: Date::parse("2025-01-01").unwrap(),
+                    value,
+                    unit: WeightUnit::Lb,
+                })
+                .into_iter()
+                .collect(),
+            events,
+            ..Default::default()
+        };
+        let flok = Flok {
+            pastures: vec![
+                pasture("North", Some(5.0), None),
+                pasture("Creek", None, Some(20)),
+                pasture("Hill", None, None),
+            ],
+            animals: vec![
+                animal(
+                    "1",
+                    Some(150.0),
+                    vec![
+                        moved("North", "2025-05-01"),
+                        moved("Creek", "2025-05-11"),
+                        moved("North", "2025-06-01"),
+                    ],
+                ),
+                animal(
+                    "2",
+                    None,
+                    vec![moved("North", "2025-05-06"), moved("Creek", "2025-05-11")],
+                ),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(animal_units(&flok.animals[0], Date::today()), 0.15);
+        let from = Date::parse("2025-01-01").unwrap();
+        let date = Date::parse("2025-06-10").unwrap();
+        let pastures = grazing(&flok, from, date);
+        let summary: Vec<String> = pastures
+            .iter()
+            .map(|g| {
+                format!(
+                    "{} now {} days {} aud {:.2} per acre {:?} for {:?} last {:?} rested {:?}",
+                    g.pasture,
+                    g.animals_now,
+                    g.occupied_days,
+                    g.animal_unit_days,
+                    g.aud_per_acre.map(|a| (a * 100.0).round() / 100.0),
+                    g.grazing_for,
+                    g.last_grazed.map(|d| d.to_iso()),
+                    g.rested
+                )
+            })
+            .collect();
+        // North: 1 for 10 days and 10 days from June 1st, 2 for 5 days
+        // Creek: 1 for 21 days, 2 since May 11th
+        assert_eq!(
+            summary,
+            vec![
+                "North now 1 days 20 aud 4.00 per acre Some(0.8) for Some(10) \
+                 last Some(\"2025-06-10\") rested Some(0)",
+                "Creek now 1 days 31 aud 9.35 per acre None for Some(31) \
+                 last Some(\"2025-06-10\") rested Some(0)",
+                "Hill now 0 days 0 aud 0.00 per acre None for None last None rested None",
+            ]
+        );
+        let suggestion = suggest(&pastures).unwrap();
+        assert_eq!(suggestion.from.as_deref(), Some("Creek"));
+        assert_eq!(suggestion.to, "Hill");
+        assert_eq!(suggestion.ready_in, 0);
+
+        // once 2 leaves Creek, it rests
+        let later = Date::parse("2025-06-20").unwrap();
+        let mut flok = flok;
+        flok.animals[1].events.push(moved("North", "2025-06-15"));
+        let creek = &grazing(&flok, from, later)[1];
+        assert_eq!(creek.rested, Some(6));
+        assert_eq!(creek.ready_in(), 14);
+        assert!(timeline_svg(&flok, from, later).contains("Creek"));
+    }
+}
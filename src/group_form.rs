@@ -0,0 +1,324 @@
+use fltk::{
+    button::Button,
+    dialog,
+    frame::Frame,
+    group::Flex,
+    input::{FloatInput, Input, IntInput, MultilineInput},
+    menu::Choice,
+    prelude::{GroupExt, InputExt, MenuExt, WidgetBase, WidgetExt, WindowExt},
+    widget::Widget,
+    window::Window,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    business_obj::BusinessObject,
+    date_input::DateInput,
+    flok::*,
+    form::*,
+    groups::{self, Group, Pasture},
+    list_editor::{show_list_editor, ItemForm},
+    validate::check_event,
+};
+
+/// The fields of one group of animals.
+#[derive(Clone)]
+pub struct GroupForm {
+    pub name: Input,
+    pub members: MultilineInput,
+}
+
+impl ItemForm<Group> for GroupForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let mut form = Self {
+            name: Input::default(),
+            members: MultilineInput::default(),
+        };
+        form.members
+            .set_tooltip("Animal ids, separated by commas or on separate lines");
+        let ui = create_form(vec![("Name", &form.name), ("Members", &form.members)])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, g: &Group) {
+        self.name.set_value(&g.name);
+        self.members.set_value(&g.members.join("\n"));
+    }
+
+    fn item(&mut self, flok: &Flok, index: Option<usize>) -> Result<Group, Vec<FieldError>> {
+        let mut errors = vec![];
+        let name = self.name.value().trim().to_string();
+        if name.is_empty() {
+            errors.push(FieldError::new("Name", "Name is empty"));
+        } else if flok
+            .groups
+            .iter()
+            .enumerate()
+            .any(|(i, g)| Some(i) != index && g.name.trim().eq_ignore_ascii_case(&name))
+        {
+            errors.push(FieldError::new(
+                "Name",
+                format!("There is already a {name}"),
+            ));
+        }
+        let members: Vec<Id> = self
+            .members
+            .value()
+            .split(|c: char| c == ',' || c == '\n')
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect();
+        let missing: Vec<&str> = members
+            .iter()
+            .filter(|m| flok.find((*m).clone()).is_none())
+            .map(|m| m.as_str())
+            .collect();
+        if !missing.is_empty() {
+            errors.push(FieldError::new(
+                "Members",
+                format!("Not in the flock: {}", missing.join(", ")),
+            ));
+        }
+
+        highlight(&mut self.name, "Name", &errors);
+        highlight(&mut self.members, "Members", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Group { name, members })
+    }
+}
+
+/// Edit the groups animals are run in.
+pub fn show_groups(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Group, GroupForm>(
+        "Groups",
+        flok,
+        |f| &mut f.groups,
+        |g| format!("{} ({} animals)", g.name, g.members.len()),
+    )
+}
+
+/// The fields of one pasture.
+#[derive(Clone)]
+pub struct PastureForm {
+    pub name: Input,
+    pub notes: Input,
+    pub acres: FloatInput,
+    pub rest_days: IntInput,
+}
+
+impl ItemForm<Pasture> for PastureForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let mut form = Self {
+            name: Input::default(),
+            notes: Input::default(),
+            acres: FloatInput::default(),
+            rest_days: IntInput::default(),
+        };
+        form.rest_days.set_tooltip(&format!(
+            "Days it should rest between grazings, {} if blank",
+            Pasture::DEFAULT_REST_DAYS
+        ));
+        let ui = create_form(vec![
+            ("Name", &form.name),
+            ("Notes", &form.notes),
+            ("Acres", &form.acres),
+            ("Rest days", &form.rest_days),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, p: &Pasture) {
+        self.name.set_value(&p.name);
+        self.notes.set_value(&p.notes);
+        self.acres
+            .set_value(&p.acres.map(|a| a.to_string()).unwrap_or_default());
+        self.rest_days
+            .set_value(&p.rest_days.map(|d| d.to_string()).unwrap_or_default());
+    }
+
+    fn item(&mut self, flok: &Flok, index: Option<usize>) -> Result<Pasture, Vec<FieldError>> {
+        let mut errors = vec![];
+        let name = self.name.value().trim().to_string();
+        if name.is_empty() {
+            errors.push(FieldError::new("Name", "Name is empty"));
+        } else if flok
+            .pastures
+            .iter()
+            .enumerate()
+            .any(|(i, p)| Some(i) != index && p.name.trim().eq_ignore_ascii_case(&name))
+        {
+            errors.push(FieldError::new(
+                "Name",
+                format!("There is already a {name}"),
+            ));
+        }
+        let acres = self.acres.value().trim().to_string();
+        let acres = if acres.is_empty() {
+            None
+        } else {
+            match acres.parse::<f64>() {
+                Ok(a) if a > 0.0 => Some(a),
+                _ => {
+                    errors.push(FieldError::new(
+                        "Acres",
+                        format!("\"{acres}\" is not a number of acres"),
+                    ));
+                    None
+                }
+            }
+        };
+        let rest_days = self.rest_days.value().trim().to_string();
+        let rest_days = if rest_days.is_empty() {
+            None
+        } else {
+            rest_days.parse::<u32>().map(Some).unwrap_or_else(|_| {
+                errors.push(FieldError::new(
+                    "Rest days",
+                    format!("\"{rest_days}\" is not a number of days"),
+                ));
+                None
+            })
+        };
+        highlight(&mut self.name, "Name", &errors);
+        highlight(&mut self.acres, "Acres", &errors);
+        highlight(&mut self.rest_days, "Rest days", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Pasture {
+            name,
+            notes: self.notes.value().trim().to_string(),
+            acres,
+            rest_days,
+        })
+    }
+
+    fn replaced(flok: &mut Flok, before: &Pasture, after: &Pasture) {
+        groups::rename_pasture(flok, &before.name, &after.name);
+    }
+
+    fn in_use(flok: &Flok, pasture: &Pasture) -> Option<String> {
+        let moves = groups::moves(flok, &pasture.name);
+        (moves > 0).then(|| format!("Animals were moved to {} {moves} times", pasture.name))
+    }
+}
+
+/// Edit the pastures animals are moved between.
+pub fn show_pastures(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Pasture, PastureForm>(
+        "Pastures",
+        flok,
+        |f| &mut f.pastures,
+        |p| p.name.clone(),
+    )
+}
+
+/// Record one event, like a move or a drench, for every member of a group still in the flock.
+pub fn show_group_event(
+    flok: Arc<Mutex<Flok>>,
+    mut done: impl FnMut() + 'static,
+) -> anyhow::Result<()> {
+    let names: Vec<String> = flok.exec(|f| f.groups.iter().map(|g| g.name.clone()).collect());
+    if names.is_empty() {
+        return Err(anyhow::anyhow!("Add a group first, in Action/Groups"));
+    }
+    let mut wind = Window::default()
+        .with_size(400, 200)
+        .with_label("Group event");
+    let mut page = Flex::default_fill().column();
+    let mut group = Choice::default();
+    for name in &names {
+        group.add_choice(&name.replace('/', "\\/"));
+    }
+    group.set_value(0);
+    let mut name = Input::default();
+    name.set_value(groups::MOVE_EVENT);
+    name.set_tooltip(&format!(
+        "\"{}\" with the pasture as the value moves the group",
+        groups::MOVE_EVENT
+    ));
+    let mut value = Input::default();
+    let mut date = DateInput::default();
+    date.set_date(Some(Date::today()));
+    let notes = Input::default();
+    let ui = create_form(vec![
+        ("Group", &group),
+        ("Event", &name),
+        ("Value", &value),
+        ("Date", &date.group),
+        ("Notes", &notes),
+    ])?;
+    page.fixed(&ui, ui.height());
+    page.resizable(&Frame::default());
+
+    let mut buttons = Flex::default().row();
+    buttons.resizable(&Frame::default());
+    {
+        let mut cancel = Button::default().with_label("Cancel");
+        let mut wind = wind.clone();
+        cancel.set_callback(move |_| wind.hide());
+        buttons.fixed(&cancel, 60);
+    }
+    {
+        let mut record = Button::default().with_label("Record");
+        let mut wind = wind.clone();
+        record.set_callback(move |_| {
+            let mut errors = vec![];
+            let day = match date.parse("Date") {
+                Ok(Some(day)) => day,
+                Ok(None) => {
+                    errors.push(FieldError::new("Date", "Date is required"));
+                    Date::today()
+                }
+                Err(e) => {
+                    errors.push(e);
+                    Date::today()
+                }
+            };
+            let event = Event {
+                name: name.value().trim().to_string(),
+                value: value.value().trim().to_string(),
+                date: day,
+                notes: notes.value().trim().to_string(),
+                contact: None,
+            };
+            errors.extend(check_event(&event));
+            if event.name.eq_ignore_ascii_case(groups::MOVE_EVENT)
+                && flok.exec(|f| groups::pasture(f, &event.value).is_none())
+            {
+                errors.push(FieldError::new(
+                    "Value",
+                    format!("{} is not a pasture", event.value),
+                ));
+            }
+            highlight(&mut name, "Name", &errors);
+            highlight(&mut value, "Value", &errors);
+            highlight(&mut date.input, "Date", &errors);
+            if !errors.is_empty() {
+                display_error::<()>("Unable to record the event", Err(validation_error(errors)));
+                return;
+            }
+            let group = &names[group.value().max(0) as usize];
+            match flok.exec(|f| groups::record(f, group, &event)) {
+                Ok(recorded) => {
+                    dialog::message_default(&format!("Recorded for {recorded} animals"));
+                    done();
+                    wind.hide();
+                }
+                Err(e) => display_error::<()>("Unable to record the event", Err(e)),
+            }
+        });
+        buttons.fixed(&record, 60);
+    }
+    buttons.end();
+    page.fixed(&buttons, 25);
+    page.end();
+
+    wind.make_resizable(true);
+    wind.set_size(400, 5 + ui.height() + 25);
+    wind.end();
+    wind.show();
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flok::*,
+    report::{escape, open_in_browser},
+    validate::{self, FieldError},
+};
+
+/// Events named like this move an animal to the pasture in `value`.
+pub const MOVE_EVENT: &str = "moved to";
+
+/// Animals run together, like ewes, market lambs or rams.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub members: Vec<Id>,
+}
+
+impl Group {
+    pub fn contains(&self, animal: &Animal) -> bool {
+        animal.id.iter().any(|id| self.members.contains(id))
+    }
+}
+
+/// A paddock or field animals are moved between.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Pasture {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acres: Option<f64>,
+    /// Days it should rest between grazings, `None` for the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rest_days: Option<u32>,
+}
+
+impl Pasture {
+    /// About a month's rest lets the grass regrow and breaks the worm life cycle.
+    pub const DEFAULT_REST_DAYS: u32 = 30;
+
+    pub fn rest_days(&self) -> u32 {
+        self.rest_days.unwrap_or(Self::DEFAULT_REST_DAYS)
+    }
+}
+
+pub fn is_move(e: &Event) -> bool {
+    e.name.trim().eq_ignore_ascii_case(MOVE_EVENT)
+}
+
+/// The group called `name`.
+pub fn group<'a>(flok: &'a Flok, name: &str) -> Option<&'a Group> {
+    flok.groups
+        .iter()
+        .find(|g| g.name.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// The pasture called `name`.
+pub fn pasture<'a>(flok: &'a Flok, name: &str) -> Option<&'a Pasture> {
+    flok.pastures
+        .iter()
+        .find(|p| p.name.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// How many times animals were moved to the pasture called `name`.
+pub fn moves(flok: &Flok, name: &str) -> usize {
+    flok.animals
+        .iter()
+        .flat_map(|a| &a.events)
+        .filter(|e| is_move(e) && e.value.trim().eq_ignore_ascii_case(name.trim()))
+        .count()
+}
+
+/// Point the moves to the pasture called `from` at `to`, after it is renamed.
+pub fn rename_pasture(flok: &mut Flok, from: &str, to: &str) {
+    for e in flok.animals.iter_mut().flat_map(|a| &mut a.events) {
+        if is_move(e) && e.value.trim().eq_ignore_ascii_case(from.trim()) {
+            e.value = to.to_string();
+        }
+    }
+}
+
+/// Whether `animal` is in the group called `name`, false if there is no such group.
+pub fn in_group(flok: &Flok, name: &str, animal: &Animal) -> bool {
+    group(flok, name).is_some_and(|g| g.contains(animal))
+}
+
+/// The move that put `animal` where it was on `date`.
+pub fn location(animal: &Animal, date: Date) -> Option<&Event> {
+    animal
+        .events
+        .iter()
+        .filter(|e| is_move(e) && e.date.day <= date.day)
+        .max_by_key(|e| e.date)
+}
+
+/// The animals in one pasture.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Placement {
+    /// Empty for animals never moved
+    pub pasture: String,
+    /// Each animal with the day it arrived
+    pub animals: Vec<(Id, Option<Date>)>,
+}
+
+/// Where every animal in the flock was on `date`, by pasture, then the animals never moved.
+pub fn whereabouts(flok: &Flok, date: Date) -> Vec<Placement> {
+    let mut placed: Vec<Placement> = flok
+        .pastures
+        .iter()
+        .map(|p| Placement {
+            pasture: p.name.clone(),
+            animals: vec![],
+        })
+        .collect();
+    let mut unplaced = vec![];
+    for a in flok.animals.iter().filter(|a| a.in_flock()) {
+        let id = a.id.first().cloned().unwrap_or_default();
+        let Some(moved) = location(a, date) else {
+            unplaced.push((id, None));
+            continue;
+        };
+        let name = moved.value.trim();
+        let at = match placed
+            .iter()
+            .position(|p| p.pasture.eq_ignore_ascii_case(name))
+        {
+            Some(i) => i,
+            None => {
+                placed.push(Placement {
+                    pasture: name.to_string(),
+                    animals: vec![],
+                });
+                placed.len() - 1
+            }
+        };
+        placed[at].animals.push((id, Some(moved.date)));
+    }
+    placed.push(Placement {
+        pasture: String::new(),
+        animals: unplaced,
+    });
+    placed
+}
+
+/// Record a copy of `event` against every member of the group called `name` still in the flock,
+/// or against none of them if the event fails its checks for any member, like a sale in
+/// withdrawal. Returns how many were recorded.
+pub fn record(flok: &mut Flok, name: &str, event: &Event) -> Result<usize> {
+    let group = group(flok, name).ok_or_else(|| anyhow!("No group {name}"))?;
+    let members: Vec<usize> = (0..flok.animals.len())
+        .filter(|i| flok.animals[*i].in_flock() && group.contains(&flok.animals[*i]))
+        .collect();
+    let mut errors = vec![];
+    for i in &members {
+        let id = flok.animals[*i].id.first().cloned().unwrap_or_default();
+        errors.extend(
+            validate::check_new_events(flok, *i, std::slice::from_ref(event))
+                .into_iter()
+                .map(|e| FieldError::new(e.field, format!("{id}: {}", e.message))),
+        );
+    }
+    if !errors.is_empty() {
+        return Err(validate::validation_error(errors));
+    }
+    for i in &members {
+        flok.animals[*i].events.push(event.clone());
+    }
+    Ok(members.len())
+}
+
+/// Which pasture each animal is in on `date`, and where each group's members are.
+pub fn where_html(flok: &Flok, date: Date) -> String {
+    let pastures: String = whereabouts(flok, date)
+        .iter()
+        .filter(|p| !p.animals.is_empty())
+        .map(|p| {
+            let animals: Vec<String> = p
+                .animals
+                .iter()
+                .map(|(id, since)| match since {
+                    Some(since) => format!("{} (since {since})", escape(id)),
+                    None => escape(id),
+                })
+                .collect();
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                if p.pasture.is_empty() {
+                    "<i>Not moved yet</i>".to_string()
+                } else {
+                    escape(&p.pasture)
+                },
+                p.animals.len(),
+                animals.join(", ")
+            )
+        })
+        .collect();
+    let groups: String = flok
+        .groups
+        .iter()
+        .map(|g| {
+            let mut places: Vec<String> = vec![];
+            for a in flok
+                .animals
+                .iter()
+                .filter(|a| a.in_flock() && g.contains(a))
+            {
+                let place = location(a, date)
+                    .map(|e| e.value.trim().to_string())
+                    .unwrap_or_else(|| "not moved yet".to_string());
+                if !places.contains(&place) {
+                    places.push(place);
+                }
+            }
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&g.name),
+                g.members.len(),
+                escape(&places.join(", "))
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Where is everyone</title></head><body>\n\
+         <h1>Where is everyone on {date}</h1>\n\
+         <table border=\"1\">\n<tr><th>Pasture</th><th>Animals</th><th>Which</th></tr>\n\
+         {pastures}</table>\n<h2>Groups</h2>\n\
+         <table border=\"1\">\n<tr><th>Group</th><th>Members</th><th>In</th></tr>\n\
+         {groups}</table>\n</body></html>\n"
+    )
+}
+
+pub fn report_where(flok: &Flok) -> Result<()> {
+    open_in_browser(&where_html(flok, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn moved(pasture: &str, date: &str) -> Event {
+        Event {
+            name: MOVE_EVENT.to_string(),
+            value: pasture.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn groups_and_moves() {
+        let animal = |id: &str, events| Animal {
+            id: vec![id.to_string()],
+            events,
+            ..Default::default()
+        };
+        let mut flok = Flok {
+            groups: vec![Group {
+                name: "Market lambs".to_string(),
+                members: vec!["2".to_string(), "3".to_string()],
+            }],
+            pastures: vec![
+                Pasture {
+                    name: "North".to_string(),
+                    ..Default::default()
+                },
+                Pasture {
+                    name: "Creek".to_string(),
+                    ..Default::default()
+                },
+            ],
+            animals: vec![
+                animal("1", vec![moved("North", "2025-04-01")]),
+                animal("2", vec![]),
+                animal("3", vec![]),
+                animal("4", vec![]),
+            ],
+            ..Default::default()
+        };
+        assert!(in_group(&flok, "market lambs", &flok.animals[1]));
+        assert!(!in_group(&flok, "market lambs", &flok.animals[0]));
+        // a member's old, unrelated problem doesn't stop the group moving
+        flok.animals[2].dam = Some("3".to_string());
+        assert_eq!(
+            record(&mut flok, "Market lambs", &moved("Creek", "2025-05-01")).unwrap(),
+            2
+        );
+        assert!(record(&mut flok, "Rams", &moved("Creek", "2025-05-01")).is_err());
+        // a move to a pasture that isn't there is refused for the whole group
+        assert!(record(&mut flok, "Market lambs", &moved("South", "2025-05-02")).is_err());
+        assert_eq!(flok.animals[1].events.len(), 1);
+        flok.animals[0].events.push(moved("Creek", "2025-06-01"));
+
+        let places = |date: &str| -> Vec<(String, Vec<Id>)> {
+            whereabouts(&flok, Date::parse(date).unwrap())
+                .into_iter()
+                .map(|p| (p.pasture, p.animals.into_iter().map(|(id, _)| id).collect()))
+                .collect()
+        };
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<Id>>();
+        assert_eq!(
+            places("2025-05-15"),
+            vec![
+                ("North".to_string(), ids(&["1"])),
+                ("Creek".to_string(), ids(&["2", "3"])),
+                (String::new(), ids(&["4"])),
+            ]
+        );
+        assert_eq!(
+            places("2025-06-15")[1],
+            ("Creek".to_string(), ids(&["1", "2", "3"]))
+        );
+        assert_eq!(
+            location(&flok.animals[1], Date::parse("2025-04-15").unwrap()),
+            None
+        );
+
+        assert_eq!(moves(&flok, "creek"), 3);
+        flok.pastures[1].name = "Creek bottom".to_string();
+        rename_pasture(&mut flok, "Creek", "Creek bottom");
+        assert_eq!(moves(&flok, "Creek"), 0);
+        assert_eq!(moves(&flok, "Creek bottom"), 3);
+    }
+}
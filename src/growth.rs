@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{
+    flok::*,
+    report::{escape, open_in_browser},
+};
+
+/// Ages adjusted weights are given for.
+pub const ADJUSTED_DAYS: [i64; 3] = [60, 90, 120];
+
+/// How far from the adjusted age the nearest weighing may be.
+const ADJUST_WINDOW: i64 = 30;
+
+/// Weighings in date order, as (days since `start`, weight in `unit`).
+fn points(animal: &Animal, start: Date, unit: WeightUnit) -> Vec<(i64, f64)> {
+    let mut weights = animal.weights.clone();
+    weights.sort_by_key(|w| w.date);
+    weights
+        .iter()
+        .map(|w| ((w.date.day - start.day).num_days(), w.value_in(unit)))
+        .collect()
+}
+
+/// Where ages are counted from: birth, or the first weighing if the birth date isn't known.
+fn start(animal: &Animal) -> Option<Date> {
+    animal
+        .born
+        .or_else(|| animal.weights.iter().map(|w| w.date).min())
+}
+
+/// Average daily gain between each weighing and the next, as (from, to, gain per day).
+pub fn gains(animal: &Animal, unit: WeightUnit) -> Vec<(Date, Date, f64)> {
+    let mut weights = animal.weights.clone();
+    weights.sort_by_key(|w| w.date);
+    weights
+        .windows(2)
+        .filter_map(|w| {
+            let days = (w[1].date.day - w[0].date.day).num_days();
+            (days > 0).then(|| {
+                (
+                    w[0].date,
+                    w[1].date,
+                    (w[1].value_in(unit) - w[0].value_in(unit)) / days as f64,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Average daily gain from the first weighing to the last.
+pub fn adg(animal: &Animal, unit: WeightUnit) -> Option<f64> {
+    let start = start(animal)?;
+    let points = points(animal, start, unit);
+    let (first, last) = (points.first()?, points.last()?);
+    (last.0 > first.0).then(|| (last.1 - first.1) / (last.0 - first.0) as f64)
+}
+
+/// Weight at `days` old, projected along the line from the earliest weighing (ideally at birth)
+/// to the weighing nearest that age. `None` without a weighing within a month of it.
+pub fn adjusted(animal: &Animal, days: i64, unit: WeightUnit) -> Option<f64> {
+    animal.born?;
+    let points = points(animal, start(animal)?, unit);
+    let nearest = points
+        .iter()
+        .filter(|(age, _)| (age - days).abs() <= ADJUST_WINDOW)
+        .min_by_key(|(age, _)| (age - days).abs())?;
+    let first = points.first()?;
+    if nearest.0 <= first.0 {
+        return None;
+    }
+    let gain = (nearest.1 - first.1) / (nearest.0 - first.0) as f64;
+    Some(first.1 + gain * (days - first.0) as f64)
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Ranked {
+    pub id: Id,
+    pub born: Option<Date>,
+    pub weighings: usize,
+    pub adg: Option<f64>,
+    pub adjusted_60: Option<f64>,
+    pub adjusted_90: Option<f64>,
+    pub adjusted_120: Option<f64>,
+}
+
+/// Lambs born in `season`, fastest growing first. Lambs that can't be ranked go last.
+pub fn ranking(flok: &Flok, season: i32) -> Vec<Ranked> {
+    use chrono::Datelike;
+    let unit = flok.weight_unit;
+    let mut ranked: Vec<Ranked> = flok
+        .animals
+        .iter()
+        .filter(|a| a.born.is_some_and(|b| b.day.year() == season))
+        .map(|a| Ranked {
+            id: a.id.first().cloned().unwrap_or_default(),
+            born: a.born,
+            weighings: a.weights.len(),
+            adg: adg(a, unit),
+            adjusted_60: adjusted(a, 60, unit),
+            adjusted_90: adjusted(a, 90, unit),
+            adjusted_120: adjusted(a, 120, unit),
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.adg
+            .unwrap_or(f64::MIN)
+            .total_cmp(&a.adg.unwrap_or(f64::MIN))
+    });
+    ranked
+}
+
+fn number(n: Option<f64>) -> String {
+    n.map(|n| format!("{n:.2}")).unwrap_or_default()
+}
+
+pub fn ranking_html(flok: &Flok, season: i32) -> String {
+    let unit = flok.weight_unit.name();
+    let rows: String = ranking(flok, season)
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 <td>{}</td></tr>\n",
+                i + 1,
+                escape(&r.id),
+                r.born.map(|b| b.to_string()).unwrap_or_default(),
+                r.weighings,
+                number(r.adg),
+                number(r.adjusted_60),
+                number(r.adjusted_90),
+                number(r.adjusted_120)
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>ADG {season}</title></head><body>\n\
+         <h1>Average daily gain, lambs born {season}</h1>\n\
+         <table border=\"1\">\n<tr><th>Rank</th><th>Lamb</th><th>Born</th><th>Weighings</th>\
+         <th>ADG ({unit}/day)</th><th>60 day ({unit})</th><th>90 day ({unit})</th>\
+         <th>120 day ({unit})</th></tr>\n\
+         {rows}</table>\n</body></html>\n"
+    )
+}
+
+pub fn report_ranking(flok: &Flok, season: i32) -> Result<()> {
+    open_in_browser(&ranking_html(flok, season), ".html")
+}
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 360.0;
+const MARGIN: f64 = 50.0;
+
+/// Weight against age, with the adjusted weights marked.
+pub fn growth_svg(animal: &Animal, unit: WeightUnit) -> Result<String> {
+    let id = animal.id.first().cloned().unwrap_or_default();
+    let start = start(animal).ok_or_else(|| anyhow!("{id} has no weights"))?;
+    let points = points(animal, start, unit);
+    let adjusted: Vec<(i64, f64)> = ADJUSTED_DAYS
+        .iter()
+        .filter_map(|d| adjusted(animal, *d, unit).map(|w| (*d, w)))
+        .collect();
+    let all = points.iter().chain(adjusted.iter());
+    let max_age = all.clone().map(|p| p.0).max().unwrap_or(0).max(1) as f64;
+    let max_weight = all.map(|p| p.1).fold(1.0, f64::max) * 1.1;
+    let x = |age: i64| MARGIN + age as f64 / max_age * (WIDTH - 2.0 * MARGIN);
+    let y = |weight: f64| HEIGHT - MARGIN - weight / max_weight * (HEIGHT - 2.0 * MARGIN);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\n\
+         <text x=\"{MARGIN}\" y=\"20\" font-size=\"16\">Growth of {}</text>\n\
+         <line x1=\"{MARGIN}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"black\"/>\n\
+         <line x1=\"{MARGIN}\" y1=\"{MARGIN}\" x2=\"{MARGIN}\" y2=\"{b}\" stroke=\"black\"/>\n\
+         <text x=\"{r}\" y=\"{label}\" text-anchor=\"end\">{} (days)</text>\n\
+         <text x=\"5\" y=\"{MARGIN}\">{}</text>\n",
+        escape(&id),
+        if animal.born.is_some() {
+            "age"
+        } else {
+            "since first weighing"
+        },
+        unit.name(),
+        b = HEIGHT - MARGIN,
+        r = WIDTH - MARGIN,
+        label = HEIGHT - 10.0,
+    );
+    for i in 0..=4 {
+        let (age, weight) = (max_age / 4.0 * i as f64, max_weight / 4.0 * i as f64);
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{age:.0}</text>\n\
+             <text x=\"{}\" y=\"{}\" text-anchor=\"end\">{weight:.0}</text>\n",
+            x(age as i64),
+            HEIGHT - MARGIN + 15.0,
+            MARGIN - 5.0,
+            y(weight) + 4.0,
+        );
+    }
+    let line: Vec<String> = points
+        .iter()
+        .map(|(age, weight)| format!("{:.1},{:.1}", x(*age), y(*weight)))
+        .collect();
+    svg += &format!(
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"2\"/>\n",
+        line.join(" ")
+    );
+    for (age, weight) in &points {
+        svg += &format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"steelblue\"/>\n\
+             <text x=\"{:.1}\" y=\"{:.1}\">{weight:.1}</text>\n",
+            x(*age),
+            y(*weight),
+            x(*age) + 6.0,
+            y(*weight) - 6.0
+        );
+    }
+    for (age, weight) in &adjusted {
+        svg += &format!(
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"8\" height=\"8\" fill=\"orange\"/>\n\
+             <text x=\"{:.1}\" y=\"{:.1}\" fill=\"darkorange\">{age} day {weight:.1}</text>\n",
+            x(*age) - 4.0,
+            y(*weight) - 4.0,
+            x(*age) + 6.0,
+            y(*weight) + 14.0
+        );
+    }
+    svg += "</svg>\n";
+    Ok(svg)
+}
+
+pub fn report_growth(animal: &Animal, unit: WeightUnit) -> Result<()> {
+    open_in_browser(&growth_svg(animal, unit)?, ".svg")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lamb(weights: &[(&str, f64)]) -> Animal {
+        Animal {
+            id: vec!["7".to_string()],
+            born: Date::parse("2024-03-01"),
+            weights: weights
+                .iter()
+                .map(|(d, v)| Weight {
+                    date: Date::parse(d).unwrap(),
+                    value: *v,
+                    unit: WeightUnit::Lb,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn gains_and_adjusted_weights() {
+        // born 10 lb, 70 lb at 60 days, 90 lb at 100 days
+        let a = lamb(&[
+            ("2024-06-09", 90.0),
+            ("2024-03-01", 10.0),
+            ("2024-04-30", 70.0),
+        ]);
+        let gains: Vec<f64> = gains(&a, WeightUnit::Lb).iter().map(|g| g.2).collect();
+        assert_eq!(gains, vec![1.0, 0.5]);
+        assert_eq!(adg(&a, WeightUnit::Lb), Some(0.8));
+        assert_eq!(adjusted(&a, 60, WeightUnit::Lb), Some(70.0));
+        assert_eq!(adjusted(&a, 90, WeightUnit::Lb), Some(82.0));
+        assert_eq!(adjusted(&a, 120, WeightUnit::Lb), Some(106.0));
+        // no weighing near 200 days
+        assert_eq!(adjusted(&a, 200, WeightUnit::Lb), None);
+        let kg = adg(&a, WeightUnit::Kg).unwrap();
+        assert!((kg - 0.8 / 2.2046226).abs() < 1e-6);
+        assert!(growth_svg(&a, WeightUnit::Lb)
+            .unwrap()
+            .contains("60 day 70.0"));
+    }
+
+    #[test]
+    fn season_ranking() {
+        let mut slow = lamb(&[("2024-03-01", 10.0), ("2024-04-30", 40.0)]);
+        slow.id = vec!["slow".to_string()];
+        let mut old = lamb(&[("2023-03-01", 10.0), ("2023-04-30", 90.0)]);
+        old.id = vec!["old".to_string()];
+        old.born = Date::parse("2023-03-01");
+        let flok = Flok {
+            animals: vec![
+                slow,
+                lamb(&[]),
+                lamb(&[("2024-03-01", 10.0), ("2024-04-30", 70.0)]),
+                old,
+            ],
+            ..Default::default()
+        };
+        let ids: Vec<(String, Option<f64>)> = ranking(&flok, 2024)
+            .into_iter()
+            .map(|r| (r.id, r.adg))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                ("7".to_string(), Some(1.0)),
+                ("slow".to_string(), Some(0.5)),
+                ("7".to_string(), None)
+            ]
+        );
+    }
+}
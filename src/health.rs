@@ -0,0 +1,390 @@
+use anyhow::Result;
+use chrono::{Datelike, Days};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    breeding,
+    flok::*,
+    groups, treatment,
+    validate::{self, FieldError},
+};
+
+/// How far ahead a treatment counts as due.
+pub const DUE_SOON_DAYS: u64 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// Every `days` since the treatment was last given
+    Every { days: u32 },
+    /// A booster `booster` days after the first dose, then every `then` days
+    Booster { booster: u32, then: Option<u32> },
+    /// `days` before the expected lambing date
+    BeforeLambing { days: u32 },
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::Every { days: 365 }
+    }
+}
+
+impl Schedule {
+    pub fn describe(&self) -> String {
+        match self {
+            Schedule::Every { days } => format!("every {days} days"),
+            Schedule::Booster {
+                booster,
+                then: Some(then),
+            } => format!("booster after {booster} days, then every {then} days"),
+            Schedule::Booster {
+                booster,
+                then: None,
+            } => format!("booster after {booster} days"),
+            Schedule::BeforeLambing { days } => format!("{days} days before lambing"),
+        }
+    }
+}
+
+/// Which animals a protocol applies to. Empty fields match every animal.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Target {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sex: Option<Sex>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_age_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    /// Only members of this group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+impl Target {
+    pub fn matches(&self, flok: &Flok, animal: &Animal, on: Date) -> bool {
+        let age = animal.born.map(|b| (on.day - b.day).num_days());
+        self.sex.is_none_or(|s| s == animal.sex)
+            && self
+                .group
+                .as_ref()
+                .is_none_or(|g| groups::in_group(flok, g, animal))
+            && self
+                .min_age_days
+                .is_none_or(|min| age.is_some_and(|age| age >= min as i64))
+            && self
+                .max_age_days
+                .is_none_or(|max| age.is_none_or(|age| age <= max as i64))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        parts.extend(self.sex.map(|s| s.name().to_lowercase()));
+        parts.extend(self.min_age_days.map(|d| format!("at least {d} days old")));
+        parts.extend(self.max_age_days.map(|d| format!("at most {d} days old")));
+        parts.extend(self.group.as_ref().map(|g| format!("in {g}")));
+        if parts.is_empty() {
+            "all animals".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// A recurring treatment, eg "CD&T booster 4 weeks after the first dose, then annually".
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Protocol {
+    pub name: String,
+    /// The event recorded when it is given, usually a product name
+    pub treatment: String,
+    pub schedule: Schedule,
+    pub target: Target,
+    /// Months (1 to 12) the protocol runs in, empty for all year
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub months: Vec<u32>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Overdue,
+    Due,
+}
+
+impl Status {
+    pub fn name(&self) -> &str {
+        match self {
+            Status::Overdue => "Overdue",
+            Status::Due => "Due",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DueTreatment {
+    pub status: Status,
+    pub id: Id,
+    pub protocol: String,
+    pub treatment: String,
+    pub due: Date,
+}
+
+fn add_days(date: Date, days: u64) -> Date {
+    (date.day + Days::new(days)).into()
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// When `animal` next needs `protocol`, `None` if it doesn't apply. An animal of unknown age is
+/// due a first dose `today`.
+pub fn next_due(flok: &Flok, protocol: &Protocol, animal: &Animal, today: Date) -> Option<Date> {
+    let mut given: Vec<Date> = animal
+        .events
+        .iter()
+        .filter(|e| same_name(&e.name, &protocol.treatment))
+        .map(|e| e.date)
+        .collect();
+    given.sort();
+    // a first dose is due once the animal is old enough
+    let first = || match (animal.born, protocol.target.min_age_days) {
+        (Some(born), Some(min)) => add_days(born, min as u64),
+        (Some(born), None) => born,
+        (None, _) => today,
+    };
+    match &protocol.schedule {
+        Schedule::Every { days } => Some(match given.last() {
+            Some(last) => add_days(*last, *days as u64),
+            None => first(),
+        }),
+        Schedule::Booster { booster, then } => match given.as_slice() {
+            [] => Some(first()),
+            [first] => Some(add_days(*first, *booster as u64)),
+            [.., last] => then.map(|then| add_days(*last, then as u64)),
+        },
+        Schedule::BeforeLambing { days } => {
+            let lambing = breeding::expected_lambing(flok, animal)?.expected;
+            let due = lambing.day - Days::new(*days as u64);
+            // already given in the run up to this lambing
+            let since = due - Days::new(*days as u64);
+            (!given.iter().any(|g| g.day >= since)).then_some(due.into())
+        }
+    }
+}
+
+/// Animals due or overdue a treatment on `today`, most overdue first.
+pub fn due(flok: &Flok, today: Date) -> Vec<DueTreatment> {
+    let soon = add_days(today, DUE_SOON_DAYS);
+    let mut due: Vec<DueTreatment> = flok
+        .protocols
+        .iter()
+        .filter(|p| p.months.is_empty() || p.months.contains(&today.day.month()))
+        .flat_map(|p| {
+            flok.animals
+                .iter()
+                .filter(|a| a.in_flock() && p.target.matches(flok, a, soon))
+                .filter_map(move |a| {
+                    let due = next_due(flok, p, a, today).filter(|d| d.day <= soon.day)?;
+                    Some(DueTreatment {
+                        status: if due.day < today.day {
+                            Status::Overdue
+                        } else {
+                            Status::Due
+                        },
+                        id: a.id.first().cloned().unwrap_or_default(),
+                        protocol: p.name.clone(),
+                        treatment: p.treatment.clone(),
+                        due,
+                    })
+                })
+        })
+        .collect();
+    due.sort_by(|a, b| (a.due, &a.id).cmp(&(b.due, &b.id)));
+    due
+}
+
+/// Record each treatment against its animal on `date`, with the dose worked out from the
+/// animal's weight when the treatment is a product with a dose per kg. Nothing is recorded if
+/// a treatment fails its checks. Returns how many were recorded.
+pub fn record(flok: &mut Flok, treatments: &[DueTreatment], date: Date) -> Result<usize> {
+    // each animal treated, by its index, with its new treatments
+    let mut treated: Vec<(usize, Vec<Event>)> = vec![];
+    for t in treatments {
+        let Some(i) = flok.animals.iter().position(|a| a.id.contains(&t.id)) else {
+            continue;
+        };
+        let value = flok
+            .product(&t.treatment)
+            .and_then(|p| treatment::suggested_dose(p, &flok.animals[i]))
+            .unwrap_or_default();
+        let at = match treated.iter().position(|(j, _)| *j == i) {
+            Some(at) => at,
+            None => {
+                treated.push((i, vec![]));
+                treated.len() - 1
+            }
+        };
+        treated[at].1.push(Event {
+            name: t.treatment.clone(),
+            value,
+            date,
+            notes: t.protocol.clone(),
+            contact: None,
+        });
+    }
+    let errors: Vec<FieldError> = treated
+        .iter()
+        .flat_map(|(i, events)| {
+            let id = flok.animals[*i].id.first().cloned().unwrap_or_default();
+            validate::check_new_events(flok, *i, events)
+                .into_iter()
+                .map(move |e| FieldError::new(e.field, format!("{id}: {}", e.message)))
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(validate::validation_error(errors));
+    }
+    let mut recorded = 0;
+    for (i, events) in treated {
+        recorded += events.len();
+        flok.animals[i].events.extend(events);
+    }
+    Ok(recorded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(name: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn animal(id: &str, sex: Sex, born: &str, events: Vec<Event>) -> Animal {
+        Animal {
+            id: vec![id.to_string()],
+            sex,
+            born: Date::parse(born),
+            events,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn due_and_overdue() {
+        let cdt = Protocol {
+            name: "CD&T".to_string(),
+            treatment: "CD&T".to_string(),
+            schedule: Schedule::Booster {
+                booster: 28,
+                then: Some(365),
+            },
+            target: Target {
+                min_age_days: Some(42),
+                ..Default::default()
+            },
+            months: vec![],
+        };
+        let pre_lambing = Protocol {
+            name: "Pre-lambing".to_string(),
+            treatment: "CD&T".to_string(),
+            schedule: Schedule::BeforeLambing { days: 30 },
+            target: Target {
+                sex: Some(Sex::Female),
+                ..Default::default()
+            },
+            months: vec![],
+        };
+        let summer_worming = Protocol {
+            name: "Worm check".to_string(),
+            treatment: "FAMACHA".to_string(),
+            schedule: Schedule::Every { days: 42 },
+            months: vec![6, 7, 8],
+            ..Default::default()
+        };
+        let mut flok = Flok {
+            protocols: vec![cdt, pre_lambing, summer_worming],
+            animals: vec![
+                // first dose 2024-05-01, booster due 2024-05-29
+                animal(
+                    "lamb",
+                    Sex::Male,
+                    "2024-03-01",
+                    vec![event("cd&t", "2024-05-01")],
+                ),
+                // too young for a first dose until 2024-06-12
+                animal("young", Sex::Male, "2024-05-01", vec![]),
+                // boosted, next due 2025-04-29; lambing due 2024-07-24, vaccinate 2024-06-24
+                animal(
+                    "ewe",
+                    Sex::Female,
+                    "2022-03-01",
+                    vec![
+                        event("CD&T", "2024-04-01"),
+                        event("CD&T", "2024-04-29"),
+                        event("Bred", "2024-02-28"),
+                    ],
+                ),
+                animal(
+                    "sold",
+                    Sex::Male,
+                    "2024-03-01",
+                    vec![event("sold", "2024-04-01")],
+                ),
+            ],
+            ..Default::default()
+        };
+        let list = |flok: &Flok, date: &str| -> Vec<(Status, String, String)> {
+            due(flok, Date::parse(date).unwrap())
+                .into_iter()
+                .map(|d| (d.status, d.id, d.protocol))
+                .collect()
+        };
+        assert_eq!(
+            list(&flok, "2024-05-25"),
+            vec![(Status::Due, "lamb".to_string(), "CD&T".to_string())]
+        );
+        assert_eq!(
+            list(&flok, "2024-06-20"),
+            vec![
+                (Status::Overdue, "ewe".to_string(), "Worm check".to_string()),
+                (
+                    Status::Overdue,
+                    "lamb".to_string(),
+                    "Worm check".to_string()
+                ),
+                (
+                    Status::Overdue,
+                    "young".to_string(),
+                    "Worm check".to_string()
+                ),
+                (Status::Overdue, "lamb".to_string(), "CD&T".to_string()),
+                (Status::Overdue, "young".to_string(), "CD&T".to_string()),
+                (Status::Due, "ewe".to_string(), "Pre-lambing".to_string()),
+            ]
+        );
+
+        let today = Date::parse("2024-06-20").unwrap();
+        let cdt: Vec<DueTreatment> = due(&flok, today)
+            .into_iter()
+            .filter(|d| d.treatment == "CD&T")
+            .collect();
+        // nothing is recorded when a treatment fails the checks
+        let tomorrow = add_days(Date::today(), 1);
+        assert!(record(&mut flok, &cdt, tomorrow).is_err());
+        assert_eq!(list(&flok, "2024-06-20").len(), 6);
+        // nor is it stopped by an animal's old, unrelated problem
+        flok.animals[1].sire = Some("young".to_string());
+        assert_eq!(record(&mut flok, &cdt, today).unwrap(), 3);
+        assert_eq!(list(&flok, "2024-06-20").len(), 3);
+
+        // an animal of unknown age is due from the day asked about, not the day it's run
+        let stray = Animal::default();
+        assert_eq!(
+            next_due(&flok, &flok.protocols[2], &stray, today),
+            Some(today)
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use fltk::{
+    browser::MultiBrowser,
+    button::Button,
+    dialog,
+    frame::Frame,
+    group::Flex,
+    input::{Input, IntInput},
+    menu::Choice,
+    prelude::{BrowserExt, GroupExt, InputExt, MenuExt, WidgetBase, WidgetExt, WindowExt},
+    widget::Widget,
+    window::Window,
+};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    business_obj::BusinessObject,
+    flok::*,
+    form::*,
+    groups,
+    health::{self, DueTreatment, Protocol, Schedule, Target},
+    list_editor::{show_list_editor, ItemForm},
+};
+
+const SCHEDULES: [&str; 3] = ["Every", "Booster, then every", "Before lambing"];
+const SEXES: [&str; 3] = ["Any", "Male", "Female"];
+
+#[derive(Clone)]
+pub struct ProtocolForm {
+    pub name: Input,
+    pub treatment: Input,
+    pub schedule: Choice,
+    pub days: IntInput,
+    pub then: IntInput,
+    pub sex: Choice,
+    pub min_age: IntInput,
+    pub max_age: IntInput,
+    pub group: Input,
+    pub months: Input,
+}
+
+fn days_text(days: Option<u32>) -> String {
+    days.map(|d| d.to_string()).unwrap_or_default()
+}
+
+/// A number of days, `None` when blank.
+fn parse_days(input: &IntInput, field: &'static str, errors: &mut Vec<FieldError>) -> Option<u32> {
+    let text = input.value();
+    if text.trim().is_empty() {
+        return None;
+    }
+    text.trim().parse().map(Some).unwrap_or_else(|_| {
+        errors.push(FieldError::new(
+            field,
+            format!("\"{text}\" is not a number of days"),
+        ));
+        None
+    })
+}
+
+impl ItemForm<Protocol> for ProtocolForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let choice = |items: &[&str]| {
+            let mut choice = Choice::default();
+            items.iter().for_each(|i| choice.add_choice(i));
+            choice
+        };
+        let mut form = Self {
+            name: Input::default(),
+            treatment: Input::default(),
+            schedule: choice(&SCHEDULES),
+            days: IntInput::default(),
+            then: IntInput::default(),
+            sex: choice(&SEXES),
+            min_age: IntInput::default(),
+            max_age: IntInput::default(),
+            group: Input::default(),
+            months: Input::default(),
+        };
+        form.group
+            .set_tooltip("Only members of this group, blank for any animal");
+        form.treatment
+            .set_tooltip("The event recorded, usually a product name");
+        form.months
+            .set_tooltip("Months it runs in, eg 6,7,8 for summer. Blank for all year.");
+        let ui = create_form(vec![
+            ("Name", &form.name),
+            ("Treatment", &form.treatment),
+            ("Schedule", &form.schedule),
+            ("Days", &form.days),
+            ("Then every (days)", &form.then),
+            ("Sex", &form.sex),
+            ("Min age (days)", &form.min_age),
+            ("Max age (days)", &form.max_age),
+            ("Group", &form.group),
+            ("Months", &form.months),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, p: &Protocol) {
+        self.name.set_value(&p.name);
+        self.treatment.set_value(&p.treatment);
+        let (schedule, days, then) = match p.schedule {
+            Schedule::Every { days } => (0, days, None),
+            Schedule::Booster { booster, then } => (1, booster, then),
+            Schedule::BeforeLambing { days } => (2, days, None),
+        };
+        self.schedule.set_value(schedule);
+        self.days.set_value(&days.to_string());
+        self.then.set_value(&days_text(then));
+        self.sex.set_value(match p.target.sex {
+            None => 0,
+            Some(Sex::Male) => 1,
+            Some(Sex::Female) => 2,
+        });
+        self.min_age.set_value(&days_text(p.target.min_age_days));
+        self.max_age.set_value(&days_text(p.target.max_age_days));
+        self.group
+            .set_value(p.target.group.as_deref().unwrap_or_default());
+        self.months.set_value(
+            &p.months
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+
+    fn item(&mut self, flok: &Flok, index: Option<usize>) -> Result<Protocol, Vec<FieldError>> {
+        let mut errors = vec![];
+        let name = self.name.value().trim().to_string();
+        if name.is_empty() {
+            errors.push(FieldError::new("Name", "Name is empty"));
+        } else if flok
+            .protocols
+            .iter()
+            .enumerate()
+            .any(|(i, p)| Some(i) != index && p.name.trim().eq_ignore_ascii_case(&name))
+        {
+            errors.push(FieldError::new(
+                "Name",
+                format!("There is already a {name}"),
+            ));
+        }
+        let treatment = self.treatment.value().trim().to_string();
+        if treatment.is_empty() {
+            errors.push(FieldError::new("Treatment", "Treatment is empty"));
+        }
+        let days = parse_days(&self.days, "Days", &mut errors).unwrap_or_else(|| {
+            errors.push(FieldError::new("Days", "Days is required"));
+            0
+        });
+        let then = parse_days(&self.then, "Then", &mut errors);
+        let schedule = match self.schedule.value() {
+            1 => Schedule::Booster {
+                booster: days,
+                then,
+            },
+            2 => Schedule::BeforeLambing { days },
+            _ => Schedule::Every { days },
+        };
+        let target = Target {
+            sex: match self.sex.value() {
+                1 => Some(Sex::Male),
+                2 => Some(Sex::Female),
+                _ => None,
+            },
+            min_age_days: parse_days(&self.min_age, "Min age", &mut errors),
+            max_age_days: parse_days(&self.max_age, "Max age", &mut errors),
+            group: Some(self.group.value().trim().to_string()).filter(|g| !g.is_empty()),
+        };
+        if let Some(group) = target
+            .group
+            .as_ref()
+            .filter(|g| groups::group(flok, g).is_none())
+        {
+            errors.push(FieldError::new(
+                "Group",
+                format!("There is no group {group}"),
+            ));
+        }
+        let months: Vec<u32> = self
+            .months
+            .value()
+            .split(',')
+            .map(|m| m.trim())
+            .filter(|m| !m.is_empty())
+            .filter_map(|m| match m.parse::<u32>() {
+                Ok(m) if (1..=12).contains(&m) => Some(m),
+                _ => {
+                    errors.push(FieldError::new(
+                        "Months",
+                        format!("\"{m}\" is not a month from 1 to 12"),
+                    ));
+                    None
+                }
+            })
+            .collect();
+
+        highlight(&mut self.name, "Name", &errors);
+        highlight(&mut self.treatment, "Treatment", &errors);
+        highlight(&mut self.days, "Days", &errors);
+        highlight(&mut self.then, "Then", &errors);
+        highlight(&mut self.min_age, "Min age", &errors);
+        highlight(&mut self.max_age, "Max age", &errors);
+        highlight(&mut self.group, "Group", &errors);
+        highlight(&mut self.months, "Months", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Protocol {
+            name,
+            treatment,
+            schedule,
+            target,
+            months,
+        })
+    }
+}
+
+/// Edit the flock's recurring health protocols.
+pub fn show_protocols(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Protocol, ProtocolForm>(
+        "Health protocols",
+        flok,
+        |f| &mut f.protocols,
+        |p| format!("{} ({})", p.name, p.schedule.describe()),
+    )
+}
+
+/// Animals due or overdue a treatment, with a button to record it for those selected.
+/// `done` is called after treatments are recorded.
+pub fn show_due(flok: Arc<Mutex<Flok>>, mut done: impl FnMut() + 'static) -> anyhow::Result<()> {
+    let mut wind = Window::default()
+        .with_size(650, 400)
+        .with_label("Treatments due");
+    let mut page = Flex::default_fill().column();
+    let mut list = MultiBrowser::default();
+    list.set_column_widths(&[80, 100, 100, 180, 150]);
+    list.set_column_char('\t');
+
+    let due: Rc<RefCell<Vec<DueTreatment>>> = Default::default();
+    let mut refresh = {
+        let (flok, mut list, due) = (flok.clone(), list.clone(), due.clone());
+        move || {
+            let today = Date::today();
+            *due.borrow_mut() = flok.exec(|f| health::due(f, today));
+            list.clear();
+            list.add("@bStatus\t@bDue\t@bAnimal\t@bProtocol\t@bTreatment");
+            for d in due.borrow().iter() {
+                let line = format!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    d.status.name(),
+                    d.due,
+                    d.id,
+                    d.protocol,
+                    d.treatment
+                );
+                list.add(&line.replace('@', "@@"));
+            }
+        }
+    };
+    refresh();
+
+    let mut buttons = Flex::default().row();
+    {
+        let mut all = Button::default().with_label("Select all");
+        let (mut list, due) = (list.clone(), due.clone());
+        all.set_callback(move |_| {
+            for line in 0..due.borrow().len() {
+                list.select(line as i32 + 2);
+            }
+        });
+        buttons.fixed(&all, 80);
+    }
+    buttons.resizable(&Frame::default());
+    {
+        let mut close = Button::default().with_label("Close");
+        let mut wind = wind.clone();
+        close.set_callback(move |_| wind.hide());
+        buttons.fixed(&close, 60);
+    }
+    {
+        let mut record = Button::default().with_label("Record selected...");
+        let list = list.clone();
+        record.set_callback(move |_| {
+            let selected: Vec<DueTreatment> = due
+                .borrow()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| list.selected(*i as i32 + 2))
+                .map(|(_, d)| d.clone())
+                .collect();
+            if selected.is_empty() {
+                dialog::message_default("Select the treatments given first");
+                return;
+            }
+            let Some(date) = dialog::input_default(
+                &format!("Record {} treatments given on", selected.len()),
+                &Date::today().to_string(),
+            ) else {
+                return;
+            };
+            match Date::parse(&date) {
+                Some(date) => match flok.exec(|f| health::record(f, &selected, date)) {
+                    Ok(_) => {
+                        done();
+                        refresh();
+                    }
+                    Err(e) => display_error::<()>("Unable to record the treatments", Err(e)),
+                },
+                None => dialog::alert_default(&format!("\"{date}\" is not a date")),
+            }
+        });
+        buttons.fixed(&record, 130);
+    }
+    buttons.end();
+    page.fixed(&buttons, 25);
+    page.end();
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+    Ok(())
+}
@@ -0,0 +1,323 @@
+use anyhow::{anyhow, Result};
+use calamine::{Data, DataType, Reader};
+use chrono::NaiveDate;
+use std::path::Path;
+
+use crate::flok::*;
+
+/// Add one animal per row of the first sheet of a spreadsheet to `flok`.
+pub fn import_file(flok: &mut Flok, file: &str) -> Result<()> {
+    import_dated(flok, file, Date::today())
+}
+
+/// Like [import_file], giving `undated` to events whose column header has no date in it.
+pub fn import_dated(flok: &mut Flok, file: &str, undated: Date) -> Result<()> {
+    let rows = read_rows(file)?;
+    let mut rows = rows.iter().enumerate();
+    let (_, headers) = rows.next().ok_or(anyhow!("No headers found in sheet"))?;
+    let headers: Vec<(String, Date)> = headers
+        .iter()
+        .enumerate()
+        .map(|(col, h)| header(col, h, undated))
+        .collect();
+    for (row_index, row) in rows {
+        if row.iter().all(is_blank) {
+            continue;
+        }
+        let mut animal = Animal::default();
+        for ((header, date), cell) in headers.iter().zip(row.iter()) {
+            if is_blank(cell) {
+                continue;
+            }
+            if header.starts_with("id:") {
+                animal.id.push(cell.to_string());
+            } else {
+                match header.as_str() {
+                    "id" => animal.id.insert(
+                        0,
+                        if is_placeholder(cell) {
+                            // spreadsheet row numbers start at 1
+                            format!("unknown-{}", row_index + 1)
+                        } else {
+                            cell.to_string()
+                        },
+                    ),
+                    "born" => animal.born = cell_date(cell),
+                    "sire" => animal.sire = some_string(cell),
+                    "dam" => animal.dam = some_string(cell),
+                    "sex" => animal.sex = Sex::from(cell.to_string()),
+                    "desc" | "description" => animal.description = cell.to_string(),
+                    _ if weight(header, *date, cell).is_some() => {
+                        animal.weights.extend(weight(header, *date, cell))
+                    }
+                    _ if score(header, *date, cell).is_some() => {
+                        animal.scores.extend(score(header, *date, cell))
+                    }
+                    _event => {
+                        animal.events.push(Event {
+                            name: header.clone(),
+                            value: match cell {
+                                Data::DateTime(_) | Data::DateTimeIso(_) => cell_date(cell)
+                                    .map(|d| d.to_iso())
+                                    .unwrap_or_else(|| cell.to_string()),
+                                _ => cell.to_string(),
+                            },
+                            date: *date,
+                            notes: "".to_string(),
+                            contact: None,
+                        });
+                    }
+                }
+            }
+        }
+        if animal.id.is_empty() {
+            animal.id.push(format!("unknown-{}", row_index + 1));
+        }
+        flok.animals.push(animal);
+    }
+    Ok(())
+}
+
+/// The cells of the first sheet. calamine doesn't read CSV, so that is done here.
+fn read_rows(file: &str) -> Result<Vec<Vec<Data>>> {
+    let is_csv = Path::new(file)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        let mut csv = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(file)?;
+        let mut rows = vec![];
+        for record in csv.records() {
+            rows.push(
+                record?
+                    .iter()
+                    .map(|s| {
+                        if s.is_empty() {
+                            Data::Empty
+                        } else {
+                            Data::String(s.to_string())
+                        }
+                    })
+                    .collect(),
+            );
+        }
+        Ok(rows)
+    } else {
+        let mut wb = calamine::open_workbook_auto(file)?;
+        let sheet = &wb.worksheets()[0].1;
+        Ok(sheet.rows().map(|r| r.to_vec()).collect())
+    }
+}
+
+/// Event name and date for a column. Column headers like "prohibit 4/22/2025" or
+/// "2024/9/3 wormer" date the events in that column. Large numbers are Excel dates.
+fn header(col: usize, cell: &Data, undated: Date) -> (String, Date) {
+    let is_date = match cell {
+        Data::DateTime(_) | Data::DateTimeIso(_) => true,
+        Data::Int(_) | Data::Float(_) => cell.as_f64().is_some_and(|f| f > 10000.0),
+        _ => false,
+    };
+    if let Some(date) = cell.as_date().filter(|_| is_date) {
+        return (date.format("%Y-%m-%d").to_string(), date.into());
+    }
+    let name = cell.to_string().trim().to_lowercase();
+    if name.is_empty() {
+        return (format!("column {}", col + 1), undated);
+    }
+    let date = name
+        .split_whitespace()
+        .find_map(|word| {
+            ["%m/%d/%Y", "%Y/%m/%d", "%Y-%m-%d"]
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(word, f).ok())
+        })
+        .map(Date::from)
+        .unwrap_or(undated);
+    (name, date)
+}
+
+/// A number in a column like "11/19 weight" or "weight kg", in pounds unless the header says kg.
+fn weight(header: &str, date: Date, cell: &Data) -> Option<Weight> {
+    let words: Vec<&str> = header.split_whitespace().collect();
+    if !words.iter().any(|w| w.starts_with("weight") || *w == "wt") {
+        return None;
+    }
+    let value = match cell {
+        Data::Int(_) | Data::Float(_) => cell.as_f64(),
+        _ => cell.to_string().trim().parse().ok(),
+    }?;
+    Some(Weight {
+        date,
+        value,
+        unit: if words.contains(&"kg") {
+            WeightUnit::Kg
+        } else {
+            WeightUnit::Lb
+        },
+    })
+}
+
+/// A number in a column like "famacha 6/1" or "bcs".
+fn score(header: &str, date: Date, cell: &Data) -> Option<Score> {
+    let words: Vec<&str> = header.split_whitespace().collect();
+    let kind = if words.contains(&"famacha") {
+        ScoreKind::Famacha
+    } else if words.contains(&"bcs") {
+        ScoreKind::Bcs
+    } else {
+        return None;
+    };
+    let value = match cell {
+        Data::Int(_) | Data::Float(_) => cell.as_f64(),
+        _ => cell.to_string().trim().parse().ok(),
+    }?;
+    Some(Score { date, kind, value })
+}
+
+fn is_blank(cell: &Data) -> bool {
+    cell.to_string().trim().is_empty()
+}
+
+/// "?", "???" and "#N/A" mean the id wasn't known.
+fn is_placeholder(cell: &Data) -> bool {
+    let s = cell.to_string();
+    let s = s.trim();
+    s.is_empty() || s.chars().all(|c| c == '?') || s == "#N/A"
+}
+
+fn cell_date(cell: &Data) -> Option<Date> {
+    match cell {
+        Data::DateTime(_) | Data::DateTimeIso(_) => cell
+            .as_datetime()
+            .map(Date::from)
+            .or_else(|| cell.as_date().map(Date::from)),
+        Data::String(s) => Date::parse(s),
+        _ => None,
+    }
+}
+
+fn some_string(cell: &Data) -> Option<String> {
+    if is_placeholder(cell) {
+        None
+    } else {
+        Some(cell.to_string().trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::{export, ExportOptions};
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn day(y: i32, m: u32, d: u32) -> Date {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().into()
+    }
+
+    fn import(file: &str) -> Flok {
+        let mut flok = Flok::default();
+        import_dated(&mut flok, file, day(2025, 1, 1)).unwrap();
+        flok
+    }
+
+    fn animal<'a>(flok: &'a Flok, id: &str) -> &'a Animal {
+        flok.find(id.to_string()).expect(id)
+    }
+
+    fn event<'a>(flok: &'a Flok, id: &str, name: &str) -> &'a Event {
+        animal(flok, id)
+            .events
+            .iter()
+            .find(|e| e.name == name)
+            .expect(name)
+    }
+
+    #[test]
+    fn xlsx() {
+        let flok = import(&fixture("flock.xlsx"));
+        assert_eq!(flok.animals.len(), 19);
+        assert_eq!(animal(&flok, "4").born, Some(day(2017, 3, 3)));
+        assert_eq!(animal(&flok, "4").dam, Some("1".to_string()));
+        assert_eq!(animal(&flok, "1").description, "cinnimon");
+        // header dates
+        assert_eq!(event(&flok, "4", "2024/9/3 wormer").date, day(2024, 9, 3));
+        assert_eq!(
+            event(&flok, "4", "vaccine 12/26/2021").date,
+            day(2021, 12, 26)
+        );
+        assert_eq!(event(&flok, "4", "2021-11-11").date, day(2021, 11, 11));
+        assert_eq!(event(&flok, "1", "purchase price").date, day(2025, 1, 1));
+        // date cells keep their date
+        assert_eq!(event(&flok, "1", "purchase date").value, "2016-11-20");
+        // "#N/A" dams and blank cells
+        assert_eq!(animal(&flok, "1").dam, None);
+        assert!(!animal(&flok, "6")
+            .events
+            .iter()
+            .any(|e| e.name == "batt tag"));
+    }
+
+    #[test]
+    fn csv() {
+        let flok = import(&fixture("flock.csv"));
+        assert_eq!(flok.animals.len(), 5);
+        let ram = animal(&flok, "1002");
+        assert_eq!(ram.sex, Sex::Male);
+        assert_eq!((ram.sire.clone(), ram.dam.clone()), (None, None));
+        // retag
+        assert_eq!(animal(&flok, "1183").id, vec!["1010", "1183"]);
+        assert_eq!(animal(&flok, "1010").born, Some(day(2023, 4, 22)));
+        // placeholder id, by spreadsheet row
+        assert_eq!(animal(&flok, "unknown-5").dam, Some("1010".to_string()));
+        assert_eq!(
+            event(&flok, "1001", "cd&t 4/22/2025").date,
+            day(2025, 4, 22)
+        );
+        assert_eq!(event(&flok, "1010", "column 11").value, "purple");
+        assert_eq!(
+            animal(&flok, "1001").weights,
+            vec![Weight {
+                date: day(2025, 1, 1),
+                value: 150.0,
+                unit: WeightUnit::Lb
+            }]
+        );
+        assert!(animal(&flok, "1020").events.is_empty());
+    }
+
+    #[test]
+    fn ods() {
+        let flok = import(&fixture("flock.ods"));
+        assert_eq!(flok.animals.len(), 4);
+        assert_eq!(animal(&flok, "1030").born, Some(day(2024, 3, 2)));
+        assert_eq!(animal(&flok, "1030").dam, Some("1001".to_string()));
+        assert_eq!(animal(&flok, "unknown-4").dam, None);
+        // a date cell as the header
+        assert_eq!(event(&flok, "1030", "2024-11-19").date, day(2024, 11, 19));
+        assert_eq!(event(&flok, "1030", "2024-11-19").value, "38.5");
+        assert_eq!(event(&flok, "1001", "purchase date").value, "2020-05-01");
+        assert_eq!(
+            event(&flok, "1001", "prohibit 7/30/2025").date,
+            day(2025, 7, 30)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        for fixture_name in ["flock.xlsx", "flock.csv", "flock.ods"] {
+            let flok = import(&fixture(fixture_name));
+            for format in ["xlsx", "csv", "ods"] {
+                let file = dir.path().join(format!("export.{format}"));
+                let file = file.to_str().unwrap();
+                export(&flok, &ExportOptions::all(&flok), file).unwrap();
+                assert_eq!(import(file), flok, "{fixture_name} to {format}");
+            }
+        }
+    }
+}
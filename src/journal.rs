@@ -0,0 +1,656 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    crypto,
+    flok::*,
+    merge::Field,
+    report::{escape, open_in_browser},
+};
+
+/// One saved change, a line of JSON in the journal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub time: Date,
+    pub user: String,
+    pub change: Change,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Change {
+    /// `before` is `None` for an added animal, `after` for a deleted one.
+    Animal {
+        before: Option<Animal>,
+        after: Option<Animal>,
+    },
+    Flock {
+        name: String,
+        date_format: DateFormat,
+        #[serde(default)]
+        weight_unit: WeightUnit,
+    },
+    /// An edited product is both removed and added.
+    Products {
+        removed: Vec<Product>,
+        added: Vec<Product>,
+    },
+    Protocols {
+        removed: Vec<crate::health::Protocol>,
+        added: Vec<crate::health::Protocol>,
+    },
+    Exposures {
+        removed: Vec<crate::breeding::Exposure>,
+        added: Vec<crate::breeding::Exposure>,
+    },
+    Ledger {
+        removed: Vec<LedgerEntry>,
+        added: Vec<LedgerEntry>,
+    },
+    Contacts {
+        removed: Vec<Contact>,
+        added: Vec<Contact>,
+    },
+    Premises {
+        premises_id: String,
+    },
+    Groups {
+        removed: Vec<crate::groups::Group>,
+        added: Vec<crate::groups::Group>,
+    },
+    Pastures {
+        removed: Vec<crate::groups::Pasture>,
+        added: Vec<crate::groups::Pasture>,
+    },
+    FeedPurchases {
+        removed: Vec<crate::feed::FeedPurchase>,
+        added: Vec<crate::feed::FeedPurchase>,
+    },
+    Feedings {
+        removed: Vec<crate::feed::Feeding>,
+        added: Vec<crate::feed::Feeding>,
+    },
+}
+
+impl Change {
+    fn concerns(&self, id: &Id) -> bool {
+        match self {
+            Change::Animal { before, after } => {
+                before.iter().chain(after.iter()).any(|a| a.id.contains(id))
+            }
+            Change::Exposures { removed, added } => removed
+                .iter()
+                .chain(added)
+                .any(|e| &e.ram == id || e.ewes.contains(id)),
+            Change::Ledger { removed, added } => removed
+                .iter()
+                .chain(added)
+                .any(|e| e.animal.as_ref() == Some(id)),
+            Change::Groups { removed, added } => {
+                removed.iter().chain(added).any(|g| g.members.contains(id))
+            }
+            Change::Flock { .. }
+            | Change::Products { .. }
+            | Change::Protocols { .. }
+            | Change::Contacts { .. }
+            | Change::Premises { .. }
+            | Change::Pastures { .. }
+            | Change::FeedPurchases { .. }
+            | Change::Feedings { .. } => false,
+        }
+    }
+
+    /// What changed, one line per field or event.
+    pub fn describe(&self) -> Vec<String> {
+        match self {
+            Change::Animal {
+                before: None,
+                after: Some(a),
+            } => vec![format!("added {}", a.id.join(", "))],
+            Change::Animal {
+                before: Some(a),
+                after: None,
+            } => vec![format!("deleted {}", a.id.join(", "))],
+            Change::Animal {
+                before: Some(before),
+                after: Some(after),
+            } => {
+                let mut lines: Vec<String> = Field::ALL
+                    .iter()
+                    .filter(|f| f.differs(before, after))
+                    .map(|f| {
+                        format!(
+                            "{}: {} -> {}",
+                            f.name(),
+                            f.show(Some(before)),
+                            f.show(Some(after))
+                        )
+                    })
+                    .collect();
+                let event = |e: &Event| {
+                    format!(
+                        "{} {} {} {} {}",
+                        e.date,
+                        e.name,
+                        e.value,
+                        e.notes,
+                        e.contact.as_deref().unwrap_or_default()
+                    )
+                };
+                lines.extend(
+                    before
+                        .events
+                        .iter()
+                        .filter(|e| !after.events.contains(e))
+                        .map(|e| format!("removed event {}", event(e).trim())),
+                );
+                lines.extend(
+                    after
+                        .events
+                        .iter()
+                        .filter(|e| !before.events.contains(e))
+                        .map(|e| format!("added event {}", event(e).trim())),
+                );
+                let weight = |w: &Weight| format!("{} {} {}", w.date, w.value, w.unit.name());
+                lines.extend(
+                    before
+                        .weights
+                        .iter()
+                        .filter(|w| !after.weights.contains(w))
+                        .map(|w| format!("removed weight {}", weight(w))),
+                );
+                lines.extend(
+                    after
+                        .weights
+                        .iter()
+                        .filter(|w| !before.weights.contains(w))
+                        .map(|w| format!("added weight {}", weight(w))),
+                );
+                let score = |s: &Score| format!("{} {} {}", s.date, s.kind.name(), s.value);
+                lines.extend(
+                    before
+                        .scores
+                        .iter()
+                        .filter(|s| !after.scores.contains(s))
+                        .map(|s| format!("removed score {}", score(s))),
+                );
+                lines.extend(
+                    after
+                        .scores
+                        .iter()
+                        .filter(|s| !before.scores.contains(s))
+                        .map(|s| format!("added score {}", score(s))),
+                );
+                lines
+            }
+            Change::Animal {
+                before: None,
+                after: None,
+            } => vec![],
+            Change::Flock {
+                name,
+                date_format,
+                weight_unit,
+            } => vec![format!(
+                "flock \"{name}\", dates {}, weights in {}",
+                date_format.name(),
+                weight_unit.name()
+            )],
+            Change::Products { removed, added } => {
+                describe_list("product", removed, added, |p| p.name.clone())
+            }
+            Change::Protocols { removed, added } => {
+                describe_list("health protocol", removed, added, |p| p.name.clone())
+            }
+            Change::Exposures { removed, added } => {
+                describe_list("exposure", removed, added, |e| {
+                    format!("{} {} from {}", e.name, e.ram, e.from)
+                })
+            }
+            Change::Ledger { removed, added } => {
+                describe_list("ledger entry", removed, added, |e| {
+                    format!("{} {} {:.2}", e.date, e.category.name(), e.amount)
+                })
+            }
+            Change::Contacts { removed, added } => {
+                describe_list("contact", removed, added, |c| c.name.clone())
+            }
+            Change::Premises { premises_id } => vec![format!("premises ID \"{premises_id}\"")],
+            Change::Groups { removed, added } => describe_list("group", removed, added, |g| {
+                format!("{} of {}", g.name, g.members.join(", "))
+            }),
+            Change::Pastures { removed, added } => {
+                describe_list("pasture", removed, added, |p| p.name.clone())
+            }
+            Change::FeedPurchases { removed, added } => {
+                describe_list("feed purchase", removed, added, |p| {
+                    format!("{} {} {} {}", p.date, p.quantity, p.unit, p.feed)
+                })
+            }
+            Change::Feedings { removed, added } => describe_list("feeding", removed, added, |f| {
+                format!("{} {} {} to {}", f.date, f.quantity, f.feed, f.group)
+            }),
+        }
+    }
+}
+
+/// One line for each item of a flock-wide list `removed` and `added`.
+fn describe_list<T>(
+    what: &str,
+    removed: &[T],
+    added: &[T],
+    show: impl Fn(&T) -> String,
+) -> Vec<String> {
+    removed
+        .iter()
+        .map(|i| format!("removed {what} {}", show(i)))
+        .chain(added.iter().map(|i| format!("added {what} {}", show(i))))
+        .collect()
+}
+
+/// The journal for `file`, "herd.flok" keeps it in "herd.journal".
+pub fn path(file: &str) -> PathBuf {
+    Path::new(file).with_extension("journal")
+}
+
+pub fn read(file: &str) -> Result<Vec<Entry>> {
+    let path = path(file);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|l| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|l| Ok(serde_json::from_str(&crypto::open_line(&l?)?)?))
+        .collect()
+}
+
+/// Who to record changes as.
+fn user() -> String {
+    ["FLOK_USER", "USER", "USERNAME"]
+        .iter()
+        .find_map(|v| std::env::var(v).ok())
+        .unwrap_or("unknown".to_string())
+}
+
+/// The flock as the journal last saw it.
+pub fn replay<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> Flok {
+    let mut flok = Flok::default();
+    for entry in entries {
+        match &entry.change {
+            Change::Animal { before, after } => {
+                let position = before
+                    .as_ref()
+                    .and_then(|b| flok.animals.iter().position(|a| a == b));
+                match (position, after) {
+                    (Some(p), Some(a)) => flok.animals[p] = a.clone(),
+                    (Some(p), None) => {
+                        flok.animals.remove(p);
+                    }
+                    (None, Some(a)) => flok.animals.push(a.clone()),
+                    (None, None) => {}
+                }
+            }
+            Change::Flock {
+                name,
+                date_format,
+                weight_unit,
+            } => {
+                flok.name = name.clone();
+                flok.date_format = *date_format;
+                flok.weight_unit = *weight_unit;
+            }
+            Change::Products { removed, added } => apply(&mut flok.products, removed, added),
+            Change::Protocols { removed, added } => apply(&mut flok.protocols, removed, added),
+            Change::Exposures { removed, added } => apply(&mut flok.exposures, removed, added),
+            Change::Ledger { removed, added } => apply(&mut flok.ledger, removed, added),
+            Change::Contacts { removed, added } => apply(&mut flok.contacts, removed, added),
+            Change::Premises { premises_id } => flok.premises_id = premises_id.clone(),
+            Change::Groups { removed, added } => apply(&mut flok.groups, removed, added),
+            Change::Pastures { removed, added } => apply(&mut flok.pastures, removed, added),
+            Change::FeedPurchases { removed, added } => {
+                apply(&mut flok.feed_purchases, removed, added)
+            }
+            Change::Feedings { removed, added } => apply(&mut flok.feedings, removed, added),
+        }
+    }
+    flok
+}
+
+fn apply<T: PartialEq + Clone>(list: &mut Vec<T>, removed: &[T], added: &[T]) {
+    for item in removed {
+        if let Some(p) = list.iter().position(|i| i == item) {
+            list.remove(p);
+        }
+    }
+    list.extend(added.iter().cloned());
+}
+
+/// The flock as it was at the end of `date`, or at `date` if it has a time.
+pub fn as_of(entries: &[Entry], date: Date) -> Flok {
+    replay(entries.iter().filter(|e| match date.time {
+        None => e.time.day <= date.day,
+        Some(_) => e.time <= date,
+    }))
+}
+
+/// The items of a flock-wide list removed and added between `before` and `after`, `None` if
+/// it didn't change.
+fn diff<T: PartialEq + Clone>(before: &[T], after: &[T]) -> Option<(Vec<T>, Vec<T>)> {
+    let mut added: Vec<Option<&T>> = after.iter().map(Some).collect();
+    let mut removed = vec![];
+    for item in before {
+        match added.iter_mut().find(|a| **a == Some(item)) {
+            Some(kept) => *kept = None,
+            None => removed.push(item.clone()),
+        }
+    }
+    let added: Vec<T> = added.into_iter().flatten().cloned().collect();
+    (!removed.is_empty() || !added.is_empty()).then_some((removed, added))
+}
+
+/// The changes that turn `before` into `after`.
+pub fn changes(before: &Flok, after: &Flok) -> Vec<Change> {
+    let mut changes = vec![];
+    if before.name != after.name
+        || before.date_format != after.date_format
+        || before.weight_unit != after.weight_unit
+    {
+        changes.push(Change::Flock {
+            name: after.name.clone(),
+            date_format: after.date_format,
+            weight_unit: after.weight_unit,
+        });
+    }
+    if let Some((removed, added)) = diff(&before.products, &after.products) {
+        changes.push(Change::Products { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.protocols, &after.protocols) {
+        changes.push(Change::Protocols { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.exposures, &after.exposures) {
+        changes.push(Change::Exposures { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.ledger, &after.ledger) {
+        changes.push(Change::Ledger { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.contacts, &after.contacts) {
+        changes.push(Change::Contacts { removed, added });
+    }
+    if before.premises_id != after.premises_id {
+        changes.push(Change::Premises {
+            premises_id: after.premises_id.clone(),
+        });
+    }
+    if let Some((removed, added)) = diff(&before.groups, &after.groups) {
+        changes.push(Change::Groups { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.pastures, &after.pastures) {
+        changes.push(Change::Pastures { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.feed_purchases, &after.feed_purchases) {
+        changes.push(Change::FeedPurchases { removed, added });
+    }
+    if let Some((removed, added)) = diff(&before.feedings, &after.feedings) {
+        changes.push(Change::Feedings { removed, added });
+    }
+    let mut old: Vec<Option<&Animal>> = before.animals.iter().map(Some).collect();
+    let mut edited = vec![];
+    for a in &after.animals {
+        match old.iter_mut().find(|o| *o == &Some(a)) {
+            Some(unchanged) => *unchanged = None,
+            None => edited.push(a),
+        }
+    }
+    for a in edited {
+        let previous = old
+            .iter_mut()
+            .find(|o| o.is_some_and(|o| o.id.iter().any(|id| a.id.contains(id))))
+            .and_then(|o| o.take());
+        changes.push(Change::Animal {
+            before: previous.cloned(),
+            after: Some(a.clone()),
+        });
+    }
+    changes.extend(old.into_iter().flatten().map(|a| Change::Animal {
+        before: Some(a.clone()),
+        after: None,
+    }));
+    changes
+}
+
+/// Append what changed since the last save of `file`.
+pub fn record(file: &str, flok: &Flok) -> Result<()> {
+    let entries = read(file)?;
+    let changes = changes(&replay(&entries), flok);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let mut journal = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path(file))?;
+    let (time, user) = (Date::now(), user());
+    for change in changes {
+        let entry = Entry {
+            time,
+            user: user.clone(),
+            change,
+        };
+        writeln!(
+            journal,
+            "{}",
+            crypto::seal_line(serde_json::to_string(&entry)?)?
+        )?;
+    }
+    Ok(())
+}
+
+/// Encrypt the lines of the journal for `file` written before it had a passphrase.
+pub fn reseal(file: &str, passphrase: &str) -> Result<()> {
+    let path = path(file);
+    if !path.exists() {
+        return Ok(());
+    }
+    // sealed lines are hex, the others JSON
+    let plain = |l: &str| l.trim_start().starts_with('{');
+    let text = fs::read_to_string(&path)?;
+    if !text.lines().any(plain) {
+        return Ok(());
+    }
+    let mut sealed = String::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        if plain(line) {
+            sealed += &crypto::encrypt_line(line, passphrase)?;
+        } else {
+            sealed += line;
+        }
+        sealed.push('\n');
+    }
+    let temp = path.with_extension("journal.tmp");
+    fs::write(&temp, sealed)?;
+    fs::rename(temp, path)?;
+    Ok(())
+}
+
+/// The journal entries that touch the animal with tag `id`.
+pub fn history<'a>(entries: &'a [Entry], id: &Id) -> Vec<&'a Entry> {
+    entries.iter().filter(|e| e.change.concerns(id)).collect()
+}
+
+pub fn history_html(entries: &[Entry], id: &Id) -> String {
+    let rows: String = history(entries, id)
+        .iter()
+        .map(|e| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                e.time,
+                escape(&e.user),
+                e.change
+                    .describe()
+                    .iter()
+                    .map(|l| escape(l))
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>History of {id}</title></head><body>\n<h1>History of {id}</h1>\n\
+         <table border=\"1\">\n<tr><th>When</th><th>Who</th><th>Change</th></tr>\n{rows}</table>\n\
+         </body></html>\n",
+        id = escape(id)
+    )
+}
+
+pub fn report_history(file: &str, id: &Id) -> Result<()> {
+    open_in_browser(&history_html(&read(file)?, id), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn animal(id: &str, description: &str) -> Animal {
+        Animal {
+            id: vec![id.to_string()],
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn history_and_rebuild() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("herd.flok");
+        let file = file.to_str().unwrap();
+
+        let mut flok = Flok {
+            name: "herd".to_string(),
+            weight_unit: WeightUnit::Kg,
+            animals: vec![animal("1", "ewe"), animal("2", "ram")],
+            ..Default::default()
+        };
+        record(file, &flok).unwrap();
+        flok.animals[0].description = "good mom".to_string();
+        flok.animals.remove(1);
+        flok.animals.push(animal("3", "lamb"));
+        record(file, &flok).unwrap();
+        // nothing changed, nothing recorded
+        record(file, &flok).unwrap();
+
+        let mut entries = read(file).unwrap();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(replay(&entries), flok);
+        assert_eq!(
+            history(&entries, &"1".to_string())
+                .iter()
+                .map(|e| e.change.describe())
+                .collect::<Vec<_>>(),
+            vec![
+                vec!["added 1".to_string()],
+                vec!["Description: ewe -> good mom".to_string()]
+            ]
+        );
+
+        // pretend the first save was last year
+        let last_year = Date::parse("2020-06-01 10:00").unwrap();
+        entries[..3].iter_mut().for_each(|e| e.time = last_year);
+        let then = as_of(&entries, Date::parse("2020-06-01").unwrap());
+        assert_eq!(then.animals, vec![animal("1", "ewe"), animal("2", "ram")]);
+        assert!(as_of(&entries, Date::parse("2020-05-31").unwrap())
+            .animals
+            .is_empty());
+
+        reseal(file, "correct horse").unwrap();
+        let sealed = fs::read_to_string(path(file)).unwrap();
+        assert_eq!(sealed.lines().count(), 6);
+        assert!(sealed
+            .lines()
+            .all(|l| l.chars().all(|c| c.is_ascii_hexdigit())));
+        assert!(!sealed.contains("good mom"));
+    }
+
+    #[test]
+    fn flock_lists() {
+        let entries = |changes: Vec<Change>| -> Vec<Entry> {
+            changes
+                .into_iter()
+                .map(|change| Entry {
+                    time: Date::now(),
+                    user: "me".to_string(),
+                    change,
+                })
+                .collect()
+        };
+        let mut flok = Flok {
+            products: vec![Product {
+                name: "Cydectin".to_string(),
+                meat_withdrawal: 14,
+                ..Default::default()
+            }],
+            protocols: vec![crate::health::Protocol {
+                name: "CDT booster".to_string(),
+                treatment: "CDT".to_string(),
+                ..Default::default()
+            }],
+            exposures: vec![crate::breeding::Exposure {
+                name: "Fall".to_string(),
+                ram: "9".to_string(),
+                ewes: vec!["1".to_string()],
+                ..Default::default()
+            }],
+            ledger: vec![LedgerEntry {
+                date: Date::parse("2024-05-01").unwrap(),
+                category: Category::Sale,
+                amount: 250.0,
+                animal: Some("1".to_string()),
+                description: String::new(),
+            }],
+            contacts: vec![Contact {
+                name: "Sale barn".to_string(),
+                ..Default::default()
+            }],
+            premises_id: "00ABC12".to_string(),
+            groups: vec![crate::groups::Group {
+                name: "Ewes".to_string(),
+                members: vec!["1".to_string()],
+            }],
+            pastures: vec![crate::groups::Pasture {
+                name: "Creek".to_string(),
+                ..Default::default()
+            }],
+            feed_purchases: vec![crate::feed::FeedPurchase {
+                feed: "Grass hay".to_string(),
+                quantity: 40.0,
+                cost: 300.0,
+                ..Default::default()
+            }],
+            feedings: vec![crate::feed::Feeding {
+                group: "Ewes".to_string(),
+                feed: "Grass hay".to_string(),
+                quantity: 2.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut saved = entries(changes(&Flok::default(), &flok));
+        assert_eq!(replay(&saved), flok);
+        assert_eq!(saved[0].change.describe(), vec!["added product Cydectin"]);
+
+        let before = flok.clone();
+        flok.products[0].meat_withdrawal = 36;
+        let edits = entries(changes(&before, &flok));
+        assert_eq!(
+            edits[0].change.describe(),
+            vec!["removed product Cydectin", "added product Cydectin"]
+        );
+        saved.extend(edits);
+        assert_eq!(replay(&saved), flok);
+    }
+}
@@ -0,0 +1,428 @@
+use anyhow::Result;
+use chrono::Datelike;
+use enum_ordinalize::Ordinalize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    feed,
+    flok::*,
+    report::{escape, open_in_browser},
+};
+
+/// Whether `animal` was in the flock on `date`: born by then and not yet dead or sold.
+pub fn on_hand(animal: &Animal, date: Date) -> bool {
+    animal.born.is_none_or(|b| b.day <= date.day)
+        && animal.died().is_none_or(|d| d.day >= date.day)
+        && !animal
+            .events
+            .iter()
+            .any(|e| e.is_sale() && e.date.day < date.day)
+}
+
+/// Profit and loss for one animal.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AnimalPnl {
+    pub id: Id,
+    pub born: Option<Date>,
+    pub income: f64,
+    pub expenses: f64,
+    /// Its share of flock-wide costs and income
+    pub allocated: f64,
+    /// Its share of what its groups were fed
+    pub feed: f64,
+    pub profit: f64,
+}
+
+/// Each animal's profit from transactions in `year`, or all of them. Flock-wide transactions
+/// are shared equally by the animals on hand that day, and feed by the group members it was fed
+/// to.
+pub fn animal_pnl(flok: &Flok, year: Option<i32>) -> Vec<AnimalPnl> {
+    let in_year = |t: &&LedgerEntry| year.is_none_or(|y| t.date.day.year() == y);
+    let mut allocated = vec![0.0; flok.animals.len()];
+    for t in flok
+        .ledger
+        .iter()
+        .filter(in_year)
+        .filter(|t| t.animal.is_none() && !feed::is_purchase(flok, t))
+    {
+        let sharing: Vec<usize> = (0..flok.animals.len())
+            .filter(|i| on_hand(&flok.animals[*i], t.date))
+            .collect();
+        for i in &sharing {
+            allocated[*i] += t.signed() / sharing.len() as f64;
+        }
+    }
+    flok.animals
+        .iter()
+        .zip(allocated)
+        .zip(feed::feed_costs(flok, year).animals)
+        .map(|((a, allocated), feed)| {
+            let own: Vec<&LedgerEntry> = flok
+                .ledger
+                .iter()
+                .filter(in_year)
+                .filter(|t| t.animal.as_ref().is_some_and(|id| a.id.contains(id)))
+                .collect();
+            let income: f64 = own
+                .iter()
+                .filter(|t| t.category.is_income())
+                .map(|t| t.amount)
+                .sum();
+            let expenses: f64 = own
+                .iter()
+                .filter(|t| !t.category.is_income())
+                .map(|t| t.amount)
+                .sum();
+            AnimalPnl {
+                id: a.id.first().cloned().unwrap_or_default(),
+                born: a.born,
+                income,
+                expenses,
+                allocated,
+                feed,
+                profit: income - expenses + allocated - feed,
+            }
+        })
+        .collect()
+}
+
+/// Profit from the lambs born in one year, over their lives.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CropPnl {
+    pub season: i32,
+    pub lambs: usize,
+    pub income: f64,
+    pub costs: f64,
+    pub profit: f64,
+    pub per_lamb: f64,
+}
+
+pub fn crop_pnl(flok: &Flok) -> Vec<CropPnl> {
+    let mut crops: BTreeMap<i32, Vec<AnimalPnl>> = BTreeMap::new();
+    for (a, pnl) in flok.animals.iter().zip(animal_pnl(flok, None)) {
+        if let Some(born) = a.born.filter(|_| a.dam.is_some()) {
+            crops.entry(born.day.year()).or_default().push(pnl);
+        }
+    }
+    crops
+        .into_iter()
+        .map(|(season, lambs)| {
+            let income: f64 = lambs.iter().map(|l| l.income).sum();
+            let profit: f64 = lambs.iter().map(|l| l.profit).sum();
+            CropPnl {
+                season,
+                lambs: lambs.len(),
+                income,
+                costs: income - profit,
+                profit,
+                per_lamb: profit / lambs.len() as f64,
+            }
+        })
+        .collect()
+}
+
+/// The flock's income and expenses in one year.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct YearPnl {
+    pub year: i32,
+    pub income: f64,
+    pub expenses: f64,
+    pub profit: f64,
+    pub by_category: BTreeMap<String, f64>,
+}
+
+/// The ledger and feed purchases by year.
+pub fn year_pnl(flok: &Flok) -> Vec<YearPnl> {
+    let bought: Vec<LedgerEntry> = flok
+        .feed_purchases
+        .iter()
+        .map(|p| LedgerEntry {
+            date: p.date,
+            category: p.kind.category(),
+            amount: p.cost,
+            animal: None,
+            description: p.feed.clone(),
+        })
+        .collect();
+    let mut years: BTreeMap<i32, Vec<&LedgerEntry>> = BTreeMap::new();
+    // a purchase also entered in the ledger is counted once
+    let entered = flok.ledger.iter().filter(|t| !feed::is_purchase(flok, t));
+    for t in entered.chain(&bought) {
+        years.entry(t.date.day.year()).or_default().push(t);
+    }
+    years
+        .into_iter()
+        .map(|(year, ts)| {
+            let mut by_category = BTreeMap::new();
+            for t in &ts {
+                *by_category
+                    .entry(t.category.name().to_string())
+                    .or_insert(0.0) += t.signed();
+            }
+            let income: f64 = ts
+                .iter()
+                .filter(|t| t.category.is_income())
+                .map(|t| t.amount)
+                .sum();
+            let expenses: f64 = ts
+                .iter()
+                .filter(|t| !t.category.is_income())
+                .map(|t| t.amount)
+                .sum();
+            YearPnl {
+                year,
+                income,
+                expenses,
+                profit: income - expenses,
+                by_category,
+            }
+        })
+        .collect()
+}
+
+/// A money amount in a cell like "$1,250.00", `None` if it isn't one.
+pub fn money(text: &str) -> Option<f64> {
+    let text: String = text
+        .chars()
+        .filter(|c| !matches!(c, '$' | ',' | ' '))
+        .collect();
+    text.parse().ok().filter(|n: &f64| *n > 0.0)
+}
+
+/// Record the prices kept as events, like the sheet's "purchase price" and "sale $" columns, in
+/// the ledger. A "purchase date" event dates the purchase. Prices already in the ledger are
+/// skipped. Returns how many were added.
+pub fn prices_from_events(flok: &mut Flok) -> usize {
+    let mut found = vec![];
+    for a in &flok.animals {
+        let id = a.id.first().cloned().unwrap_or_default();
+        let purchased = a
+            .events
+            .iter()
+            .find(|e| e.name.trim().eq_ignore_ascii_case("purchase date"))
+            .and_then(|e| Date::parse(&e.value));
+        for e in &a.events {
+            let Some(amount) = money(&e.value) else {
+                continue;
+            };
+            let (category, date) = if e.is_sale() {
+                (Category::Sale, e.date)
+            } else if e.is_purchase() {
+                (Category::Purchase, purchased.unwrap_or(e.date))
+            } else {
+                continue;
+            };
+            found.push(LedgerEntry {
+                date,
+                category,
+                amount,
+                animal: Some(id.clone()),
+                description: e.name.clone(),
+            });
+        }
+    }
+    let mut added = 0;
+    for t in found {
+        let recorded = flok
+            .ledger
+            .iter()
+            .any(|l| l.animal == t.animal && l.category == t.category && l.amount == t.amount);
+        if !recorded {
+            flok.ledger.push(t);
+            added += 1;
+        }
+    }
+    added
+}
+
+fn dollars(n: f64) -> String {
+    format!("{n:.2}")
+}
+
+pub fn pnl_html(flok: &Flok) -> String {
+    let categories: Vec<String> = Category::VARIANTS
+        .iter()
+        .map(|c| c.name().to_string())
+        .collect();
+    let years: String = year_pnl(flok)
+        .iter()
+        .map(|y| {
+            let cells: String = categories
+                .iter()
+                .map(|c| {
+                    format!(
+                        "<td>{}</td>",
+                        y.by_category
+                            .get(c)
+                            .map(|n| dollars(*n))
+                            .unwrap_or_default()
+                    )
+                })
+                .collect();
+            format!(
+                "<tr><td>{}</td>{cells}<td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                y.year,
+                dollars(y.income),
+                dollars(y.expenses),
+                dollars(y.profit)
+            )
+        })
+        .collect();
+    let crops: String = crop_pnl(flok)
+        .iter()
+        .map(|c| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                c.season,
+                c.lambs,
+                dollars(c.income),
+                dollars(c.costs),
+                dollars(c.profit),
+                dollars(c.per_lamb)
+            )
+        })
+        .collect();
+    let mut animals = animal_pnl(flok, None);
+    animals.retain(|a| {
+        [a.income, a.expenses, a.allocated, a.feed]
+            .iter()
+            .any(|n| *n != 0.0)
+    });
+    animals.sort_by(|a, b| b.profit.total_cmp(&a.profit));
+    let animals: String = animals
+        .iter()
+        .map(|a| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 </tr>\n",
+                escape(&a.id),
+                a.born.map(|b| b.to_string()).unwrap_or_default(),
+                dollars(a.income),
+                dollars(a.expenses),
+                dollars(a.allocated),
+                dollars(a.feed),
+                dollars(a.profit)
+            )
+        })
+        .collect();
+    let unallocated = feed::feed_costs(flok, None).unallocated;
+    let unallocated = if unallocated > 0.0 {
+        format!(
+            "<p>Feed fed to groups with no members on hand, not shared by any animal: {}</p>\n",
+            dollars(unallocated)
+        )
+    } else {
+        String::new()
+    };
+    let category_headers: String = categories
+        .iter()
+        .map(|c| format!("<th>{}</th>", escape(c)))
+        .collect();
+    format!(
+        "<html><head><title>Profit and loss</title></head><body>\n<h1>Profit and loss</h1>\n\
+         <h2>By year</h2>\n<table border=\"1\">\n<tr><th>Year</th>{category_headers}\
+         <th>Income</th><th>Expenses</th><th>Profit</th></tr>\n{years}</table>\n\
+         <h2>By lamb crop</h2>\n\
+         <p>Lambs born each year, with their share of flock-wide costs over their lives.</p>\n\
+         <table border=\"1\">\n<tr><th>Born</th><th>Lambs</th><th>Income</th><th>Costs</th>\
+         <th>Profit</th><th>Per lamb</th></tr>\n{crops}</table>\n\
+         <h2>By animal</h2>\n\
+         <p>Flock-wide costs such as feed and hay are shared by the animals on hand that day. \
+         Feed recorded as fed to a group is shared by the members on hand that day.</p>\n\
+         <table border=\"1\">\n<tr><th>Animal</th><th>Born</th><th>Income</th><th>Expenses</th>\
+         <th>Share of flock</th><th>Feed</th><th>Profit</th></tr>\n{animals}</table>\n\
+         {unallocated}</body></html>\n"
+    )
+}
+
+pub fn report_pnl(flok: &Flok) -> Result<()> {
+    open_in_browser(&pnl_html(flok), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(date: &str, category: Category, amount: f64, animal: Option<&str>) -> LedgerEntry {
+        LedgerEntry {
+            date: Date::parse(date).unwrap(),
+            category,
+            amount,
+            animal: animal.map(|a| a.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn event(name: &str, value: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            value: value.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn profit_and_loss() {
+        let animal = |id: &str, dam: Option<&str>, born: &str, events| Animal {
+            id: vec![id.to_string()],
+            dam: dam.map(|d| d.to_string()),
+            born: Date::parse(born),
+            events,
+            ..Default::default()
+        };
+        let mut flok = Flok {
+            animals: vec![
+                animal(
+                    "ewe",
+                    None,
+                    "2020-03-01",
+                    vec![
+                        event("purchase price", "$250", "2025-01-01"),
+                        event("purchase date", "2021-05-01", "2025-01-01"),
+                    ],
+                ),
+                animal(
+                    "lamb",
+                    Some("ewe"),
+                    "2024-03-01",
+                    vec![event("sale $", "1,200.50", "2024-10-01")],
+                ),
+                // born after the hay was bought
+                animal("late", Some("ewe"), "2024-06-01", vec![]),
+            ],
+            ledger: vec![
+                entry("2024-04-01", Category::Hay, 300.0, None),
+                entry("2024-05-01", Category::Vet, 50.0, Some("lamb")),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(prices_from_events(&mut flok), 2);
+        assert_eq!(prices_from_events(&mut flok), 0);
+        assert_eq!(flok.ledger[2].date.to_iso(), "2021-05-01");
+
+        let pnl: Vec<(String, f64)> = animal_pnl(&flok, Some(2024))
+            .into_iter()
+            .map(|p| (p.id, p.profit))
+            .collect();
+        assert_eq!(
+            pnl,
+            vec![
+                ("ewe".to_string(), -150.0),
+                ("lamb".to_string(), 1000.5),
+                ("late".to_string(), 0.0)
+            ]
+        );
+        let crops: Vec<(i32, usize, f64)> = crop_pnl(&flok)
+            .into_iter()
+            .map(|c| (c.season, c.lambs, c.profit))
+            .collect();
+        assert_eq!(crops, vec![(2024, 2, 1000.5)]);
+        let years: Vec<(i32, f64, f64)> = year_pnl(&flok)
+            .into_iter()
+            .map(|y| (y.year, y.income, y.expenses))
+            .collect();
+        assert_eq!(years, vec![(2021, 0.0, 250.0), (2024, 1200.5, 350.0)]);
+    }
+}
@@ -0,0 +1,140 @@
+use enum_ordinalize::Ordinalize;
+use fltk::{
+    input::{FloatInput, Input},
+    menu::Choice,
+    prelude::{InputExt, MenuExt},
+    widget::Widget,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    date_input::DateInput,
+    flok::*,
+    form::*,
+    list_editor::{show_list_editor, ItemForm},
+};
+
+/// The fields of one ledger entry.
+#[derive(Clone)]
+pub struct LedgerForm {
+    pub date: DateInput,
+    pub category: Choice,
+    pub amount: FloatInput,
+    pub animal: Input,
+    pub description: Input,
+}
+
+impl ItemForm<LedgerEntry> for LedgerForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let mut form = Self {
+            date: DateInput::default(),
+            category: {
+                let mut category = Choice::default();
+                for c in Category::VARIANTS {
+                    category.add_choice(c.name());
+                }
+                category
+            },
+            amount: FloatInput::default(),
+            animal: Input::default(),
+            description: Input::default(),
+        };
+        form.animal
+            .set_tooltip("Blank for flock-wide costs, which are shared by the animals on hand");
+        let ui = create_form(vec![
+            ("Date", &form.date.group),
+            ("Category", &form.category),
+            ("Amount", &form.amount),
+            ("Animal", &form.animal),
+            ("Description", &form.description),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, e: &LedgerEntry) {
+        // a new entry is dated today
+        self.date.set_date(Some(if e.amount == 0.0 {
+            Date::today()
+        } else {
+            e.date
+        }));
+        self.category.set_value(e.category.ordinal() as i32);
+        self.amount.set_value(&if e.amount == 0.0 {
+            String::new()
+        } else {
+            format!("{:.2}", e.amount)
+        });
+        self.animal
+            .set_value(e.animal.as_deref().unwrap_or_default());
+        self.description.set_value(&e.description);
+    }
+
+    fn item(&mut self, flok: &Flok, _index: Option<usize>) -> Result<LedgerEntry, Vec<FieldError>> {
+        let mut errors = vec![];
+        let date = self.date.parse("Date").unwrap_or_else(|e| {
+            errors.push(e);
+            None
+        });
+        if date.is_none() && !errors.iter().any(|e| e.field == "Date") {
+            errors.push(FieldError::new("Date", "Date is required"));
+        }
+        let text = self.amount.value();
+        let amount = match text
+            .trim()
+            .trim_start_matches('$')
+            .replace(',', "")
+            .parse::<f64>()
+        {
+            Ok(amount) if amount > 0.0 => amount,
+            _ => {
+                errors.push(FieldError::new(
+                    "Amount",
+                    format!("\"{text}\" is not an amount"),
+                ));
+                0.0
+            }
+        };
+        let animal = Some(self.animal.value().trim().to_string()).filter(|a| !a.is_empty());
+        if let Some(id) = animal
+            .as_ref()
+            .filter(|id| flok.find((*id).clone()).is_none())
+        {
+            errors.push(FieldError::new(
+                "Animal",
+                format!("{id} is not in the flock"),
+            ));
+        }
+
+        highlight(&mut self.date.input, "Date", &errors);
+        highlight(&mut self.amount, "Amount", &errors);
+        highlight(&mut self.animal, "Animal", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(LedgerEntry {
+            date: date.unwrap_or_default(),
+            category: Category::from_ordinal(self.category.value() as i8).unwrap_or_default(),
+            amount,
+            animal,
+            description: self.description.value().trim().to_string(),
+        })
+    }
+}
+
+/// Edit the flock's income and expenses.
+pub fn show_ledger(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<LedgerEntry, LedgerForm>(
+        "Ledger",
+        flok,
+        |f| &mut f.ledger,
+        |e| {
+            format!(
+                "{} {} {:.2} {}",
+                e.date,
+                e.category.name(),
+                e.signed(),
+                e.animal.as_deref().unwrap_or("flock")
+            )
+        },
+    )
+}
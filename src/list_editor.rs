@@ -0,0 +1,152 @@
+use fltk::{
+    browser::HoldBrowser,
+    button::Button,
+    frame::Frame,
+    group::Flex,
+    prelude::{BrowserExt, GroupExt, WidgetBase, WidgetExt, WindowExt},
+    widget::Widget,
+    window::Window,
+};
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use crate::{business_obj::BusinessObject, flok::*, form::*};
+
+/// The fields of one item of a list kept in the flock, such as a product.
+pub trait ItemForm<T>: Clone + 'static {
+    /// The form's fields, laid out with `create_form`.
+    fn create() -> anyhow::Result<(Self, Widget)>;
+    fn set_item(&mut self, item: &T);
+    /// The item entered, checked against `flok`. `index` is the item being replaced, if any.
+    fn item(&mut self, flok: &Flok, index: Option<usize>) -> Result<T, Vec<FieldError>>;
+    /// Update what refers to an item after `before` is saved as `after`, like a renamed pasture.
+    fn replaced(_flok: &mut Flok, _before: &T, _after: &T) {}
+    /// Why `item` can't be deleted, `None` if nothing refers to it.
+    fn in_use(_flok: &Flok, _item: &T) -> Option<String> {
+        None
+    }
+}
+
+/// A list of items beside a form to add, change and delete them. `items` picks the list out of
+/// the flock and `label` is how each item is listed.
+pub fn show_list_editor<T, F>(
+    title: &'static str,
+    flok: Arc<Mutex<Flok>>,
+    items: fn(&mut Flok) -> &mut Vec<T>,
+    label: fn(&T) -> String,
+) -> anyhow::Result<()>
+where
+    T: Clone + Default + 'static,
+    F: ItemForm<T>,
+{
+    let mut wind = Window::default().with_size(650, 350).with_label(title);
+    let mut page = Flex::default_fill().column();
+    let mut panes = Flex::default().row();
+    let mut list = HoldBrowser::default();
+    panes.fixed(&list, 200);
+    let (mut form, _) = F::create()?;
+    panes.end();
+
+    // the item being edited, `None` for a new one
+    let editing = Rc::new(Cell::new(None::<usize>));
+    let mut refresh = {
+        let (flok, mut list) = (flok.clone(), list.clone());
+        move || {
+            list.clear();
+            for item in &flok.exec(|f| items(f).clone()) {
+                // '@' starts a formatting code
+                list.add(&label(item).replace('@', "@@"));
+            }
+        }
+    };
+    refresh();
+
+    let mut buttons = Flex::default().row();
+    {
+        let mut new = Button::default().with_label("New");
+        let (mut form, editing, mut refresh) = (form.clone(), editing.clone(), refresh.clone());
+        new.set_callback(move |_| {
+            editing.set(None);
+            refresh();
+            form.set_item(&T::default());
+        });
+        buttons.fixed(&new, 60);
+    }
+    {
+        let mut delete = Button::default().with_label("Delete");
+        let (flok, mut form, editing, mut refresh) =
+            (flok.clone(), form.clone(), editing.clone(), refresh.clone());
+        delete.set_callback(move |_| {
+            if let Some(i) = editing.get() {
+                if let Some(reason) = flok.exec(|f| F::in_use(f, &items(f)[i])) {
+                    display_error::<()>("Unable to delete", Err(anyhow::anyhow!(reason)));
+                    return;
+                }
+                editing.set(None);
+                flok.exec(|f| items(f).remove(i));
+                form.set_item(&T::default());
+                refresh();
+            }
+        });
+        buttons.fixed(&delete, 60);
+    }
+    buttons.resizable(&Frame::default());
+    {
+        let mut close = Button::default().with_label("Close");
+        let mut wind = wind.clone();
+        close.set_callback(move |_| wind.hide());
+        buttons.fixed(&close, 60);
+    }
+    {
+        let mut save = Button::default().with_label("Save");
+        let (flok, mut form, editing, mut list) =
+            (flok.clone(), form.clone(), editing.clone(), list.clone());
+        save.set_callback(move |_| {
+            let index = editing.get();
+            match flok.exec(|f| form.item(f, index)) {
+                Ok(item) => {
+                    let i = flok.exec(|f| match index {
+                        Some(i) => {
+                            let before = std::mem::replace(&mut items(f)[i], item.clone());
+                            F::replaced(f, &before, &item);
+                            i
+                        }
+                        None => {
+                            items(f).push(item.clone());
+                            items(f).len() - 1
+                        }
+                    });
+                    editing.set(Some(i));
+                    refresh();
+                    list.select(i as i32 + 1);
+                }
+                Err(errors) => display_error::<()>(
+                    &format!("Unable to save {}", title.to_lowercase()),
+                    Err(validation_error(errors)),
+                ),
+            }
+        });
+        buttons.fixed(&save, 60);
+    }
+    buttons.end();
+    page.fixed(&buttons, 25);
+    page.end();
+
+    list.set_callback(move |l| {
+        if l.value() > 0 {
+            let i = l.value() as usize - 1;
+            editing.set(Some(i));
+            if let Some(item) = flok.exec(|f| items(f).get(i).cloned()) {
+                form.set_item(&item);
+            }
+        }
+    });
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+    Ok(())
+}
@@ -1,35 +1,66 @@
 #![feature(mapped_lock_guards)]
 
-use anyhow::Result;
-use calamine::{DataType, Reader};
-use chrono::{Local, TimeZone};
+use anyhow::{anyhow, Result};
 use clap::Parser;
+use enum_ordinalize::Ordinalize;
 use fltk::{
     app::{self},
-    dialog::file_chooser,
+    dialog::{self, file_chooser},
     enums::{Mode, Shortcut},
     group::Pack,
     menu::{self, SysMenuBar},
     prelude::{GroupExt, MenuExt, WidgetBase, WidgetExt},
     window::Window,
 };
-use layout::{
-    backends::svg::SVGWriter,
-    gv::{DotParser, GraphBuilder},
-};
-use std::{
-    fs::File,
-    io::Write,
-    sync::{Arc, Mutex},
-};
-use tempfile::NamedTempFile;
+use std::sync::{Arc, Mutex};
 
 mod animal_form;
+mod backup;
+mod backup_form;
+mod breeding;
+mod breeding_form;
 mod business_obj;
+mod cli;
+mod contact_form;
+mod contacts;
+mod crypto;
+mod cull;
+mod cull_form;
+mod date;
+mod date_input;
+mod event_form;
+mod export;
+mod feed;
+mod feed_form;
 mod flok;
+mod flok_file;
 mod flok_form;
 mod form;
-mod event_form;
+mod grazing;
+mod group_form;
+mod groups;
+mod growth;
+mod health;
+mod health_form;
+mod import;
+mod journal;
+mod ledger;
+mod ledger_form;
+mod list_editor;
+mod merge;
+mod merge_form;
+mod product_form;
+mod productivity;
+mod regulatory;
+mod report;
+mod scores;
+mod storage;
+mod sync;
+// served by flok2-server, the sync tests run it here
+#[cfg(test)]
+mod sync_server;
+mod treatment;
+mod validate;
 
 use flok::*;
 use form::*;
@@ -37,12 +68,20 @@ use form::*;
 use crate::business_obj::BusinessObject;
 
 #[derive(clap::Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
     file: Option<String>,
     #[clap(long, short)]
     import: Option<String>,
+    #[command(subcommand)]
+    command: Option<cli::Command>,
 }
 pub fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        return cli::run(command);
+    }
+
     let app = app::App::default().with_scheme(app::Scheme::Plastic);
     app.set_visual(Mode::MultiSample | Mode::Alpha)?;
 
@@ -57,11 +96,11 @@ pub fn main() -> Result<()> {
 
     let form = Arc::new(Mutex::new(flok_form::FlokForm::create(Flok::default())));
 
-    if let Some(file) = Cli::parse().file {
-        *(form.lock().unwrap().flok.lock().unwrap()) = serde_json::from_reader(File::open(file)?)?;
+    if let Some(file) = cli.file {
+        open_flok(form.clone(), file)?;
     }
 
-    if let Some(file) = Cli::parse().import {
+    if let Some(file) = cli.import {
         import_file(form.clone(), file)?;
     }
 
@@ -90,9 +129,9 @@ pub fn main() -> Result<()> {
             Shortcut::None,
             menu::MenuFlag::Normal,
             move |_| {
-                form.lock()
-                    .expect("Unable to lock editor")
-                    .set_value(&Default::default())
+                let mut form = form.lock().expect("Unable to lock editor");
+                form.set_value(&Default::default());
+                *form.file.lock().unwrap() = None;
             },
         );
     }
@@ -106,12 +145,7 @@ pub fn main() -> Result<()> {
                 let mut flok_form = form.lock().unwrap();
                 flok_form.flok.lock().unwrap().animals.push(Animal {
                     id: vec!["new".to_string()],
-                    born: None,
-                    sire: None,
-                    dam: None,
-                    events: vec![],
-                    description: "".to_string(),
-                    sex: Sex::Female,
+                    ..Default::default()
                 });
                 flok_form.update();
             },
@@ -126,11 +160,351 @@ pub fn main() -> Result<()> {
             move |_| {
                 display_error(
                     "Unable to report lineage",
-                    form.lock().unwrap().flok.exec(report_lineage),
+                    form.lock().unwrap().flok.exec(report::report_lineage),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/ADG Ranking...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to rank lambs", adg_ranking(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Productivity Report...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report productivity",
+                    productivity_report(form.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Ledger...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show the ledger",
+                    ledger_form::show_ledger(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Feed Purchases...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show feed purchases",
+                    feed_form::show_feed_purchases(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Feedings...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show feedings",
+                    feed_form::show_feedings(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Feed Inventory\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report feed",
+                    form.lock().unwrap().flok.exec(|f| feed::report_feed(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Record Prices in Ledger\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                let added = form
+                    .lock()
+                    .unwrap()
+                    .flok
+                    .exec(|f| ledger::prices_from_events(f));
+                dialog::message_default(&format!(
+                    "Added {added} purchase and sale prices to the ledger"
+                ));
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Contacts...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show contacts",
+                    contact_form::show_contacts(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Contact History...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show contact history",
+                    contact_form::show_history(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Link Events to Contacts\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                let linked = form
+                    .lock()
+                    .unwrap()
+                    .flok
+                    .exec(|f| contacts::link_contacts(f));
+                dialog::message_default(&format!(
+                    "Linked {linked} purchase and sale events to contacts"
+                ));
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Profit and Loss Report\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report profit and loss",
+                    form.lock().unwrap().flok.exec(|f| ledger::report_pnl(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Breeding Groups...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show breeding groups",
+                    breeding_form::show_exposures(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Lambing Calendar\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show the lambing calendar",
+                    form.lock()
+                        .unwrap()
+                        .flok
+                        .exec(|f| breeding::report_calendar(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Groups...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show groups",
+                    group_form::show_groups(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Pastures...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show pastures",
+                    group_form::show_pastures(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Group Event...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                let flok = form.lock().unwrap().flok.clone();
+                let form = form.clone();
+                display_error(
+                    "Unable to record a group event",
+                    group_form::show_group_event(flok, move || form.lock().unwrap().update()),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Show Group...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to show the group", show_group(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Where Is Everyone\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report where animals are",
+                    form.lock().unwrap().flok.exec(|f| groups::report_where(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Grazing Plan\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report grazing",
+                    form.lock()
+                        .unwrap()
+                        .flok
+                        .exec(|f| grazing::report_grazing(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Treatments Due...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                let flok = form.lock().unwrap().flok.clone();
+                let form = form.clone();
+                display_error(
+                    "Unable to show treatments due",
+                    health_form::show_due(flok, move || form.lock().unwrap().update()),
                 );
             },
         );
     }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Parasite Report\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report parasites",
+                    form.lock()
+                        .unwrap()
+                        .flok
+                        .exec(|f| scores::report_parasites(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Cull List\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to list culls", cull_list(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Withdrawal Report\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to report withdrawal",
+                    form.lock()
+                        .unwrap()
+                        .flok
+                        .exec(|f| treatment::report_withdrawal(f)),
+                );
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Movement Records...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to report movements", movement_records(form.clone())),
+        );
+    }
     {
         let form = form.clone();
         menu.add(
@@ -142,6 +516,165 @@ pub fn main() -> Result<()> {
             },
         );
     }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Export...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error("Unable to export file", export_flok(form.clone()));
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Restore from Backup...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to restore", restore_flok(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/View As Of Date...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to rebuild flock", view_as_of(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Action/Merge...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to merge", merge_flok(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Sync/Share on server...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to share flock", share_flok(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Sync/Sync now\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to sync", sync_flok(form.clone())),
+        );
+    }
+    for format in DateFormat::VARIANTS {
+        let form = form.clone();
+        menu.add(
+            &format!(
+                "&Settings/Date Format/{}\t",
+                format.name().replace('/', "\\/")
+            ),
+            Shortcut::None,
+            menu::MenuFlag::Radio,
+            move |_| {
+                let mut flok_form = form.lock().unwrap();
+                date::set_format(*format);
+                flok_form.flok.lock().unwrap().date_format = *format;
+                flok_form.update();
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Settings/Products...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show products",
+                    product_form::show_products(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Settings/Health Protocols...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                display_error(
+                    "Unable to show health protocols",
+                    health_form::show_protocols(form.lock().unwrap().flok.clone()),
+                )
+            },
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Settings/Cull Rules...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to edit cull rules", cull_rules(form.clone())),
+        );
+    }
+    for unit in WeightUnit::VARIANTS {
+        let form = form.clone();
+        menu.add(
+            &format!("&Settings/Weight Unit/{}\t", unit.name()),
+            Shortcut::None,
+            menu::MenuFlag::Radio,
+            move |_| form.lock().unwrap().flok.exec(|f| f.weight_unit = *unit),
+        );
+    }
+
+    menu.add(
+        "&Settings/Encrypt with Passphrase...\t",
+        Shortcut::None,
+        menu::MenuFlag::Normal,
+        move |_| display_error("Unable to set passphrase", set_passphrase()),
+    );
+    menu.add(
+        "&Settings/Save Without Encryption\t",
+        Shortcut::None,
+        menu::MenuFlag::Normal,
+        move |_| crypto::set_passphrase(None),
+    );
+    {
+        let form = form.clone();
+        menu.add(
+            "&Settings/Backups to Keep...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| display_error("Unable to set backups", set_backups(form.clone())),
+        );
+    }
+    {
+        let form = form.clone();
+        menu.add(
+            "&Settings/Premises ID...\t",
+            Shortcut::None,
+            menu::MenuFlag::Normal,
+            move |_| {
+                let form = form.lock().unwrap();
+                let current = form.flok.exec(|f| f.premises_id.clone());
+                if let Some(premises_id) =
+                    dialog::input_default("The farm's premises ID, for movement records", &current)
+                {
+                    form.flok
+                        .exec(|f| f.premises_id = premises_id.trim().to_string());
+                }
+            },
+        );
+    }
 
     pack.resizable(&form.lock().unwrap().pack);
     pack.end();
@@ -156,24 +689,206 @@ pub fn main() -> Result<()> {
     Ok(())
 }
 
+/// JSON or SQLite
+const FLOK_FILES: &str = "*.flok\t*.flokdb";
+
 fn save_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
-    if let Some(mut file) = file_chooser("File to save to", "*.flok", ".", true) {
-        if !file.ends_with(".flok") {
+    if let Some(mut file) = file_chooser("File to save to", FLOK_FILES, ".", true) {
+        if !file.ends_with(".flok")
+            && !storage::SQLITE_EXTENSIONS
+                .iter()
+                .any(|e| file.ends_with(&format!(".{e}")))
+        {
             file += ".flok"
         }
         let mut form = form.lock().expect("Unable to lock flok");
         // commit data from UI to data structure
-        form.commit();
+        form.commit().map_err(validation_error)?;
         let flok = form.flok.lock().unwrap();
-        serde_json::to_writer_pretty(File::create(file)?, &*flok)?;
+        flok_file::save(&file, &flok)?;
+        *form.file.lock().unwrap() = Some(file);
     }
     Ok(())
 }
 
 fn load_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
-    if let Some(file) = file_chooser("File to load from", "*.flok", ".", true) {
-        let flok: Flok = serde_json::from_reader(File::open(file)?)?;
-        form.lock().unwrap().set_value(&flok);
+    if let Some(file) = file_chooser("File to load from", FLOK_FILES, ".", true) {
+        open_flok(form, file)?;
+    }
+    Ok(())
+}
+
+/// Edit `file`. An unencrypted file stays unencrypted when saved.
+fn open_flok(form: Arc<Mutex<flok_form::FlokForm>>, file: String) -> Result<()> {
+    let flok = load_file(&file)?;
+    if !crypto::is_encrypted(&std::fs::read(&file).unwrap_or_default()) {
+        crypto::set_passphrase(None);
+    }
+    let mut form = form.lock().unwrap();
+    form.set_value(&flok);
+    *form.file.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Load `file`, asking for the passphrase if it is encrypted.
+fn load_file(file: &str) -> Result<Flok> {
+    let mut prompt = "Passphrase for this flock";
+    loop {
+        match flok_file::load(file) {
+            Err(e) if e.downcast_ref::<crypto::PassphraseError>().is_some() => {
+                let Some(passphrase) = dialog::password_default(prompt, "") else {
+                    return Err(e);
+                };
+                crypto::set_passphrase(Some(passphrase));
+                prompt = "Wrong passphrase, try again";
+            }
+            result => return result,
+        }
+    }
+}
+
+fn set_passphrase() -> Result<()> {
+    let Some(passphrase) = dialog::password_default("Passphrase to encrypt the flock with", "")
+    else {
+        return Ok(());
+    };
+    if dialog::password_default("Type the passphrase again", "") != Some(passphrase.clone()) {
+        return Err(anyhow!("The passphrases don't match"));
+    }
+    crypto::set_passphrase(Some(passphrase));
+    dialog::message_default(
+        "The flock, its journal and backups will be encrypted when it is next saved. \
+         Without the passphrase they can't be read.",
+    );
+    Ok(())
+}
+
+fn restore_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let Some(file) = form.lock().unwrap().file.lock().unwrap().clone() else {
+        return Err(anyhow!(
+            "Load the flock first, backups are kept next to its file"
+        ));
+    };
+    backup_form::show_restore(file, move |flok| form.lock().unwrap().set_value(&flok))
+}
+
+fn set_backups(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let form = form.lock().unwrap();
+    let current = form.flok.exec(|f| f.backups());
+    if let Some(keep) = dialog::input_default(
+        "Backups to keep next to the file, 0 for none",
+        &current.to_string(),
+    ) {
+        let keep = keep.trim().parse::<usize>()?;
+        form.flok.exec(|f| f.backups = Some(keep));
+    }
+    Ok(())
+}
+
+/// Show only the members of a group in the table, or every animal when left blank.
+fn show_group(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let mut form = form.lock().unwrap();
+    let current = form.group.lock().unwrap().clone().unwrap_or_default();
+    let Some(name) = dialog::input_default("Show the group, blank for every animal", &current)
+    else {
+        return Ok(());
+    };
+    let name = name.trim().to_string();
+    if !name.is_empty() && form.flok.exec(|f| groups::group(f, &name).is_none()) {
+        return Err(anyhow!("There is no group {name}"));
+    }
+    *form.group.lock().unwrap() = Some(name).filter(|n| !n.is_empty());
+    form.update();
+    Ok(())
+}
+
+/// The inspector's records of animals acquired and disposed of between two dates.
+fn movement_records(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    use chrono::Datelike;
+    let form = form.lock().unwrap();
+    let today = Date::today();
+    let ask = |label: &str, default: Date| -> Result<Option<Date>> {
+        dialog::input_default(label, &default.to_string())
+            .map(|d| Date::parse(&d).ok_or_else(|| anyhow!("\"{d}\" is not a date")))
+            .transpose()
+    };
+    let start = chrono::NaiveDate::from_ymd_opt(today.day.year(), 1, 1).unwrap();
+    let Some(from) = ask("Movements from", start.into())? else {
+        return Ok(());
+    };
+    let Some(to) = ask("Movements to", today)? else {
+        return Ok(());
+    };
+    form.flok
+        .exec(|f| regulatory::report_movements(f, Some(from), Some(to)))
+}
+
+fn adg_ranking(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    use chrono::Datelike;
+    let form = form.lock().unwrap();
+    if let Some(season) =
+        dialog::input_default("Rank lambs born in", &Date::today().day.year().to_string())
+    {
+        let season = season.trim().parse::<i32>()?;
+        form.flok.exec(|f| growth::report_ranking(f, season))?;
+    }
+    Ok(())
+}
+
+fn productivity_report(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    use chrono::Datelike;
+    let form = form.lock().unwrap();
+    if let Some(season) = dialog::input_default(
+        "Report on lambs born in",
+        &Date::today().day.year().to_string(),
+    ) {
+        let season = season.trim().parse::<i32>()?;
+        form.flok
+            .exec(|f| productivity::report_productivity(f, season))?;
+    }
+    Ok(())
+}
+
+/// Rank the flock for culling with the rules kept next to its file, or the defaults.
+fn cull_list(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let form = form.lock().unwrap();
+    let rules = match form.file.lock().unwrap().as_deref() {
+        Some(file) => cull::read_rules(&cull::rules_path(file))?,
+        None => cull::Rules::default(),
+    };
+    form.flok.exec(|f| cull::report_cull(f, &rules))
+}
+
+fn cull_rules(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let form = form.lock().unwrap();
+    let Some(file) = form.file.lock().unwrap().clone() else {
+        return Err(anyhow!(
+            "Load or save the flock first, its cull rules are kept with the file"
+        ));
+    };
+    cull_form::show_rules(cull::rules_path(&file))
+}
+
+/// Show the flock as it was on a past date, rebuilt from the journal, in a window of its own
+/// that is never saved so the flock being edited is left alone.
+fn view_as_of(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let Some(file) = form.lock().unwrap().file.lock().unwrap().clone() else {
+        return Err(anyhow!(
+            "Load or save the flock first, its history is kept with the file"
+        ));
+    };
+    if let Some(date) =
+        dialog::input_default("Show the flock as it was on", &Date::today().to_string())
+    {
+        let date = Date::parse(&date).ok_or_else(|| anyhow!("\"{date}\" is not a date"))?;
+        let flok = journal::as_of(&journal::read(&file)?, date);
+        let mut wind = Window::default()
+            .with_size(400, 600)
+            .with_label(&format!("{} as of {date} (not saved)", flok.name));
+        let past = flok_form::FlokForm::create(flok);
+        wind.resizable(&past.pack);
+        wind.end();
+        wind.show();
     }
     Ok(())
 }
@@ -191,99 +906,87 @@ fn import_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
     Ok(())
 }
 
-fn import_file(form: Arc<Mutex<flok_form::FlokForm>>, file: String) -> Result<(), anyhow::Error> {
-    let form_guard = form.lock().unwrap();
-    let mut flok_guard = form_guard.flok.lock().unwrap();
-    let mut wb = calamine::open_workbook_auto(file)?;
-    let sheet = &wb.worksheets()[0].1;
-    let headers = sheet
-        .headers()
-        .ok_or(anyhow::anyhow!("No headers found in sheet"))?;
-    let now = Local::now();
-    for row in sheet.rows() {
-        let mut animal = Animal::default();
-        for (header, cell) in headers.iter().zip(row.iter()) {
-            let header = header.to_lowercase();
-            if header.starts_with("id:") {
-                animal.id.push(cell.to_string());
-            } else {
-                match header.as_str() {
-                    "id" => animal.id.insert(0, cell.to_string()),
-                    "born" => {
-                        animal.born = cell
-                            .get_datetime()
-                            .and_then(|d| d.as_datetime())
-                            .map(|d| Local.from_local_datetime(&d).unwrap())
-                    }
-                    "sire" => animal.sire = some_string(cell.to_string()),
-                    "dam" => animal.dam = some_string(cell.to_string()),
-                    "sex" => animal.sex = Sex::from(cell.to_string()),
-                    _event => {
-                        animal.events.push(Event {
-                            name: header,
-                            value: cell.to_string(),
-                            date: now,
-                            notes: "".to_string(),
-                        });
-                    }
-                }
-            }
-        }
-        eprintln!("Animal: {:?}", animal);
-        flok_guard.animals.push(animal);
+fn export_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    if let Some(file) = file_chooser("File to export to", "*.xlsx\t*.ods\t*.csv", ".", true) {
+        let form = form.lock().unwrap();
+        form.flok
+            .exec(|flok| export::export(flok, &export::ExportOptions::all(flok), &file))?;
     }
     Ok(())
 }
 
-fn some_string(to_string: String) -> Option<String> {
-    let t = to_string.trim();
-    if t.is_empty() {
-        None
+/// Merge another copy of the flock into the one being edited.
+fn merge_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let Some(base) = file_chooser("The copy both started from", FLOK_FILES, ".", true) else {
+        return Ok(());
+    };
+    let Some(theirs) = file_chooser("The other copy", FLOK_FILES, ".", true) else {
+        return Ok(());
+    };
+    let mine = {
+        let mut form = form.lock().unwrap();
+        form.commit().map_err(validation_error)?;
+        form.flok.exec(|f| f.clone())
+    };
+    let merged = merge::merge(&load_file(&base)?, &mine, &load_file(&theirs)?);
+    if merged.conflicts.is_empty() {
+        form.lock().unwrap().set_value(&merged.flok);
     } else {
-        Some(t.to_string())
+        merge_form::show_resolver(merged, move |flok| form.lock().unwrap().set_value(&flok));
     }
+    Ok(())
 }
 
-fn report_lineage(f: &mut Flok) -> Result<()> {
-    let mut file = NamedTempFile::with_suffix(".svg")?;
-
-    let unknown = "unknown".to_string();
-    let input = "digraph {\n".to_string()
-        + "rankdir=LR;\n"
-        + &f.animals
-            .iter()
-            .map(|animal| {
-                let id = animal.id.first().unwrap_or(&unknown);
-                let mut result = String::new();
-                if let Some(dam_id) = &animal.dam {
-                    result += &format!("    {} -> {}\n", id, dam_id);
-                }
-                // if let Some(sire_id) = &animal.sire {
-                //     result += &format!("    {} -> {}\n", id, sire_id);
-                // }
-                result
-            })
-            .collect::<String>()
-        + "}\n";
-    let input = input.replace("?", "000").replace("#N/A", "000");
-    eprintln!("DOT:\n{}", input);
-
-    // Render the nodes to some rendering backend.
-    let gaph = match DotParser::new(input.as_str()).process() {
-        Ok(graph) => graph,
-        Err(e) => panic!("Unable to parse DOT: {}", e),
+fn share_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let current = form.lock().unwrap().flok.exec(|f| f.sync.clone());
+    let Some(server) = dialog::input_default(
+        "Server address",
+        &current
+            .as_ref()
+            .map(|s| s.server.clone())
+            .unwrap_or("http://127.0.0.1:8080".to_string()),
+    ) else {
+        return Ok(());
     };
-    let mut graph_builder = GraphBuilder::new();
-    graph_builder.visit_graph(&gaph);
-    let mut visual_graph = graph_builder.get();
-
-    let mut svg = SVGWriter::new();
-    visual_graph.do_it(false, false, false, &mut svg);
-
-    file.as_file().write_all(svg.finalize().as_bytes())?;
-    file.disable_cleanup(true);
+    let Some(name) = dialog::input_default(
+        "Name of the flock on the server",
+        &current.map(|s| s.name).unwrap_or_default(),
+    ) else {
+        return Ok(());
+    };
+    form.lock()
+        .unwrap()
+        .flok
+        .exec(|flok| sync::share(flok, &server, &name));
+    sync_flok(form)
+}
 
-    webbrowser::open(&("file://".to_owned() + file.path().to_str().unwrap()))?;
+fn sync_flok(form: Arc<Mutex<flok_form::FlokForm>>) -> Result<()> {
+    let report = {
+        let mut form = form.lock().unwrap();
+        form.commit().map_err(validation_error)?;
+        let report = form.flok.exec(sync::sync)?;
+        form.update();
+        report
+    };
+    let Some(unsettled) = report.conflicts else {
+        dialog::message_default(&report.describe());
+        return Ok(());
+    };
+    // sync on once the user picks which side of each conflict to keep
+    let revision = unsettled.revision;
+    merge_form::show_resolver(unsettled.merged, move |resolved| {
+        form.lock()
+            .unwrap()
+            .flok
+            .exec(|flok| sync::settle(flok, resolved, revision));
+        display_error("Unable to sync", sync_flok(form.clone()));
+    });
+    Ok(())
+}
 
+fn import_file(form: Arc<Mutex<flok_form::FlokForm>>, file: String) -> Result<()> {
+    let form = form.lock().unwrap();
+    form.flok.exec(|flok| import::import_file(flok, &file))?;
     Ok(())
 }
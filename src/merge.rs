@@ -0,0 +1,986 @@
+use crate::{
+    flok::*,
+    groups::{Group, Pasture},
+};
+
+/// One animal as it was in the common base and on each side.
+struct Slot<'a> {
+    base: Option<&'a Animal>,
+    mine: Option<&'a Animal>,
+    theirs: Option<&'a Animal>,
+}
+
+/// The parts of an animal that are merged as a whole. Events are merged one by one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// Deleted on one side and edited on the other
+    Present,
+    Id,
+    Sex,
+    Born,
+    Sire,
+    Dam,
+    Description,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::Id,
+        Field::Sex,
+        Field::Born,
+        Field::Sire,
+        Field::Dam,
+        Field::Description,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Present => "Deleted",
+            Field::Id => "Identity",
+            Field::Sex => "Sex",
+            Field::Born => "Born",
+            Field::Sire => "Sire",
+            Field::Dam => "Dam",
+            Field::Description => "Description",
+        }
+    }
+
+    /// The field as text, for choosing between the two sides.
+    pub fn show(&self, animal: Option<&Animal>) -> String {
+        let Some(a) = animal else {
+            return "(deleted)".to_string();
+        };
+        let or_none = |v: Option<String>| v.unwrap_or("(none)".to_string());
+        match self {
+            Field::Present => "(edited)".to_string(),
+            Field::Id => a.id.join(", "),
+            Field::Sex => a.sex.name().to_string(),
+            Field::Born => or_none(a.born.map(|d| d.to_string())),
+            Field::Sire => or_none(a.sire.clone()),
+            Field::Dam => or_none(a.dam.clone()),
+            Field::Description => a.description.clone(),
+        }
+    }
+
+    pub fn differs(&self, a: &Animal, b: &Animal) -> bool {
+        match self {
+            Field::Present => false,
+            Field::Id => a.id != b.id,
+            Field::Sex => a.sex != b.sex,
+            Field::Born => a.born != b.born,
+            Field::Sire => a.sire != b.sire,
+            Field::Dam => a.dam != b.dam,
+            Field::Description => a.description != b.description,
+        }
+    }
+
+    fn copy(&self, from: &Animal, to: &mut Animal) {
+        match self {
+            Field::Present => *to = from.clone(),
+            Field::Id => to.id = from.id.clone(),
+            Field::Sex => to.sex = from.sex,
+            Field::Born => to.born = from.born,
+            Field::Sire => to.sire = from.sire.clone(),
+            Field::Dam => to.dam = from.dam.clone(),
+            Field::Description => to.description = from.description.clone(),
+        }
+    }
+}
+
+/// A setting of the whole flock, as one side has it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Setting {
+    Name(String),
+    DateFormat(DateFormat),
+    WeightUnit(WeightUnit),
+    PremisesId(String),
+}
+
+impl Setting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::Name(_) => "Flock name",
+            Setting::DateFormat(_) => "Date format",
+            Setting::WeightUnit(_) => "Weight unit",
+            Setting::PremisesId(_) => "Premises ID",
+        }
+    }
+
+    pub fn show(&self) -> String {
+        match self {
+            Setting::Name(name) | Setting::PremisesId(name) => name.clone(),
+            Setting::DateFormat(format) => format.name().to_string(),
+            Setting::WeightUnit(unit) => unit.name().to_string(),
+        }
+    }
+
+    fn apply(&self, flok: &mut Flok) {
+        match self {
+            Setting::Name(name) => flok.name = name.clone(),
+            Setting::DateFormat(format) => flok.date_format = *format,
+            Setting::WeightUnit(unit) => flok.weight_unit = *unit,
+            Setting::PremisesId(id) => flok.premises_id = id.clone(),
+        }
+    }
+}
+
+/// A flock list item known by its name, as one side has it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Product(Product),
+    Contact(Contact),
+    Group(Group),
+    Pasture(Pasture),
+}
+
+/// The field of a conflict over an item deleted on one side and edited on the other.
+const DELETED: &str = "Deleted";
+
+/// Products, contacts, groups and pastures, which are matched by name and merged field by
+/// field like animals.
+trait Named: Clone + Default + PartialEq {
+    /// Like "Group", for conflict labels
+    const KIND: &'static str;
+    const FIELDS: &'static [&'static str];
+
+    fn name(&self) -> &str;
+    /// `field` as text, for comparing and choosing between the sides
+    fn show(&self, field: &str) -> String;
+    fn copy(&self, field: &str, to: &mut Self);
+    /// Merge lists within the item, like a group's members, so additions on both sides are kept.
+    fn merge_lists(&mut self, _base: &Self, _mine: &Self, _theirs: &Self) {}
+    fn list(flok: &mut Flok) -> &mut Vec<Self>;
+    fn item(self) -> Item;
+    fn from_item(item: &Item) -> Option<&Self>;
+}
+
+fn or_none<T: ToString>(value: Option<T>) -> String {
+    value.map_or("(none)".to_string(), |v| v.to_string())
+}
+
+impl Named for Product {
+    const KIND: &'static str = "Product";
+    const FIELDS: &'static [&'static str] = &[
+        "Name",
+        "Kind",
+        "Meat withdrawal",
+        "Milk withdrawal",
+        "Dose per kg",
+    ];
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn show(&self, field: &str) -> String {
+        match field {
+            "Name" => self.name.clone(),
+            "Kind" => self.kind.name().to_string(),
+            "Meat withdrawal" => self.meat_withdrawal.to_string(),
+            "Milk withdrawal" => self.milk_withdrawal.to_string(),
+            "Dose per kg" => or_none(self.dose_per_kg),
+            _ => String::new(),
+        }
+    }
+
+    fn copy(&self, field: &str, to: &mut Self) {
+        match field {
+            "Name" => to.name = self.name.clone(),
+            "Kind" => to.kind = self.kind,
+            "Meat withdrawal" => to.meat_withdrawal = self.meat_withdrawal,
+            "Milk withdrawal" => to.milk_withdrawal = self.milk_withdrawal,
+            "Dose per kg" => to.dose_per_kg = self.dose_per_kg,
+            _ => {}
+        }
+    }
+
+    fn list(flok: &mut Flok) -> &mut Vec<Self> {
+        &mut flok.products
+    }
+
+    fn item(self) -> Item {
+        Item::Product(self)
+    }
+
+    fn from_item(item: &Item) -> Option<&Self> {
+        match item {
+            Item::Product(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+impl Named for Contact {
+    const KIND: &'static str = "Contact";
+    const FIELDS: &'static [&'static str] = &["Name", "Farm", "Address", "Phone", "Premises ID"];
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn show(&self, field: &str) -> String {
+        match field {
+            "Name" => self.name.clone(),
+            "Farm" => self.farm.clone(),
+            "Address" => self.address.clone(),
+            "Phone" => self.phone.clone(),
+            "Premises ID" => self.premises_id.clone(),
+            _ => String::new(),
+        }
+    }
+
+    fn copy(&self, field: &str, to: &mut Self) {
+        match field {
+            "Name" => to.name = self.name.clone(),
+            "Farm" => to.farm = self.farm.clone(),
+            "Address" => to.address = self.address.clone(),
+            "Phone" => to.phone = self.phone.clone(),
+            "Premises ID" => to.premises_id = self.premises_id.clone(),
+            _ => {}
+        }
+    }
+
+    fn list(flok: &mut Flok) -> &mut Vec<Self> {
+        &mut flok.contacts
+    }
+
+    fn item(self) -> Item {
+        Item::Contact(self)
+    }
+
+    fn from_item(item: &Item) -> Option<&Self> {
+        match item {
+            Item::Contact(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl Named for Group {
+    const KIND: &'static str = "Group";
+    const FIELDS: &'static [&'static str] = &["Name"];
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn show(&self, field: &str) -> String {
+        match field {
+            "Name" => self.name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    fn copy(&self, field: &str, to: &mut Self) {
+        if field == "Name" {
+            to.name = self.name.clone();
+        }
+    }
+
+    fn merge_lists(&mut self, base: &Self, mine: &Self, theirs: &Self) {
+        self.members = merge_list(&base.members, &mine.members, &theirs.members);
+    }
+
+    fn list(flok: &mut Flok) -> &mut Vec<Self> {
+        &mut flok.groups
+    }
+
+    fn item(self) -> Item {
+        Item::Group(self)
+    }
+
+    fn from_item(item: &Item) -> Option<&Self> {
+        match item {
+            Item::Group(g) => Some(g),
+            _ => None,
+        }
+    }
+}
+
+impl Named for Pasture {
+    const KIND: &'static str = "Pasture";
+    const FIELDS: &'static [&'static str] = &["Name", "Notes", "Acres", "Rest days"];
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn show(&self, field: &str) -> String {
+        match field {
+            "Name" => self.name.clone(),
+            "Notes" => self.notes.clone(),
+            "Acres" => or_none(self.acres),
+            "Rest days" => or_none(self.rest_days),
+            _ => String::new(),
+        }
+    }
+
+    fn copy(&self, field: &str, to: &mut Self) {
+        match field {
+            "Name" => to.name = self.name.clone(),
+            "Notes" => to.notes = self.notes.clone(),
+            "Acres" => to.acres = self.acres,
+            "Rest days" => to.rest_days = self.rest_days,
+            _ => {}
+        }
+    }
+
+    fn list(flok: &mut Flok) -> &mut Vec<Self> {
+        &mut flok.pastures
+    }
+
+    fn item(self) -> Item {
+        Item::Pasture(self)
+    }
+
+    fn from_item(item: &Item) -> Option<&Self> {
+        match item {
+            Item::Pasture(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+impl Item {
+    fn kind(&self) -> &'static str {
+        match self {
+            Item::Product(_) => Product::KIND,
+            Item::Contact(_) => Contact::KIND,
+            Item::Group(_) => Group::KIND,
+            Item::Pasture(_) => Pasture::KIND,
+        }
+    }
+
+    fn show(&self, field: &str) -> String {
+        match self {
+            Item::Product(p) => p.show(field),
+            Item::Contact(c) => c.show(field),
+            Item::Group(g) => g.show(field),
+            Item::Pasture(p) => p.show(field),
+        }
+    }
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// Set `field` of the item called `name` as `chosen` has it, removing the item if `chosen` is
+/// `None`.
+fn resolve_named<T: Named>(flok: &mut Flok, name: &str, field: &str, chosen: Option<&Item>) {
+    let chosen = chosen.and_then(T::from_item);
+    let list = T::list(flok);
+    let position = list.iter().position(|i| same_name(i.name(), name));
+    match (position, chosen) {
+        (Some(p), Some(from)) if field == DELETED => list[p] = from.clone(),
+        (Some(p), Some(from)) => from.copy(field, &mut list[p]),
+        (Some(p), None) => {
+            list.remove(p);
+        }
+        (None, Some(from)) => list.push(from.clone()),
+        (None, None) => {}
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Conflict {
+    /// A field of an animal changed differently on both sides. The merged flock holds `mine`,
+    /// except when one side deleted the animal, where it holds the edited side so nothing is lost.
+    Animal {
+        id: Id,
+        field: Field,
+        mine: Option<Box<Animal>>,
+        theirs: Option<Box<Animal>>,
+    },
+    /// A setting changed differently on both sides. The merged flock holds `mine`.
+    Setting { mine: Setting, theirs: Setting },
+    /// A field of a product, contact, group or pasture changed differently on both sides, or
+    /// the item was deleted on one side and edited on the other. The merged flock holds `mine`,
+    /// or the edited side.
+    Item {
+        name: String,
+        field: &'static str,
+        mine: Option<Box<Item>>,
+        theirs: Option<Box<Item>>,
+    },
+}
+
+impl Conflict {
+    /// What changed on both sides, like "12 Description".
+    pub fn label(&self) -> String {
+        match self {
+            Conflict::Animal { id, field, .. } => format!("{id} {}", field.name()),
+            Conflict::Setting { mine, .. } => mine.name().to_string(),
+            Conflict::Item {
+                name,
+                field,
+                mine,
+                theirs,
+            } => {
+                let kind = mine.as_ref().or(theirs.as_ref()).map_or("", |i| i.kind());
+                format!("{kind} {name} {field}")
+            }
+        }
+    }
+
+    /// One side's value as text, for choosing between them.
+    pub fn show(&self, theirs: bool) -> String {
+        match self {
+            Conflict::Animal {
+                field,
+                mine,
+                theirs: their_animal,
+                ..
+            } => field.show(if theirs { their_animal } else { mine }.as_deref()),
+            Conflict::Setting {
+                mine,
+                theirs: their_setting,
+            } => if theirs { their_setting } else { mine }.show(),
+            Conflict::Item {
+                field,
+                mine,
+                theirs: their_item,
+                ..
+            } => match if theirs { their_item } else { mine } {
+                None => "(deleted)".to_string(),
+                Some(_) if *field == DELETED => "(edited)".to_string(),
+                Some(item) => item.show(field),
+            },
+        }
+    }
+
+    /// Apply one side's value to the merged flock.
+    pub fn resolve(&self, flok: &mut Flok, take_theirs: bool) {
+        let (field, chosen, other) = match self {
+            Conflict::Animal {
+                field,
+                mine,
+                theirs,
+                ..
+            } => {
+                if take_theirs {
+                    (field, theirs, mine)
+                } else {
+                    (field, mine, theirs)
+                }
+            }
+            Conflict::Setting { mine, theirs } => {
+                if take_theirs { theirs } else { mine }.apply(flok);
+                return;
+            }
+            Conflict::Item {
+                name,
+                field,
+                mine,
+                theirs,
+            } => {
+                let (chosen, other) = if take_theirs {
+                    (theirs, mine)
+                } else {
+                    (mine, theirs)
+                };
+                let chosen = chosen.as_deref();
+                match chosen.or(other.as_deref()) {
+                    Some(Item::Product(_)) => resolve_named::<Product>(flok, name, field, chosen),
+                    Some(Item::Contact(_)) => resolve_named::<Contact>(flok, name, field, chosen),
+                    Some(Item::Group(_)) => resolve_named::<Group>(flok, name, field, chosen),
+                    Some(Item::Pasture(_)) => resolve_named::<Pasture>(flok, name, field, chosen),
+                    None => {}
+                }
+                return;
+            }
+        };
+        let position = flok.animals.iter().position(|a| {
+            chosen
+                .iter()
+                .chain(other.iter())
+                .any(|side| shares_id(a, side))
+        });
+        match (position, chosen) {
+            (Some(p), Some(from)) => field.copy(from, &mut flok.animals[p]),
+            (Some(p), None) => {
+                flok.animals.remove(p);
+            }
+            (None, Some(from)) => flok.animals.push(from.as_ref().clone()),
+            (None, None) => {}
+        }
+    }
+}
+
+pub struct Merged {
+    pub flok: Flok,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn shares_id(a: &Animal, b: &Animal) -> bool {
+    a.id.iter().any(|id| b.id.contains(id))
+}
+
+/// Line up the animals of the three versions. Animals are the same animal if they share any id,
+/// so retagging on one side is still seen as an edit.
+fn slots<'a>(base: &'a Flok, mine: &'a Flok, theirs: &'a Flok) -> Vec<Slot<'a>> {
+    let mut slots: Vec<Slot> = base
+        .animals
+        .iter()
+        .map(|a| Slot {
+            base: Some(a),
+            mine: None,
+            theirs: None,
+        })
+        .collect();
+    for a in &mine.animals {
+        match slots
+            .iter_mut()
+            .find(|s| s.mine.is_none() && s.base.is_some_and(|b| shares_id(a, b)))
+        {
+            Some(s) => s.mine = Some(a),
+            None => slots.push(Slot {
+                base: None,
+                mine: Some(a),
+                theirs: None,
+            }),
+        }
+    }
+    for a in &theirs.animals {
+        match slots
+            .iter_mut()
+            .find(|s| s.theirs.is_none() && s.base.or(s.mine).is_some_and(|b| shares_id(a, b)))
+        {
+            Some(s) => s.theirs = Some(a),
+            None => slots.push(Slot {
+                base: None,
+                mine: None,
+                theirs: Some(a),
+            }),
+        }
+    }
+    slots
+}
+
+/// The side that changed from `base`, or `None` if both changed it differently.
+fn pick<T: PartialEq>(base: T, mine: T, theirs: T) -> Option<T> {
+    if mine == theirs || theirs == base {
+        Some(mine)
+    } else if mine == base {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+/// Keep items, like events or products, added on either side and drop those removed on either
+/// side. One edited on both sides keeps both edits.
+fn merge_list<T: PartialEq + Clone>(base: &[T], mine: &[T], theirs: &[T]) -> Vec<T> {
+    mine.iter()
+        .filter(|e| !base.contains(e) || theirs.contains(e))
+        .chain(
+            theirs
+                .iter()
+                .filter(|e| !base.contains(e) && !mine.contains(e)),
+        )
+        .cloned()
+        .collect()
+}
+
+/// Merge items matched by name: fields changed on one side are taken, the same field changed
+/// on both sides is a conflict, and an item deleted on one side and edited on the other is kept
+/// until chosen.
+fn merge_named<T: Named>(
+    base: &[T],
+    mine: &[T],
+    theirs: &[T],
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<T> {
+    let find = |list: &'_ [T], name: &str| -> Option<usize> {
+        list.iter().position(|i| same_name(i.name(), name))
+    };
+    let mut names: Vec<&str> = vec![];
+    for item in mine.iter().chain(theirs) {
+        if !names.iter().any(|n| same_name(n, item.name())) {
+            names.push(item.name());
+        }
+    }
+    let mut merged = vec![];
+    for name in names {
+        let b = find(base, name).map(|i| &base[i]);
+        let m = find(mine, name).map(|i| &mine[i]);
+        let t = find(theirs, name).map(|i| &theirs[i]);
+        match (m, t) {
+            (Some(m), Some(t)) => {
+                let base = b.cloned().unwrap_or_default();
+                let mut item = m.clone();
+                for field in T::FIELDS {
+                    let (was, mine, theirs) = (base.show(field), m.show(field), t.show(field));
+                    if mine == theirs {
+                        continue;
+                    }
+                    if mine == was {
+                        t.copy(field, &mut item);
+                    } else if theirs != was {
+                        conflicts.push(Conflict::Item {
+                            name: m.name().to_string(),
+                            field,
+                            mine: Some(Box::new(m.clone().item())),
+                            theirs: Some(Box::new(t.clone().item())),
+                        });
+                    }
+                }
+                item.merge_lists(&base, m, t);
+                merged.push(item);
+            }
+            _ => match pick(b, m, t) {
+                Some(i) => merged.extend(i.cloned()),
+                None => {
+                    let edited = m.or(t).unwrap();
+                    conflicts.push(Conflict::Item {
+                        name: edited.name().to_string(),
+                        field: DELETED,
+                        mine: m.cloned().map(|i| Box::new(i.item())),
+                        theirs: t.cloned().map(|i| Box::new(i.item())),
+                    });
+                    merged.push(edited.clone());
+                }
+            },
+        }
+    }
+    merged
+}
+
+/// Merge an animal present on both sides field by field.
+fn merge_animal(
+    base: &Animal,
+    mine: &Animal,
+    theirs: &Animal,
+    conflicts: &mut Vec<Conflict>,
+) -> Animal {
+    let mut merged = mine.clone();
+    for field in Field::ALL {
+        if !field.differs(mine, theirs) {
+            continue;
+        }
+        if !field.differs(mine, base) {
+            field.copy(theirs, &mut merged);
+        } else if field.differs(theirs, base) {
+            conflicts.push(Conflict::Animal {
+                id: mine.id.first().cloned().unwrap_or_default(),
+                field,
+                mine: Some(Box::new(mine.clone())),
+                theirs: Some(Box::new(theirs.clone())),
+            });
+        }
+    }
+    merged.events = merge_list(&base.events, &mine.events, &theirs.events);
+    merged.weights = merge_list(&base.weights, &mine.weights, &theirs.weights);
+    merged.scores = merge_list(&base.scores, &mine.scores, &theirs.scores);
+    merged
+}
+
+/// The side of a setting that changed from `base`, or a conflict keeping `mine`.
+fn merge_setting<T: PartialEq + Clone>(
+    base: &T,
+    mine: &T,
+    theirs: &T,
+    setting: fn(T) -> Setting,
+    conflicts: &mut Vec<Conflict>,
+) -> T {
+    pick(base, mine, theirs)
+        .unwrap_or_else(|| {
+            conflicts.push(Conflict::Setting {
+                mine: setting(mine.clone()),
+                theirs: setting(theirs.clone()),
+            });
+            mine
+        })
+        .clone()
+}
+
+/// Three way merge of two edited copies of `base`. Changes to different animals, fields or events
+/// combine, the same field changed on both sides is a conflict.
+pub fn merge(base: &Flok, mine: &Flok, theirs: &Flok) -> Merged {
+    let mut conflicts = vec![];
+    let mut animals = vec![];
+    for slot in slots(base, mine, theirs) {
+        match (slot.mine, slot.theirs) {
+            (Some(m), Some(t)) => animals.push(merge_animal(
+                slot.base.unwrap_or(&Animal::default()),
+                m,
+                t,
+                &mut conflicts,
+            )),
+            _ => match pick(slot.base, slot.mine, slot.theirs) {
+                Some(a) => animals.extend(a.cloned()),
+                None => {
+                    let edited = slot.mine.or(slot.theirs).unwrap();
+                    conflicts.push(Conflict::Animal {
+                        id: edited.id.first().cloned().unwrap_or_default(),
+                        field: Field::Present,
+                        mine: slot.mine.cloned().map(Box::new),
+                        theirs: slot.theirs.cloned().map(Box::new),
+                    });
+                    animals.push(edited.clone());
+                }
+            },
+        }
+    }
+    Merged {
+        flok: Flok {
+            name: merge_setting(
+                &base.name,
+                &mine.name,
+                &theirs.name,
+                Setting::Name,
+                &mut conflicts,
+            ),
+            date_format: merge_setting(
+                &base.date_format,
+                &mine.date_format,
+                &theirs.date_format,
+                Setting::DateFormat,
+                &mut conflicts,
+            ),
+            weight_unit: merge_setting(
+                &base.weight_unit,
+                &mine.weight_unit,
+                &theirs.weight_unit,
+                Setting::WeightUnit,
+                &mut conflicts,
+            ),
+            products: merge_named(
+                &base.products,
+                &mine.products,
+                &theirs.products,
+                &mut conflicts,
+            ),
+            protocols: merge_list(&base.protocols, &mine.protocols, &theirs.protocols),
+            exposures: merge_list(&base.exposures, &mine.exposures, &theirs.exposures),
+            ledger: merge_list(&base.ledger, &mine.ledger, &theirs.ledger),
+            contacts: merge_named(
+                &base.contacts,
+                &mine.contacts,
+                &theirs.contacts,
+                &mut conflicts,
+            ),
+            groups: merge_named(&base.groups, &mine.groups, &theirs.groups, &mut conflicts),
+            pastures: merge_named(
+                &base.pastures,
+                &mine.pastures,
+                &theirs.pastures,
+                &mut conflicts,
+            ),
+            feed_purchases: merge_list(
+                &base.feed_purchases,
+                &mine.feed_purchases,
+                &theirs.feed_purchases,
+            ),
+            feedings: merge_list(&base.feedings, &mine.feedings, &theirs.feedings),
+            premises_id: merge_setting(
+                &base.premises_id,
+                &mine.premises_id,
+                &theirs.premises_id,
+                Setting::PremisesId,
+                &mut conflicts,
+            ),
+            animals,
+            ..mine.clone()
+        },
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::feed::Feeding;
+
+    fn animal(id: &str, description: &str) -> Animal {
+        Animal {
+            id: vec![id.to_string()],
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn event(name: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn flok(animals: Vec<Animal>) -> Flok {
+        Flok {
+            animals,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fields_and_events() {
+        let mut ewe = animal("1", "ewe");
+        ewe.events = vec![event("shorn"), event("wormed")];
+        let base = flok(vec![ewe.clone(), animal("2", "ram")]);
+
+        let mut mine = base.clone();
+        mine.animals[0].description = "good mom".to_string();
+        mine.animals[0].events.push(event("lambed"));
+        mine.animals.push(animal("3", "lamb"));
+
+        let mut theirs = base.clone();
+        theirs.animals[0].sire = Some("9".to_string());
+        theirs.animals[0].events.remove(0);
+        theirs.animals[0].events.push(event("vaccinated"));
+        theirs.animals.remove(1);
+
+        let merged = merge(&base, &mine, &theirs);
+        assert!(merged.conflicts.is_empty());
+        let ewe = &merged.flok.animals[0];
+        assert_eq!(ewe.description, "good mom");
+        assert_eq!(ewe.sire, Some("9".to_string()));
+        let events: Vec<&str> = ewe.events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(events, vec!["wormed", "lambed", "vaccinated"]);
+        let ids: Vec<&str> = merged
+            .flok
+            .animals
+            .iter()
+            .map(|a| a.id[0].as_str())
+            .collect();
+        assert_eq!(ids, vec!["1", "3"]);
+    }
+
+    #[test]
+    fn conflicts() {
+        let base = flok(vec![animal("1", "ewe"), animal("2", "ram")]);
+        let mut mine = base.clone();
+        mine.animals[0].description = "twins".to_string();
+        mine.animals.remove(1);
+        let mut theirs = base.clone();
+        theirs.animals[0].description = "singles".to_string();
+        theirs.animals[1].description = "sold?".to_string();
+
+        let mut merged = merge(&base, &mine, &theirs);
+        let labels: Vec<String> = merged.conflicts.iter().map(|c| c.label()).collect();
+        assert_eq!(labels, vec!["1 Description", "2 Deleted"]);
+        // deleting loses nothing until chosen
+        assert_eq!(merged.flok.animals.len(), 2);
+        assert_eq!(merged.flok.animals[0].description, "twins");
+
+        merged.conflicts[0].resolve(&mut merged.flok, true);
+        merged.conflicts[1].resolve(&mut merged.flok, false);
+        assert_eq!(merged.flok.animals, vec![animal("1", "singles")]);
+
+        // settings changed on both sides keep mine until chosen
+        let mut mine = base.clone();
+        mine.name = "Home flock".to_string();
+        let mut theirs = base.clone();
+        theirs.name = "Hill flock".to_string();
+        let mut merged = merge(&base, &mine, &theirs);
+        assert_eq!(merged.flok.name, "Home flock");
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].show(true), "Hill flock");
+        merged.conflicts[0].resolve(&mut merged.flok, true);
+        assert_eq!(merged.flok.name, "Hill flock");
+    }
+
+    #[test]
+    fn flock_lists() {
+        let named = |name: &str| Product {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let base = Flok {
+            products: vec![named("Cydectin")],
+            ..Default::default()
+        };
+        let mut mine = base.clone();
+        mine.products.push(named("CDT"));
+        mine.ledger.push(LedgerEntry {
+            category: Category::Hay,
+            amount: 300.0,
+            ..Default::default()
+        });
+        mine.feedings.push(Feeding {
+            quantity: 2.0,
+            ..Default::default()
+        });
+        let mut theirs = base.clone();
+        theirs.products.push(named("Valbazen"));
+        theirs.feedings.push(Feeding {
+            quantity: 3.0,
+            ..Default::default()
+        });
+        theirs.ledger.push(LedgerEntry {
+            category: Category::Vet,
+            amount: 50.0,
+            ..Default::default()
+        });
+
+        let merged = merge(&base, &mine, &theirs);
+        assert!(merged.conflicts.is_empty());
+        let products: Vec<&str> = merged
+            .flok
+            .products
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(products, vec!["Cydectin", "CDT", "Valbazen"]);
+        // entries added on two devices are both kept
+        assert_eq!(merged.flok.ledger.len(), 2);
+        assert_eq!(merged.flok.feedings.len(), 2);
+    }
+
+    #[test]
+    fn named_items() {
+        let base = Flok {
+            groups: vec![Group {
+                name: "Ewes".to_string(),
+                members: vec!["1".to_string()],
+            }],
+            pastures: vec![Pasture {
+                name: "North".to_string(),
+                acres: Some(5.0),
+                ..Default::default()
+            }],
+            contacts: vec![Contact {
+                name: "Smith".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut mine = base.clone();
+        mine.groups[0].members.push("2".to_string());
+        mine.pastures[0].acres = Some(6.0);
+        mine.pastures[0].notes = "wet in spring".to_string();
+        mine.contacts.clear();
+        let mut theirs = base.clone();
+        theirs.groups[0].members.push("3".to_string());
+        theirs.pastures[0].acres = Some(4.5);
+        theirs.pastures[0].rest_days = Some(40);
+        theirs.contacts[0].phone = "555-1234".to_string();
+
+        let mut merged = merge(&base, &mine, &theirs);
+        // one group with the animals added on both sides
+        assert_eq!(
+            merged.flok.groups,
+            vec![Group {
+                name: "Ewes".to_string(),
+                members: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+            }]
+        );
+        let labels: Vec<String> = merged.conflicts.iter().map(|c| c.label()).collect();
+        assert_eq!(labels, vec!["Contact Smith Deleted", "Pasture North Acres"]);
+        // fields changed on one side are taken, and the deleted contact is kept until chosen
+        let north = &merged.flok.pastures[0];
+        assert_eq!(
+            (north.acres, north.notes.as_str(), north.rest_days),
+            (Some(6.0), "wet in spring", Some(40))
+        );
+        assert_eq!(merged.flok.contacts.len(), 1);
+        assert_eq!(merged.conflicts[1].show(true), "4.5");
+
+        merged.conflicts[0].resolve(&mut merged.flok, false);
+        merged.conflicts[1].resolve(&mut merged.flok, true);
+        assert!(merged.flok.contacts.is_empty());
+        assert_eq!(merged.flok.pastures[0].acres, Some(4.5));
+        assert_eq!(merged.flok.pastures[0].rest_days, Some(40));
+    }
+}
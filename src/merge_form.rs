@@ -0,0 +1,99 @@
+use anyhow::Result;
+use fltk::{
+    app,
+    button::{Button, RadioRoundButton},
+    enums::Align,
+    frame::Frame,
+    group::{Flex, Pack, Scroll},
+    prelude::{ButtonExt, GroupExt, WidgetBase, WidgetExt, WindowExt},
+    window::Window,
+};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{flok::*, merge::Merged};
+
+const ROW: i32 = 25;
+
+/// Ask which side wins for each conflict. `done` gets the flock once every choice is applied.
+pub fn show_resolver(merged: Merged, mut done: impl FnMut(Flok) + 'static) -> Window {
+    let Merged { flok, conflicts } = merged;
+    let mut wind = Window::default()
+        .with_size(700, (conflicts.len() as i32 + 2) * ROW + 10)
+        .with_label("Resolve merge conflicts");
+    let mut page = Flex::default_fill().column();
+
+    let mut header = Flex::default().row();
+    Frame::default().with_label("Changed");
+    Frame::default().with_label("Mine");
+    Frame::default().with_label("Theirs");
+    header.end();
+    page.fixed(&header, ROW);
+
+    let scroll = Scroll::default();
+    let mut rows = Pack::default_fill();
+    rows.set_spacing(2);
+    let mut choices = vec![];
+    for conflict in &conflicts {
+        let row = Flex::default().row().with_size(0, ROW);
+        Frame::default()
+            .with_label(&conflict.label())
+            .with_align(Align::Left | Align::Inside);
+        for side in ["mine", "theirs"] {
+            let value = conflict.show(side == "theirs");
+            let mut b = RadioRoundButton::default().with_label(&value);
+            b.set_tooltip(&format!("Keep {side}: {value}"));
+            b.set_align(Align::Left | Align::Inside | Align::Clip);
+            if side == "mine" {
+                b.set_value(true);
+            }
+            if side == "theirs" {
+                choices.push(b);
+            }
+        }
+        row.end();
+    }
+    rows.end();
+    scroll.end();
+
+    let mut buttons = Flex::default().row();
+    buttons.resizable(&Frame::default());
+    {
+        let mut cancel = Button::default().with_label("Cancel");
+        let mut wind = wind.clone();
+        cancel.set_callback(move |_| wind.hide());
+        buttons.fixed(&cancel, 60);
+    }
+    {
+        let mut save = Button::default().with_label("Merge");
+        let mut wind = wind.clone();
+        save.set_callback(move |_| {
+            let mut flok = flok.clone();
+            for (conflict, theirs) in conflicts.iter().zip(&choices) {
+                conflict.resolve(&mut flok, theirs.value());
+            }
+            done(flok);
+            wind.hide();
+        });
+        buttons.fixed(&save, 60);
+    }
+    buttons.end();
+    page.fixed(&buttons, ROW);
+    page.end();
+
+    wind.make_resizable(true);
+    wind.end();
+    wind.show();
+    wind
+}
+
+/// Run the resolver on its own, `None` if it was cancelled.
+pub fn resolve(merged: Merged) -> Result<Option<Flok>> {
+    let app = app::App::default();
+    let resolved = Rc::new(RefCell::new(None));
+    {
+        let resolved = resolved.clone();
+        show_resolver(merged, move |flok| *resolved.borrow_mut() = Some(flok));
+    }
+    app.run()?;
+    Ok(resolved.take())
+}
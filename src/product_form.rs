@@ -0,0 +1,131 @@
+use enum_ordinalize::Ordinalize;
+use fltk::{
+    input::{FloatInput, Input, IntInput},
+    menu::Choice,
+    prelude::{InputExt, MenuExt},
+    widget::Widget,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    flok::*,
+    form::*,
+    list_editor::{show_list_editor, ItemForm},
+};
+
+/// The fields of one product in the catalogue.
+#[derive(Clone)]
+pub struct ProductForm {
+    pub name: Input,
+    pub kind: Choice,
+    pub meat_withdrawal: IntInput,
+    pub milk_withdrawal: IntInput,
+    pub dose_per_kg: FloatInput,
+}
+
+impl ItemForm<Product> for ProductForm {
+    fn create() -> anyhow::Result<(Self, Widget)> {
+        let form = Self {
+            name: Input::default(),
+            kind: {
+                let mut kind = Choice::default();
+                for k in ProductKind::VARIANTS {
+                    kind.add_choice(k.name());
+                }
+                kind
+            },
+            meat_withdrawal: IntInput::default(),
+            milk_withdrawal: IntInput::default(),
+            dose_per_kg: FloatInput::default(),
+        };
+        let ui = create_form(vec![
+            ("Name", &form.name),
+            ("Kind", &form.kind),
+            ("Meat withdrawal (days)", &form.meat_withdrawal),
+            ("Milk withdrawal (days)", &form.milk_withdrawal),
+            ("Dose (ml per kg)", &form.dose_per_kg),
+        ])?;
+        Ok((form, ui))
+    }
+
+    fn set_item(&mut self, p: &Product) {
+        self.name.set_value(&p.name);
+        self.kind.set_value(p.kind.ordinal() as i32);
+        self.meat_withdrawal
+            .set_value(&p.meat_withdrawal.to_string());
+        self.milk_withdrawal
+            .set_value(&p.milk_withdrawal.to_string());
+        self.dose_per_kg
+            .set_value(&p.dose_per_kg.map(|d| d.to_string()).unwrap_or_default());
+    }
+
+    fn item(&mut self, flok: &Flok, index: Option<usize>) -> Result<Product, Vec<FieldError>> {
+        let mut errors = vec![];
+        let name = self.name.value().trim().to_string();
+        if name.is_empty() {
+            errors.push(FieldError::new("Name", "Name is empty"));
+        } else if flok
+            .products
+            .iter()
+            .enumerate()
+            .any(|(i, p)| Some(i) != index && p.name.trim().eq_ignore_ascii_case(&name))
+        {
+            errors.push(FieldError::new(
+                "Name",
+                format!("There is already a {name}"),
+            ));
+        }
+        let mut days = |input: &IntInput, field: &'static str| {
+            let text = input.value();
+            if text.trim().is_empty() {
+                return 0;
+            }
+            text.trim().parse::<u32>().unwrap_or_else(|_| {
+                errors.push(FieldError::new(
+                    field,
+                    format!("\"{text}\" is not a number of days"),
+                ));
+                0
+            })
+        };
+        let meat_withdrawal = days(&self.meat_withdrawal, "Meat withdrawal");
+        let milk_withdrawal = days(&self.milk_withdrawal, "Milk withdrawal");
+        let dose = self.dose_per_kg.value();
+        let dose_per_kg = if dose.trim().is_empty() {
+            None
+        } else {
+            dose.trim().parse::<f64>().map(Some).unwrap_or_else(|_| {
+                errors.push(FieldError::new(
+                    "Dose",
+                    format!("\"{dose}\" is not a number"),
+                ));
+                None
+            })
+        };
+
+        highlight(&mut self.name, "Name", &errors);
+        highlight(&mut self.meat_withdrawal, "Meat withdrawal", &errors);
+        highlight(&mut self.milk_withdrawal, "Milk withdrawal", &errors);
+        highlight(&mut self.dose_per_kg, "Dose", &errors);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Product {
+            name,
+            kind: ProductKind::from_ordinal(self.kind.value() as i8).unwrap_or_default(),
+            meat_withdrawal,
+            milk_withdrawal,
+            dose_per_kg,
+        })
+    }
+}
+
+/// Edit the flock's catalogue of wormers, vaccines and other products.
+pub fn show_products(flok: Arc<Mutex<Flok>>) -> anyhow::Result<()> {
+    show_list_editor::<Product, ProductForm>(
+        "Products",
+        flok,
+        |f| &mut f.products,
+        |p| format!("{} ({})", p.name, p.kind.name()),
+    )
+}
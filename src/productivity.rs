@@ -0,0 +1,374 @@
+use anyhow::Result;
+use chrono::{Datelike, Days};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    breeding::{self, GESTATION_DAYS},
+    flok::*,
+    growth,
+    report::{escape, open_in_browser},
+};
+
+/// Lambs are weaned at this age, and their weaning weight is the adjusted weight at it.
+pub const WEANING_DAYS: i64 = 90;
+
+/// A ewe whose lambs wean this much lighter than the flock average is flagged.
+const LIGHT_WEANING: f64 = 0.9;
+
+/// A lamb is weaned if it has a weaning event, or lived to weaning age by `date`.
+fn weaned(lamb: &Animal, date: Date) -> bool {
+    let weaning_event = lamb.events.iter().any(|e| {
+        let name = e.name.trim().to_lowercase();
+        name == "weaned" || name == "weaning"
+    });
+    let weaning_age = lamb
+        .born
+        .map(|b| b.day + Days::new(WEANING_DAYS as u64))
+        .filter(|w| *w <= date.day);
+    weaning_event || weaning_age.is_some_and(|w| lamb.died().is_none_or(|d| d.day >= w))
+}
+
+fn lambs_of<'a>(flok: &'a Flok, ewe: &'a Animal) -> impl Iterator<Item = &'a Animal> {
+    flok.animals
+        .iter()
+        .filter(|a| a.dam.as_ref().is_some_and(|d| ewe.id.contains(d)))
+}
+
+/// The cause of a lamb's death, from its death event.
+fn cause(lamb: &Animal) -> String {
+    let death = lamb.death();
+    let cause = death
+        .map(|e| e.value.trim())
+        .filter(|v| !v.is_empty())
+        .or_else(|| death.map(|e| e.notes.trim()).filter(|n| !n.is_empty()));
+    match cause {
+        Some(cause) => cause.to_lowercase(),
+        None => "unknown".to_string(),
+    }
+}
+
+/// How one lambing season went.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Season {
+    pub season: i32,
+    pub ewes_exposed: usize,
+    pub ewes_lambed: usize,
+    pub lambs_born: usize,
+    pub lambs_died: usize,
+    pub lambs_weaned: usize,
+    /// Lambs born per 100 ewes exposed
+    pub lambing_percent: Option<f64>,
+    /// Lambs weaned per 100 ewes exposed
+    pub weaning_percent: Option<f64>,
+    /// Lambs that died, by cause
+    pub deaths: BTreeMap<String, usize>,
+}
+
+/// Lambs born in `season` and the ewes bred for them, with lambs counted as weaned by `date`.
+pub fn season(flok: &Flok, season: i32, date: Date) -> Season {
+    let lambs: Vec<&Animal> = flok
+        .animals
+        .iter()
+        .filter(|a| a.dam.is_some() && a.born.is_some_and(|b| b.day.year() == season))
+        .collect();
+    let lambed: BTreeSet<&Id> = lambs.iter().filter_map(|l| l.dam.as_ref()).collect();
+    // ewes bred to lamb this season, and any that lambed without a breeding record
+    let exposed = flok
+        .animals
+        .iter()
+        .filter(|a| a.sex == Sex::Female)
+        .filter(|a| {
+            a.id.iter().any(|id| lambed.contains(id))
+                || breeding::bred(flok, a)
+                    .iter()
+                    .any(|b| (b.day + Days::new(GESTATION_DAYS)).year() == season)
+        })
+        .count();
+    let mut deaths = BTreeMap::new();
+    for lamb in lambs.iter().filter(|l| l.died().is_some()) {
+        *deaths.entry(cause(lamb)).or_insert(0) += 1;
+    }
+    let lambs_weaned = lambs.iter().filter(|l| weaned(l, date)).count();
+    let percent = |n: usize| (exposed > 0).then(|| 100.0 * n as f64 / exposed as f64);
+    Season {
+        season,
+        ewes_exposed: exposed,
+        ewes_lambed: lambed.len(),
+        lambs_born: lambs.len(),
+        lambs_died: deaths.values().sum(),
+        lambs_weaned,
+        lambing_percent: percent(lambs.len()),
+        weaning_percent: percent(lambs_weaned),
+        deaths,
+    }
+}
+
+impl Season {
+    /// The season as (measure, value) rows.
+    pub fn rows(&self) -> Vec<(String, String)> {
+        let percent = |p: Option<f64>| p.map(|p| format!("{p:.0}%")).unwrap_or_default();
+        let mut rows = vec![
+            ("Ewes exposed".to_string(), self.ewes_exposed.to_string()),
+            ("Ewes lambed".to_string(), self.ewes_lambed.to_string()),
+            ("Lambs born".to_string(), self.lambs_born.to_string()),
+            ("Lambs died".to_string(), self.lambs_died.to_string()),
+            ("Lambs weaned".to_string(), self.lambs_weaned.to_string()),
+            ("Lambing".to_string(), percent(self.lambing_percent)),
+            ("Weaning".to_string(), percent(self.weaning_percent)),
+        ];
+        rows.extend(
+            self.deaths
+                .iter()
+                .map(|(cause, n)| (format!("Died: {cause}"), n.to_string())),
+        );
+        rows
+    }
+}
+
+/// What a ewe has produced over her life.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Ewe {
+    pub id: Id,
+    pub born: Option<Date>,
+    /// Seasons she lambed in
+    pub lambings: usize,
+    pub lambs: usize,
+    pub weaned: usize,
+    pub lambs_per_lambing: Option<f64>,
+    /// Lambs born each year
+    pub by_year: BTreeMap<i32, usize>,
+    /// Total adjusted weaning weight of her lambs, in the flock's unit
+    pub weight_weaned: f64,
+    pub description: String,
+    pub flags: Vec<String>,
+}
+
+/// Every ewe that has lambed, most weight weaned first.
+pub fn ewes(flok: &Flok, date: Date) -> Vec<Ewe> {
+    let unit = flok.weight_unit;
+    let weaning_weight = |lamb: &Animal| growth::adjusted(lamb, WEANING_DAYS, unit);
+    let weights: Vec<f64> = flok
+        .animals
+        .iter()
+        .filter(|a| a.dam.is_some())
+        .filter_map(weaning_weight)
+        .collect();
+    let average = (!weights.is_empty()).then(|| weights.iter().sum::<f64>() / weights.len() as f64);
+    let mut ewes: Vec<Ewe> = flok
+        .animals
+        .iter()
+        .filter(|a| a.sex == Sex::Female)
+        .filter_map(|ewe| {
+            let lambs: Vec<&Animal> = lambs_of(flok, ewe).collect();
+            if lambs.is_empty() {
+                return None;
+            }
+            let mut by_year = BTreeMap::new();
+            for year in lambs.iter().filter_map(|l| l.born).map(|b| b.day.year()) {
+                *by_year.entry(year).or_insert(0) += 1;
+            }
+            let weaned = lambs.iter().filter(|l| weaned(l, date)).count();
+            let lambs_per_lambing = (!by_year.is_empty())
+                .then(|| by_year.values().sum::<usize>() as f64 / by_year.len() as f64);
+            let lamb_weights: Vec<f64> = lambs.iter().filter_map(|l| weaning_weight(l)).collect();
+            let mut flags = vec![];
+            if by_year.len() > 1 && by_year.values().all(|n| *n == 1) {
+                flags.push("singles".to_string());
+            }
+            let lost = lambs.iter().filter(|l| l.died().is_some()).count();
+            if lost > 0 {
+                flags.push(format!("lost {lost}"));
+            }
+            if let Some(average) = average.filter(|_| !lamb_weights.is_empty()) {
+                let hers = lamb_weights.iter().sum::<f64>() / lamb_weights.len() as f64;
+                if hers < average * LIGHT_WEANING {
+                    flags.push("light lambs".to_string());
+                }
+            }
+            Some(Ewe {
+                id: ewe.id.first().cloned().unwrap_or_default(),
+                born: ewe.born,
+                lambings: by_year.len(),
+                lambs: lambs.len(),
+                weaned,
+                lambs_per_lambing,
+                by_year,
+                weight_weaned: lamb_weights.iter().sum(),
+                description: ewe.description.clone(),
+                flags,
+            })
+        })
+        .collect();
+    ewes.sort_by(|a, b| {
+        b.weight_weaned
+            .total_cmp(&a.weight_weaned)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    ewes
+}
+
+pub fn productivity_html(flok: &Flok, season_year: i32, date: Date) -> String {
+    let unit = flok.weight_unit.name();
+    let s = season(flok, season_year, date);
+    let summary: String = s
+        .rows()
+        .iter()
+        .map(|(measure, value)| {
+            format!(
+                "<tr><td>{}</td><td>{}</td></tr>\n",
+                escape(measure),
+                escape(value)
+            )
+        })
+        .collect();
+    let ewes: String = ewes(flok, date)
+        .iter()
+        .map(|e| {
+            let by_year: Vec<String> = e.by_year.iter().map(|(y, n)| format!("{y}: {n}")).collect();
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 <td>{}</td><td>{:.1}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&e.id),
+                e.born.map(|b| b.to_string()).unwrap_or_default(),
+                e.lambings,
+                e.lambs,
+                e.weaned,
+                e.lambs_per_lambing
+                    .map(|n| format!("{n:.2}"))
+                    .unwrap_or_default(),
+                by_year.join(", "),
+                e.weight_weaned,
+                escape(&e.flags.join(", ")),
+                escape(&e.description)
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Productivity {season_year}</title></head><body>\n\
+         <h1>Lambing season {season_year}</h1>\n\
+         <table border=\"1\">\n<tr><th>Measure</th><th>Value</th></tr>\n{summary}</table>\n\
+         <h2>Ewe lifetime production</h2>\n\
+         <p>Lambs count as weaned at {WEANING_DAYS} days. Weight weaned is the total of their \
+         adjusted {WEANING_DAYS} day weights.</p>\n\
+         <table border=\"1\">\n<tr><th>Ewe</th><th>Born</th><th>Lambings</th><th>Lambs</th>\
+         <th>Weaned</th><th>Lambs per lambing</th><th>Lambs by year</th>\
+         <th>Weight weaned ({unit})</th><th>Flags</th><th>Description</th></tr>\n{ewes}</table>\n\
+         </body></html>\n"
+    )
+}
+
+pub fn report_productivity(flok: &Flok, season: i32) -> Result<()> {
+    open_in_browser(&productivity_html(flok, season, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(name: &str, value: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            value: value.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn lamb(id: &str, dam: &str, born: &str, events: Vec<Event>, weights: &[f64]) -> Animal {
+        let born = Date::parse(born).unwrap();
+        Animal {
+            id: vec![id.to_string()],
+            dam: Some(dam.to_string()),
+            born: Some(born),
+            events,
+            weights: weights
+                .iter()
+                .enumerate()
+                .map(|(i, w)| Weight {
+                    date: (born.day + Days::new(90 * i as u64)).into(),
+                    value: *w,
+                    unit: WeightUnit::Lb,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn season_and_lifetime() {
+        let ewe = |id: &str, events| Animal {
+            id: vec![id.to_string()],
+            sex: Sex::Female,
+            events,
+            ..Default::default()
+        };
+        let flok = Flok {
+            animals: vec![
+                ewe("twinner", vec![]),
+                ewe("singler", vec![]),
+                ewe("open", vec![event("marked", "ram", "2023-10-01")]),
+                lamb("t1", "twinner", "2024-03-01", vec![], &[10.0, 60.0]),
+                lamb(
+                    "t2",
+                    "twinner",
+                    "2024-03-01",
+                    vec![event("died", "Scours", "2024-03-10")],
+                    &[],
+                ),
+                lamb("t3", "twinner", "2023-03-01", vec![], &[10.0, 70.0]),
+                lamb("s1", "singler", "2024-03-05", vec![], &[8.0, 40.0]),
+                lamb("s2", "singler", "2023-03-05", vec![], &[8.0, 42.0]),
+                lamb(
+                    "s3",
+                    "singler",
+                    "2024-12-01",
+                    vec![event("died", "", "2024-12-01")],
+                    &[],
+                ),
+            ],
+            ..Default::default()
+        };
+        let date = Date::parse("2025-01-01").unwrap();
+        let s = season(&flok, 2024, date);
+        assert_eq!(
+            (
+                s.ewes_exposed,
+                s.ewes_lambed,
+                s.lambs_born,
+                s.lambs_died,
+                s.lambs_weaned
+            ),
+            (3, 2, 4, 2, 2)
+        );
+        assert_eq!(s.lambing_percent.map(|p| p.round()), Some(133.0));
+        assert_eq!(
+            s.deaths.into_iter().collect::<Vec<_>>(),
+            vec![("scours".to_string(), 1), ("unknown".to_string(), 1)]
+        );
+
+        let ewes = ewes(&flok, date);
+        assert_eq!(
+            ewes.iter()
+                .map(|e| (
+                    e.id.as_str(),
+                    e.lambs,
+                    e.weaned,
+                    e.weight_weaned,
+                    e.flags.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("twinner", 3, 2, 130.0, vec!["lost 1".to_string()]),
+                (
+                    "singler",
+                    3,
+                    2,
+                    82.0,
+                    vec!["lost 1".to_string(), "light lambs".to_string()]
+                ),
+            ]
+        );
+        assert!(productivity_html(&flok, 2024, date).contains("Lambs weaned"));
+    }
+}
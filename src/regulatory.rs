@@ -0,0 +1,317 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    flok::*,
+    report::{escape, open_in_browser},
+    validate::FieldError,
+};
+
+/// Events recording an official ID, like a scrapie tag, the tag in `value`.
+pub const OFFICIAL_ID_EVENTS: [&str; 2] = ["official id", "scrapie tag"];
+/// Events moving an animal onto the farm, the source in `value` or the contact.
+pub const MOVE_IN_EVENTS: [&str; 2] = ["moved in", "move in"];
+/// Events moving an animal off the farm, the destination in `value` or the contact.
+pub const MOVE_OUT_EVENTS: [&str; 2] = ["moved out", "move out"];
+
+fn named(e: &Event, names: &[&str]) -> bool {
+    names.iter().any(|n| e.name.trim().eq_ignore_ascii_case(n))
+}
+
+/// An 840 RFID tag: 15 digits starting with the US country code.
+pub fn is_official(tag: &str) -> bool {
+    let tag: String = tag.chars().filter(|c| !c.is_whitespace()).collect();
+    tag.len() == 15 && tag.starts_with("840") && tag.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The animal's official ID: the latest one recorded by an event, or else an 840 tag.
+pub fn official_id(animal: &Animal) -> Option<String> {
+    animal
+        .events
+        .iter()
+        .filter(|e| named(e, &OFFICIAL_ID_EVENTS) && !e.value.trim().is_empty())
+        .max_by_key(|e| e.date)
+        .map(|e| e.value.trim().to_string())
+        .or_else(|| animal.id.iter().find(|t| is_official(t)).cloned())
+}
+
+/// Record `tag` as the animal's official ID on `date`, adding it to its tags.
+pub fn record_official_id(animal: &mut Animal, tag: &str, date: Date) {
+    let tag = tag.trim().to_string();
+    if !animal.id.contains(&tag) {
+        animal.id.push(tag.clone());
+    }
+    animal.events.push(Event {
+        name: "official id".to_string(),
+        value: tag,
+        date,
+        ..Default::default()
+    });
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+    Died,
+}
+
+/// An animal arriving on or leaving the farm.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Movement {
+    pub date: Date,
+    pub direction: Direction,
+    pub id: Id,
+    pub official_id: Option<String>,
+    /// The event recording it, like "purchase price" or "moved out"
+    pub event: String,
+    /// Where it came from or went to
+    pub party: String,
+    pub premises_id: String,
+}
+
+impl Movement {
+    /// Animals must carry an official ID when they leave alive.
+    pub fn missing_id(&self) -> bool {
+        self.direction == Direction::Out && self.official_id.is_none()
+    }
+}
+
+/// Every arrival and departure, by date, between `from` and `to` if given. An animal's purchase
+/// or sale recorded over several events, like "purchase price" and "purchase date", is one
+/// movement.
+pub fn movements(flok: &Flok, from: Option<Date>, to: Option<Date>) -> Vec<Movement> {
+    let mut found: Vec<Movement> = vec![];
+    for a in &flok.animals {
+        let id = a.id.first().cloned().unwrap_or_default();
+        let official_id = official_id(a);
+        let mut bought: Option<Movement> = None;
+        let mut sold: Option<Movement> = None;
+        for e in &a.events {
+            let contact = e.contact.as_deref().and_then(|c| flok.contact(c));
+            let movement = |direction, party: String| Movement {
+                date: e.date,
+                direction,
+                id: id.clone(),
+                official_id: official_id.clone(),
+                event: e.name.clone(),
+                party: contact.map(|c| c.name.clone()).unwrap_or(party),
+                premises_id: contact.map(|c| c.premises_id.clone()).unwrap_or_default(),
+            };
+            if named(e, &MOVE_IN_EVENTS) {
+                found.push(movement(Direction::In, e.value.trim().to_string()));
+            } else if named(e, &MOVE_OUT_EVENTS) {
+                found.push(movement(Direction::Out, e.value.trim().to_string()));
+            } else if e.is_sale() || e.is_purchase() {
+                let (deal, direction) = if e.is_sale() {
+                    (&mut sold, Direction::Out)
+                } else {
+                    (&mut bought, Direction::In)
+                };
+                // the sheet keeps the date of a purchase as the value of a "purchase date" event
+                let date = Date::parse(&e.value)
+                    .filter(|_| e.name.to_lowercase().contains("date"))
+                    .unwrap_or(e.date);
+                let m = deal.get_or_insert_with(|| Movement {
+                    date,
+                    ..movement(direction, String::new())
+                });
+                m.date = m.date.min(date);
+                if m.party.is_empty() {
+                    if let Some(c) = contact {
+                        m.party = c.name.clone();
+                        m.premises_id = c.premises_id.clone();
+                    }
+                }
+            }
+        }
+        found.extend(bought);
+        found.extend(sold);
+        if let Some(death) = a.death() {
+            found.push(Movement {
+                date: death.date,
+                direction: Direction::Died,
+                id: id.clone(),
+                official_id: official_id.clone(),
+                event: death.name.clone(),
+                party: String::new(),
+                premises_id: String::new(),
+            });
+        }
+    }
+    found.retain(|m| {
+        from.is_none_or(|f| m.date.day >= f.day) && to.is_none_or(|t| m.date.day <= t.day)
+    });
+    found.sort_by_key(|m| m.date);
+    found
+}
+
+/// Animals that left the farm alive without an official ID.
+pub fn missing_ids(flok: &Flok) -> Vec<(Id, FieldError)> {
+    movements(flok, None, None)
+        .into_iter()
+        .filter(|m| m.missing_id())
+        .map(|m| {
+            (
+                m.id,
+                FieldError::new(
+                    "Identity",
+                    format!("Moved off the farm on {} without an official ID", m.date),
+                ),
+            )
+        })
+        .collect()
+}
+
+/// The acquisition and disposal records an inspector asks for, between `from` and `to`.
+pub fn movements_html(flok: &Flok, from: Option<Date>, to: Option<Date>) -> String {
+    let movements = movements(flok, from, to);
+    let rows = |directions: &[Direction]| -> String {
+        movements
+            .iter()
+            .filter(|m| directions.contains(&m.direction))
+            .map(|m| {
+                let official_id = match &m.official_id {
+                    Some(id) => escape(id),
+                    None if m.missing_id() => "<b style=\"color:red\">MISSING</b>".to_string(),
+                    None => String::new(),
+                };
+                format!(
+                    "<tr><td>{}</td><td>{official_id}</td><td>{}</td><td>{}</td><td>{}</td>\
+                     <td>{}</td></tr>\n",
+                    m.date,
+                    escape(&m.id),
+                    escape(&m.event),
+                    escape(&m.party),
+                    escape(&m.premises_id)
+                )
+            })
+            .collect()
+    };
+    let headers = |party: &str| {
+        format!(
+            "<tr><th>Date</th><th>Official ID</th><th>Animal</th><th>Event</th><th>{party}</th>\
+             <th>Premises ID</th></tr>\n"
+        )
+    };
+    let period = match (from, to) {
+        (Some(f), Some(t)) => format!("{f} to {t}"),
+        (Some(f), None) => format!("since {f}"),
+        (None, Some(t)) => format!("up to {t}"),
+        (None, None) => "all dates".to_string(),
+    };
+    let missing = movements.iter().filter(|m| m.missing_id()).count();
+    let warning = if missing > 0 {
+        format!("<p><b>{missing} animals left the farm without an official ID.</b></p>\n")
+    } else {
+        String::new()
+    };
+    format!(
+        "<html><head><title>Movement records</title></head><body>\n<h1>Movement records</h1>\n\
+         <p>{}, premises ID {}, {period}</p>\n{warning}\
+         <h2>Acquired</h2>\n<table border=\"1\">\n{}{}</table>\n\
+         <h2>Disposed of</h2>\n<table border=\"1\">\n{}{}</table>\n</body></html>\n",
+        escape(&flok.name),
+        escape(if flok.premises_id.is_empty() {
+            "not set"
+        } else {
+            &flok.premises_id
+        }),
+        headers("Source"),
+        rows(&[Direction::In]),
+        headers("Destination"),
+        rows(&[Direction::Out, Direction::Died])
+    )
+}
+
+pub fn report_movements(flok: &Flok, from: Option<Date>, to: Option<Date>) -> Result<()> {
+    open_in_browser(&movements_html(flok, from, to), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(name: &str, value: &str, date: &str, contact: Option<&str>) -> Event {
+        Event {
+            name: name.to_string(),
+            value: value.to_string(),
+            date: Date::parse(date).unwrap(),
+            contact: contact.map(|c| c.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn movement_records() {
+        let animal = |id: &str, events| Animal {
+            id: vec![id.to_string()],
+            events,
+            ..Default::default()
+        };
+        let mut flok = Flok {
+            contacts: vec![Contact {
+                name: "Joe Baldwin".to_string(),
+                premises_id: "00ABC12".to_string(),
+                ..Default::default()
+            }],
+            animals: vec![
+                animal(
+                    "1",
+                    vec![
+                        event("purchase price", "$250", "2025-01-01", Some("Joe Baldwin")),
+                        event("purchase date", "2024-05-01", "2025-01-01", None),
+                        event("moved out", "county fair", "2024-08-01", None),
+                        event("moved in", "county fair", "2024-08-04", None),
+                    ],
+                ),
+                animal(
+                    "2",
+                    vec![
+                        event("sale $", "300", "2024-10-01", Some("Joe Baldwin")),
+                        event("sold", "", "2024-10-01", None),
+                    ],
+                ),
+                animal("3", vec![event("died", "", "2024-12-01", None)]),
+            ],
+            ..Default::default()
+        };
+        record_official_id(
+            &mut flok.animals[0],
+            "840003123456789",
+            Date::parse("2024-05-02").unwrap(),
+        );
+        assert_eq!(flok.animals[0].id, vec!["1", "840003123456789"]);
+
+        let found: Vec<String> = movements(&flok, None, None)
+            .iter()
+            .map(|m| {
+                format!(
+                    "{} {:?} {} {} {}",
+                    m.date.to_iso(),
+                    m.direction,
+                    m.id,
+                    m.premises_id,
+                    m.missing_id()
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "2024-05-01 In 1 00ABC12 false",
+                "2024-08-01 Out 1  false",
+                "2024-08-04 In 1  false",
+                "2024-10-01 Out 2 00ABC12 true",
+                "2024-12-01 Died 3  false",
+            ]
+        );
+        let from = Date::parse("2024-06-01");
+        let to = Date::parse("2024-10-31");
+        assert_eq!(movements(&flok, from, to).len(), 3);
+        let missing = missing_ids(&flok);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].0, "2");
+        assert!(movements_html(&flok, from, to).contains("MISSING"));
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{anyhow, Result};
+use layout::{
+    backends::svg::SVGWriter,
+    gv::{DotParser, GraphBuilder},
+};
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+use crate::flok::*;
+
+/// Write `contents` to a temporary file and open it in the browser.
+pub fn open_in_browser(contents: &str, suffix: &str) -> Result<()> {
+    let mut file = NamedTempFile::with_suffix(suffix)?;
+    file.as_file().write_all(contents.as_bytes())?;
+    file.disable_cleanup(true);
+
+    webbrowser::open(&("file://".to_owned() + file.path().to_str().unwrap()))?;
+
+    Ok(())
+}
+
+/// Make text safe to put in HTML.
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn report_lineage(f: &mut Flok) -> Result<()> {
+    open_in_browser(&lineage_svg(f)?, ".svg")
+}
+
+pub fn lineage_svg(f: &Flok) -> Result<String> {
+    let unknown = "unknown".to_string();
+    let input = "digraph {\n".to_string()
+        + "rankdir=LR;\n"
+        + &f.animals
+            .iter()
+            .map(|animal| {
+                let id = animal.id.first().unwrap_or(&unknown);
+                let mut result = String::new();
+                if let Some(dam_id) = &animal.dam {
+                    result += &format!("    {} -> {}\n", id, dam_id);
+                }
+                // if let Some(sire_id) = &animal.sire {
+                //     result += &format!("    {} -> {}\n", id, sire_id);
+                // }
+                result
+            })
+            .collect::<String>()
+        + "}\n";
+    let input = input.replace("?", "000").replace("#N/A", "000");
+
+    // Render the nodes to some rendering backend.
+    let gaph = DotParser::new(input.as_str())
+        .process()
+        .map_err(|e| anyhow!("Unable to parse DOT: {}", e))?;
+    let mut graph_builder = GraphBuilder::new();
+    graph_builder.visit_graph(&gaph);
+    let mut visual_graph = graph_builder.get();
+
+    let mut svg = SVGWriter::new();
+    visual_graph.do_it(false, false, false, &mut svg);
+
+    Ok(svg.finalize())
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use chrono::Days;
+use serde::Serialize;
+
+use crate::{
+    flok::*,
+    report::{escape, open_in_browser},
+};
+
+/// FAMACHA scores at or above this call for deworming.
+pub const DEWORM_FAMACHA: f64 = 4.0;
+
+/// BCS at or below this is thin.
+pub const THIN_BCS: f64 = 2.0;
+
+/// A BCS drop of this much between scorings is worth a look.
+pub const BCS_DROP: f64 = 1.0;
+
+/// Dewormed this many times in a year marks an animal for culling.
+pub const REPEAT_DEWORMINGS: usize = 3;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Cull,
+    Deworm,
+    Watch,
+}
+
+impl Action {
+    pub fn name(&self) -> &str {
+        match self {
+            Action::Cull => "Consider culling",
+            Action::Deworm => "Deworm",
+            Action::Watch => "Watch",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Flagged {
+    pub id: Id,
+    pub famacha: Option<f64>,
+    pub bcs: Option<f64>,
+    /// Dewormings in the year to the report date
+    pub dewormings: usize,
+    pub reasons: Vec<String>,
+    pub action: Action,
+}
+
+/// Dates the animal was dewormed, by a wormer in the product catalogue or an event like
+/// "wormer" or "dewormed".
+pub fn dewormings(flok: &Flok, animal: &Animal) -> Vec<Date> {
+    animal
+        .events
+        .iter()
+        .filter(|e| match flok.product(&e.name) {
+            Some(p) => p.kind == ProductKind::Wormer,
+            None => {
+                let name = e.name.to_lowercase();
+                name.contains("wormer") || name.contains("deworm") || name.contains("wormed")
+            }
+        })
+        .map(|e| e.date)
+        .collect()
+}
+
+fn latest_two(animal: &Animal, kind: ScoreKind) -> (Option<f64>, Option<f64>) {
+    let scores = animal.scores_of(kind);
+    let mut latest = scores.iter().rev().map(|s| s.value);
+    (latest.next(), latest.next())
+}
+
+/// Why `animal` needs attention on `date`, and what to do about it. `None` if it doesn't.
+pub fn check(flok: &Flok, animal: &Animal, date: Date) -> Option<Flagged> {
+    let mut reasons = vec![];
+    let mut action = Action::Watch;
+    let (famacha, previous_famacha) = latest_two(animal, ScoreKind::Famacha);
+    let (bcs, previous_bcs) = latest_two(animal, ScoreKind::Bcs);
+    if let Some(f) = famacha {
+        if f >= DEWORM_FAMACHA {
+            reasons.push(format!("FAMACHA {f}"));
+            action = Action::Deworm;
+        }
+        if let Some(p) = previous_famacha.filter(|p| f > *p) {
+            reasons.push(format!("FAMACHA worse, {p} -> {f}"));
+        }
+    }
+    if let Some(b) = bcs {
+        if b <= THIN_BCS {
+            reasons.push(format!("thin, BCS {b}"));
+        }
+        if let Some(p) = previous_bcs.filter(|p| p - b >= BCS_DROP) {
+            reasons.push(format!("BCS dropped, {p} -> {b}"));
+        }
+    }
+    let year_ago = date.day - Days::new(365);
+    let dewormings = dewormings(flok, animal)
+        .iter()
+        .filter(|d| d.day > year_ago && d.day <= date.day)
+        .count();
+    if dewormings >= REPEAT_DEWORMINGS {
+        reasons.push(format!("dewormed {dewormings} times in a year"));
+        action = Action::Cull;
+    }
+    (!reasons.is_empty()).then(|| Flagged {
+        id: animal.id.first().cloned().unwrap_or_default(),
+        famacha,
+        bcs,
+        dewormings,
+        reasons,
+        action,
+    })
+}
+
+/// Animals in the flock that need attention, culls first, then those to deworm.
+pub fn flagged(flok: &Flok, date: Date) -> Vec<Flagged> {
+    let mut flagged: Vec<Flagged> = flok
+        .animals
+        .iter()
+        .filter(|a| a.in_flock())
+        .filter_map(|a| check(flok, a, date))
+        .collect();
+    flagged.sort_by(|a, b| (a.action, &a.id).cmp(&(b.action, &b.id)));
+    flagged
+}
+
+fn show(n: Option<f64>) -> String {
+    n.map(|n| n.to_string()).unwrap_or_default()
+}
+
+pub fn parasite_html(flok: &Flok, date: Date) -> String {
+    let rows: String = flagged(flok, date)
+        .iter()
+        .map(|f| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&f.id),
+                f.action.name(),
+                show(f.famacha),
+                show(f.bcs),
+                f.dewormings,
+                escape(&f.reasons.join(", "))
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Parasites and condition</title></head><body>\n\
+         <h1>Parasites and condition, {date}</h1>\n\
+         <p>Deworm at FAMACHA {DEWORM_FAMACHA} or worse. Animals dewormed {REPEAT_DEWORMINGS} \
+         or more times in a year are culling candidates.</p>\n\
+         <table border=\"1\">\n<tr><th>Animal</th><th>Action</th><th>FAMACHA</th><th>BCS</th>\
+         <th>Dewormings in a year</th><th>Why</th></tr>\n{rows}</table>\n</body></html>\n"
+    )
+}
+
+pub fn report_parasites(flok: &Flok) -> Result<()> {
+    open_in_browser(&parasite_html(flok, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn score(kind: ScoreKind, date: &str, value: f64) -> Score {
+        Score {
+            date: Date::parse(date).unwrap(),
+            kind,
+            value,
+        }
+    }
+
+    fn wormed(date: &str) -> Event {
+        Event {
+            name: "Valbazen".to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flags() {
+        let animal = |id: &str, scores, events| Animal {
+            id: vec![id.to_string()],
+            scores,
+            events,
+            ..Default::default()
+        };
+        let flok = Flok {
+            products: vec![Product {
+                name: "Valbazen".to_string(),
+                ..Default::default()
+            }],
+            animals: vec![
+                animal(
+                    "fine",
+                    vec![
+                        score(ScoreKind::Famacha, "2024-06-01", 2.0),
+                        score(ScoreKind::Bcs, "2024-06-01", 3.0),
+                    ],
+                    vec![wormed("2023-06-01")],
+                ),
+                animal(
+                    "anaemic",
+                    vec![
+                        score(ScoreKind::Famacha, "2024-07-15", 4.0),
+                        score(ScoreKind::Famacha, "2024-06-01", 2.0),
+                        score(ScoreKind::Bcs, "2024-06-01", 3.5),
+                        score(ScoreKind::Bcs, "2024-07-15", 2.5),
+                    ],
+                    vec![],
+                ),
+                animal(
+                    "wormy",
+                    vec![],
+                    vec![
+                        wormed("2023-09-01"),
+                        wormed("2024-05-01"),
+                        wormed("2024-07-01"),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+        let flagged = flagged(&flok, Date::parse("2024-08-01").unwrap());
+        assert_eq!(
+            flagged
+                .iter()
+                .map(|f| (f.id.as_str(), f.action, f.reasons.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "wormy",
+                    Action::Cull,
+                    vec!["dewormed 3 times in a year".to_string()]
+                ),
+                (
+                    "anaemic",
+                    Action::Deworm,
+                    vec![
+                        "FAMACHA 4".to_string(),
+                        "FAMACHA worse, 2 -> 4".to_string(),
+                        "BCS dropped, 3.5 -> 2.5".to_string()
+                    ]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,569 @@
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+};
+
+use crate::{crypto, flok::*};
+
+/// Somewhere a flock is kept.
+pub trait Storage {
+    fn load(&self) -> Result<Flok>;
+    /// Replace the stored flock with `flok`.
+    fn save(&mut self, flok: &Flok) -> Result<()>;
+    /// The animal with tag `tag`, without loading the whole flock where the backend allows.
+    fn find(&self, tag: &str) -> Result<Option<Animal>>;
+    fn date_format(&self) -> Result<DateFormat> {
+        Ok(self.load()?.date_format)
+    }
+}
+
+/// Files ending in one of these are SQLite databases, anything else is JSON.
+pub const SQLITE_EXTENSIONS: [&str; 3] = ["flokdb", "sqlite", "db"];
+
+pub fn open(file: &str) -> Result<Box<dyn Storage>> {
+    let path = PathBuf::from(file);
+    if path
+        .extension()
+        .is_some_and(|e| SQLITE_EXTENSIONS.iter().any(|s| e == *s))
+    {
+        Ok(Box::new(SqliteStorage::open(file)?))
+    } else {
+        Ok(Box::new(JsonStorage { path }))
+    }
+}
+
+/// Copy a flock between files, eg from .flok to .flokdb. `decrypt` writes it without the
+/// passphrase.
+pub fn convert(from: &str, to: &str, decrypt: bool) -> Result<()> {
+    let flok = open(from)?.load()?;
+    if decrypt {
+        crypto::set_passphrase(None);
+    }
+    open(to)?.save(&flok)
+}
+
+/// The whole flock as one JSON document, the original .flok format.
+pub struct JsonStorage {
+    pub path: PathBuf,
+}
+
+impl Storage for JsonStorage {
+    fn load(&self) -> Result<Flok> {
+        Ok(serde_json::from_slice(&crypto::open(fs::read(
+            &self.path,
+        )?)?)?)
+    }
+
+    /// Write next to the file and rename over it, so a crash or full disk leaves the old file.
+    /// Encrypted if a passphrase is set.
+    fn save(&mut self, flok: &Flok) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&crypto::seal(serde_json::to_vec_pretty(flok)?)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn find(&self, tag: &str) -> Result<Option<Animal>> {
+        Ok(self.load()?.find(tag.to_string()).cloned())
+    }
+}
+
+/// One row per animal, tag and event. Saving rewrites only the animals that changed, in one
+/// transaction, so a crash leaves the previous save intact.
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS flock (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS animals (
+    id INTEGER PRIMARY KEY,
+    position INTEGER NOT NULL,
+    sex TEXT NOT NULL,
+    born TEXT,
+    sire TEXT,
+    dam TEXT,
+    description TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tags (
+    animal INTEGER NOT NULL REFERENCES animals(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    tag TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS tags_by_tag ON tags(tag);
+CREATE INDEX IF NOT EXISTS tags_by_animal ON tags(animal);
+CREATE TABLE IF NOT EXISTS events (
+    animal INTEGER NOT NULL REFERENCES animals(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    date TEXT NOT NULL,
+    notes TEXT NOT NULL,
+    contact TEXT
+);
+CREATE INDEX IF NOT EXISTS events_by_animal ON events(animal);
+CREATE TABLE IF NOT EXISTS weights (
+    animal INTEGER NOT NULL REFERENCES animals(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    value REAL NOT NULL,
+    unit TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS weights_by_animal ON weights(animal);
+CREATE TABLE IF NOT EXISTS scores (
+    animal INTEGER NOT NULL REFERENCES animals(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    value REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS scores_by_animal ON scores(animal);
+PRAGMA foreign_keys = ON;
+";
+
+fn parse_date(s: String) -> Result<Date> {
+    Date::parse_stored(&s).ok_or_else(|| anyhow!("Bad date {s} in database"))
+}
+
+impl SqliteStorage {
+    pub fn open(file: &str) -> Result<Self> {
+        let connection = Connection::open(file)?;
+        connection.execute_batch(SCHEMA)?;
+        // files made before events had a contact
+        let has_contact = connection
+            .prepare("SELECT 1 FROM pragma_table_info('events') WHERE name = 'contact'")?
+            .exists([])?;
+        if !has_contact {
+            connection.execute("ALTER TABLE events ADD COLUMN contact TEXT", [])?;
+        }
+        Ok(Self { connection })
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row("SELECT value FROM flock WHERE key = ?1", [key], |r| {
+                r.get(0)
+            })
+            .optional()?)
+    }
+
+    fn animal(&self, id: i64) -> Result<Animal> {
+        let (sex, born, sire, dam, description): (String, Option<String>, _, _, _) =
+            self.connection.query_row(
+                "SELECT sex, born, sire, dam, description FROM animals WHERE id = ?1",
+                [id],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )?;
+        let mut tags = self
+            .connection
+            .prepare_cached("SELECT tag FROM tags WHERE animal = ?1 ORDER BY position")?;
+        let id_tags = tags
+            .query_map([id], |r| r.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        let mut events = self.connection.prepare_cached(
+            "SELECT name, value, date, notes, contact FROM events WHERE animal = ?1
+             ORDER BY position",
+        )?;
+        let events = events
+            .query_map([id], |r| {
+                Ok((
+                    r.get(0)?,
+                    r.get(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get(3)?,
+                    r.get(4)?,
+                ))
+            })?
+            .map(|r| {
+                let (name, value, date, notes, contact) = r?;
+                Ok(Event {
+                    name,
+                    value,
+                    date: parse_date(date)?,
+                    notes,
+                    contact,
+                })
+            })
+            .collect::<Result<Vec<Event>>>()?;
+        let mut weights = self.connection.prepare_cached(
+            "SELECT date, value, unit FROM weights WHERE animal = ?1 ORDER BY position",
+        )?;
+        let weights = weights
+            .query_map([id], |r| {
+                Ok((r.get::<_, String>(0)?, r.get(1)?, r.get::<_, String>(2)?))
+            })?
+            .map(|r| {
+                let (date, value, unit) = r?;
+                Ok(Weight {
+                    date: parse_date(date)?,
+                    value,
+                    unit: serde_json::from_str(&unit)?,
+                })
+            })
+            .collect::<Result<Vec<Weight>>>()?;
+        let mut scores = self.connection.prepare_cached(
+            "SELECT date, kind, value FROM scores WHERE animal = ?1 ORDER BY position",
+        )?;
+        let scores = scores
+            .query_map([id], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get(2)?))
+            })?
+            .map(|r| {
+                let (date, kind, value) = r?;
+                Ok(Score {
+                    date: parse_date(date)?,
+                    kind: serde_json::from_str(&kind)?,
+                    value,
+                })
+            })
+            .collect::<Result<Vec<Score>>>()?;
+        Ok(Animal {
+            id: id_tags,
+            sex: Sex::from(sex),
+            born: born.map(parse_date).transpose()?,
+            sire,
+            dam,
+            description,
+            events,
+            weights,
+            scores,
+        })
+    }
+
+    /// Every animal with its row id, in flock order.
+    fn animals(&self) -> Result<Vec<(i64, Animal)>> {
+        let ids = self
+            .connection
+            .prepare("SELECT id FROM animals ORDER BY position")?
+            .query_map([], |r| r.get(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+        ids.into_iter()
+            .map(|id| Ok((id, self.animal(id)?)))
+            .collect()
+    }
+}
+
+fn write_animal(tx: &Transaction, id: Option<i64>, position: usize, a: &Animal) -> Result<i64> {
+    let born = a.born.map(|d| d.to_iso());
+    let id = match id {
+        Some(id) => {
+            tx.execute(
+                "UPDATE animals SET position = ?2, sex = ?3, born = ?4, sire = ?5, dam = ?6,
+                 description = ?7 WHERE id = ?1",
+                params![
+                    id,
+                    position,
+                    a.sex.name(),
+                    born,
+                    a.sire,
+                    a.dam,
+                    a.description
+                ],
+            )?;
+            tx.execute("DELETE FROM tags WHERE animal = ?1", [id])?;
+            tx.execute("DELETE FROM events WHERE animal = ?1", [id])?;
+            tx.execute("DELETE FROM weights WHERE animal = ?1", [id])?;
+            tx.execute("DELETE FROM scores WHERE animal = ?1", [id])?;
+            id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO animals (position, sex, born, sire, dam, description)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![position, a.sex.name(), born, a.sire, a.dam, a.description],
+            )?;
+            tx.last_insert_rowid()
+        }
+    };
+    for (i, tag) in a.id.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO tags (animal, position, tag) VALUES (?1, ?2, ?3)",
+            params![id, i, tag],
+        )?;
+    }
+    for (i, e) in a.events.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO events (animal, position, name, value, date, notes, contact)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![id, i, e.name, e.value, e.date.to_iso(), e.notes, e.contact],
+        )?;
+    }
+    for (i, w) in a.weights.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO weights (animal, position, date, value, unit) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                i,
+                w.date.to_iso(),
+                w.value,
+                serde_json::to_string(&w.unit)?
+            ],
+        )?;
+    }
+    for (i, s) in a.scores.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO scores (animal, position, date, kind, value) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                i,
+                s.date.to_iso(),
+                serde_json::to_string(&s.kind)?,
+                s.value
+            ],
+        )?;
+    }
+    Ok(id)
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Flok> {
+        Ok(Flok {
+            name: self.setting("name")?.unwrap_or_default(),
+            animals: self.animals()?.into_iter().map(|(_, a)| a).collect(),
+            date_format: self.date_format()?,
+            sync: match self.setting("sync")? {
+                Some(s) => serde_json::from_str(&s)?,
+                None => None,
+            },
+            weight_unit: match self.setting("weight_unit")? {
+                Some(u) => serde_json::from_str(&u)?,
+                None => WeightUnit::default(),
+            },
+            backups: match self.setting("backups")? {
+                Some(b) => serde_json::from_str(&b)?,
+                None => None,
+            },
+            products: match self.setting("products")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            protocols: match self.setting("protocols")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            exposures: match self.setting("exposures")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            ledger: match self.setting("ledger")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            contacts: match self.setting("contacts")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            groups: match self.setting("groups")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            pastures: match self.setting("pastures")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            feed_purchases: match self.setting("feed_purchases")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            feedings: match self.setting("feedings")? {
+                Some(p) => serde_json::from_str(&p)?,
+                None => vec![],
+            },
+            premises_id: self.setting("premises_id")?.unwrap_or_default(),
+        })
+    }
+
+    fn save(&mut self, flok: &Flok) -> Result<()> {
+        if crypto::passphrase().is_some() {
+            return Err(anyhow!(
+                "SQLite files can't be encrypted, save as .flok or remove the passphrase"
+            ));
+        }
+        let mut stored: Vec<Option<(i64, Animal)>> =
+            self.animals()?.into_iter().map(Some).collect();
+        let tx = self.connection.transaction()?;
+        let set = |key: &str, value: String| {
+            tx.execute(
+                "INSERT OR REPLACE INTO flock (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+        };
+        set("name", flok.name.clone())?;
+        set("date_format", serde_json::to_string(&flok.date_format)?)?;
+        set("sync", serde_json::to_string(&flok.sync)?)?;
+        set("backups", serde_json::to_string(&flok.backups)?)?;
+        set("weight_unit", serde_json::to_string(&flok.weight_unit)?)?;
+        set("products", serde_json::to_string(&flok.products)?)?;
+        set("protocols", serde_json::to_string(&flok.protocols)?)?;
+        set("exposures", serde_json::to_string(&flok.exposures)?)?;
+        set("ledger", serde_json::to_string(&flok.ledger)?)?;
+        set("contacts", serde_json::to_string(&flok.contacts)?)?;
+        set("groups", serde_json::to_string(&flok.groups)?)?;
+        set("pastures", serde_json::to_string(&flok.pastures)?)?;
+        set(
+            "feed_purchases",
+            serde_json::to_string(&flok.feed_purchases)?,
+        )?;
+        set("feedings", serde_json::to_string(&flok.feedings)?)?;
+        set("premises_id", flok.premises_id.clone())?;
+
+        let mut edited = vec![];
+        for (position, a) in flok.animals.iter().enumerate() {
+            match stored
+                .iter_mut()
+                .find(|s| s.as_ref().is_some_and(|(_, s)| s == a))
+                .and_then(|s| s.take())
+            {
+                Some((id, _)) => {
+                    tx.execute(
+                        "UPDATE animals SET position = ?2 WHERE id = ?1 AND position != ?2",
+                        params![id, position],
+                    )?;
+                }
+                None => edited.push((position, a)),
+            }
+        }
+        for (position, a) in edited {
+            // reuse the row of an edited animal, so it keeps its place
+            let id = stored
+                .iter_mut()
+                .find(|s| {
+                    s.as_ref()
+                        .is_some_and(|(_, s)| s.id.iter().any(|t| a.id.contains(t)))
+                })
+                .and_then(|s| s.take())
+                .map(|(id, _)| id);
+            write_animal(&tx, id, position, a)?;
+        }
+        for (id, _) in stored.into_iter().flatten() {
+            tx.execute("DELETE FROM animals WHERE id = ?1", [id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn find(&self, tag: &str) -> Result<Option<Animal>> {
+        let id: Option<i64> = self
+            .connection
+            .query_row("SELECT animal FROM tags WHERE tag = ?1", [tag], |r| {
+                r.get(0)
+            })
+            .optional()?;
+        id.map(|id| self.animal(id)).transpose()
+    }
+
+    fn date_format(&self) -> Result<DateFormat> {
+        Ok(match self.setting("date_format")? {
+            Some(f) => serde_json::from_str(&f)?,
+            None => DateFormat::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sqlite_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("herd.flok");
+        let db = dir.path().join("herd.flokdb");
+        let (json, db) = (json.to_str().unwrap(), db.to_str().unwrap());
+
+        let mut flok = Flok {
+            name: "herd".to_string(),
+            premises_id: "00XYZ34".to_string(),
+            date_format: DateFormat::Us,
+            weight_unit: WeightUnit::Kg,
+            products: vec![Product {
+                name: "Valbazen".to_string(),
+                meat_withdrawal: 7,
+                dose_per_kg: Some(0.2),
+                ..Default::default()
+            }],
+            ledger: vec![LedgerEntry {
+                date: Date::parse("2024-01-15").unwrap(),
+                category: Category::Hay,
+                amount: 450.0,
+                animal: None,
+                description: "30 bales".to_string(),
+            }],
+            contacts: vec![Contact {
+                name: "Joe Baldwin".to_string(),
+                premises_id: "00ABC12".to_string(),
+                ..Default::default()
+            }],
+            animals: vec![
+                Animal {
+                    id: vec!["1".to_string(), "old tag".to_string()],
+                    sex: Sex::Female,
+                    born: Date::parse("2020-04-03"),
+                    sire: Some("2".to_string()),
+                    events: vec![
+                        Event {
+                            name: "lambed".to_string(),
+                            value: "twins".to_string(),
+                            date: Date::parse("2022-03-01 06:30").unwrap(),
+                            notes: "".to_string(),
+                            contact: None,
+                        },
+                        Event {
+                            name: "sold".to_string(),
+                            date: Date::parse("2024-05-10").unwrap(),
+                            contact: Some("Joe Baldwin".to_string()),
+                            ..Default::default()
+                        },
+                    ],
+                    weights: vec![Weight {
+                        date: Date::parse("2020-06-02").unwrap(),
+                        value: 61.5,
+                        unit: WeightUnit::Kg,
+                    }],
+                    scores: vec![Score {
+                        date: Date::parse("2022-01-10").unwrap(),
+                        kind: ScoreKind::Bcs,
+                        value: 3.5,
+                    }],
+                    ..Default::default()
+                },
+                Animal {
+                    id: vec!["2".to_string()],
+                    sex: Sex::Male,
+                    description: "ram".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        open(json).unwrap().save(&flok).unwrap();
+        convert(json, db, false).unwrap();
+        assert_eq!(open(db).unwrap().load().unwrap(), flok);
+        assert_eq!(
+            open(db).unwrap().find("old tag").unwrap().as_ref(),
+            Some(&flok.animals[0])
+        );
+
+        flok.animals.swap(0, 1);
+        flok.animals[0].description = "big ram".to_string();
+        flok.animals.push(Animal {
+            id: vec!["3".to_string()],
+            ..Default::default()
+        });
+        open(db).unwrap().save(&flok).unwrap();
+        flok.animals.remove(1);
+        open(db).unwrap().save(&flok).unwrap();
+        assert_eq!(open(db).unwrap().load().unwrap(), flok);
+        assert_eq!(open(db).unwrap().find("old tag").unwrap(), None);
+
+        convert(db, json, false).unwrap();
+        assert_eq!(open(json).unwrap().load().unwrap(), flok);
+    }
+}
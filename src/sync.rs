@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Result};
+
+use crate::{flok::*, merge};
+
+pub struct SyncReport {
+    pub revision: u64,
+    /// Changes from other devices were merged in
+    pub pulled: bool,
+    pub pushed: bool,
+    /// Fields changed on this and another device. Nothing was merged or sent until they are
+    /// resolved and given to `settle`.
+    pub conflicts: Option<Unsettled>,
+}
+
+/// A merge with the server that has conflicts to resolve.
+pub struct Unsettled {
+    pub merged: merge::Merged,
+    /// The revision on the server that was merged
+    pub revision: u64,
+}
+
+impl SyncReport {
+    pub fn describe(&self) -> String {
+        let mut s = format!("Synced to revision {}.", self.revision);
+        if self.pulled {
+            s += " Merged changes from other devices.";
+        }
+        if self.pushed {
+            s += " Sent local changes.";
+        }
+        if let Some(unsettled) = &self.conflicts {
+            let changed: Vec<String> = unsettled
+                .merged
+                .conflicts
+                .iter()
+                .map(|c| c.label())
+                .collect();
+            s += &format!(
+                "\nEdited on another device too: {}.\nChoose which to keep, then sync again.",
+                changed.join(", ")
+            );
+        }
+        s
+    }
+}
+
+/// Start sharing `flok` as `name` on `server`, eg "http://barn:8080".
+pub fn share(flok: &mut Flok, server: &str, name: &str) {
+    flok.sync = Some(SyncState {
+        server: server.trim_end_matches('/').to_string(),
+        name: name.to_string(),
+        revision: 0,
+    });
+}
+
+/// Merge in changes from the server, then send ours. If the same field was changed here and on
+/// another device the flock is left alone and the merge is in the report's `conflicts`.
+pub fn sync(flok: &mut Flok) -> Result<SyncReport> {
+    let state = flok
+        .sync
+        .clone()
+        .ok_or(anyhow!("This flock is not shared on a server"))?;
+    let mut report = SyncReport {
+        revision: state.revision,
+        pulled: false,
+        pushed: false,
+        conflicts: None,
+    };
+    // someone may push between our pull and push, so go around again
+    for _ in 0..3 {
+        let state = flok.sync.clone().unwrap();
+        if let Some((revision, theirs)) = fetch(&state, None)? {
+            if revision != state.revision {
+                let base = match state.revision {
+                    0 => Flok::default(),
+                    r => fetch(&state, Some(r))?.map(|(_, f)| f).unwrap_or_default(),
+                };
+                let merged = merge::merge(&base, &unshared(flok), &theirs);
+                if !merged.conflicts.is_empty() {
+                    // taking the revision now would send our side over theirs next time
+                    report.conflicts = Some(Unsettled { merged, revision });
+                    return Ok(report);
+                }
+                settle(flok, merged.flok, revision);
+                report.revision = revision;
+                report.pulled = true;
+            }
+        }
+        if let Some(revision) = push(flok)? {
+            flok.sync.as_mut().unwrap().revision = revision;
+            report.revision = revision;
+            report.pushed = true;
+            return Ok(report);
+        }
+    }
+    Err(anyhow!("The flock keeps changing on the server, try again"))
+}
+
+/// Take `resolved`, the merge with `revision` with its conflicts resolved, as if it had just been
+/// pulled. Sync again to send it.
+pub fn settle(flok: &mut Flok, resolved: Flok, revision: u64) {
+    let sync = flok.sync.clone().map(|s| SyncState { revision, ..s });
+    *flok = Flok { sync, ..resolved };
+}
+
+/// The flock without the local sync state.
+fn unshared(flok: &Flok) -> Flok {
+    Flok {
+        sync: None,
+        ..flok.clone()
+    }
+}
+
+fn url(state: &SyncState, revision: Option<u64>) -> String {
+    match revision {
+        None => format!("{}/flocks/{}", state.server, state.name),
+        Some(r) => format!("{}/flocks/{}/{r}", state.server, state.name),
+    }
+}
+
+fn revision_of(response: &ureq::Response) -> Result<u64> {
+    response
+        .header("X-Revision")
+        .and_then(|r| r.parse().ok())
+        .ok_or(anyhow!("Server did not send a revision"))
+}
+
+/// A revision from the server, the latest if `revision` is `None`.
+fn fetch(state: &SyncState, revision: Option<u64>) -> Result<Option<(u64, Flok)>> {
+    match ureq::get(&url(state, revision)).call() {
+        Ok(response) => {
+            let revision = revision_of(&response)?;
+            Ok(Some((
+                revision,
+                serde_json::from_reader(response.into_reader())?,
+            )))
+        }
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The new revision, or `None` if the server has changed since our last sync.
+fn push(flok: &Flok) -> Result<Option<u64>> {
+    let state = flok.sync.as_ref().unwrap();
+    match ureq::put(&url(state, None))
+        .set("X-Base-Revision", &state.revision.to_string())
+        .send_string(&serde_json::to_string(&unshared(flok))?)
+    {
+        Ok(response) => Ok(Some(revision_of(&response)?)),
+        Err(ureq::Error::Status(409, _)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sync_server;
+
+    fn animal(id: &str, description: &str) -> Animal {
+        Animal {
+            id: vec![id.to_string()],
+            description: description.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn description(flok: &Flok, id: &str) -> String {
+        flok.find(id.to_string()).unwrap().description.clone()
+    }
+
+    #[test]
+    fn two_devices() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        let store = dir.path().to_path_buf();
+        std::thread::spawn(move || sync_server::run(server, &store));
+        let address = format!("http://127.0.0.1:{port}");
+
+        let mut laptop = Flok {
+            animals: vec![animal("1", "ewe"), animal("2", "ram")],
+            ..Default::default()
+        };
+        share(&mut laptop, &address, "home");
+        assert!(sync(&mut laptop).unwrap().pushed);
+
+        let mut phone = Flok::default();
+        share(&mut phone, &address, "home");
+        let report = sync(&mut phone).unwrap();
+        assert!(report.pulled);
+        assert_eq!(phone.animals, laptop.animals);
+
+        // different animals merge
+        laptop.animals[0].description = "good mom".to_string();
+        phone.animals[1].description = "big".to_string();
+        phone.animals.push(animal("3", "lamb"));
+        assert!(sync(&mut laptop).unwrap().pushed);
+        let report = sync(&mut phone).unwrap();
+        assert!(report.pulled && report.pushed && report.conflicts.is_none());
+        sync(&mut laptop).unwrap();
+        assert_eq!(description(&laptop, "1"), "good mom");
+        assert_eq!(description(&laptop, "2"), "big");
+        assert_eq!(description(&laptop, "3"), "lamb");
+
+        // the same animal is a conflict
+        laptop.animals[0].description = "singles".to_string();
+        phone.animals[0].description = "twins".to_string();
+        sync(&mut laptop).unwrap();
+        let before = phone.clone();
+        let report = sync(&mut phone).unwrap();
+        assert!(!report.pulled && !report.pushed);
+        assert_eq!(phone, before);
+        let Unsettled {
+            mut merged,
+            revision,
+        } = report.conflicts.unwrap();
+        let labels: Vec<String> = merged.conflicts.iter().map(|c| c.label()).collect();
+        assert_eq!(labels, vec!["1 Description"]);
+        // keep ours, the laptop gets it on its next sync
+        merged.conflicts[0].resolve(&mut merged.flok, false);
+        settle(&mut phone, merged.flok, revision);
+        assert!(sync(&mut phone).unwrap().pushed);
+        sync(&mut laptop).unwrap();
+        assert_eq!(description(&laptop, "1"), "twins");
+    }
+}
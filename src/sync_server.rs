@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// The largest flock accepted, far more than any real one, so a client can't fill the memory.
+const MAX_BODY: u64 = 64 * 1024 * 1024;
+
+/// Stores every revision of each flock as `dir/<name>/<revision>.flok`.
+///
+/// * `GET /flocks/<name>` the latest revision
+/// * `GET /flocks/<name>/<revision>` an older one
+/// * `PUT /flocks/<name>` with `X-Base-Revision` set to the revision the edit started from.
+///   Answers 409 Conflict if someone else has pushed since, 413 if it's over 64 MiB.
+///
+/// The revision is returned in `X-Revision`. There is no authentication, so keep it on a
+/// private network.
+pub fn serve(addr: &str, dir: PathBuf) -> Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow!("Unable to listen on {addr}: {e}"))?;
+    eprintln!("Serving flocks from {} on {addr}", dir.display());
+    run(server, &dir);
+    Ok(())
+}
+
+pub fn run(server: Server, dir: &Path) {
+    for mut request in server.incoming_requests() {
+        let response = handle(dir, &mut request)
+            .unwrap_or_else(|e| Response::from_string(e.to_string()).with_status_code(500));
+        if let Err(e) = request.respond(response) {
+            eprintln!("Unable to respond: {e}");
+        }
+    }
+}
+
+fn handle(dir: &Path, request: &mut Request) -> Result<Response<Cursor<Vec<u8>>>> {
+    let path: Vec<&str> = request.url().trim_matches('/').split('/').collect();
+    let (name, revision) = match path.as_slice() {
+        ["flocks", name] => (name.to_string(), None),
+        ["flocks", name, revision] => match revision.parse::<u64>() {
+            Ok(r) => (name.to_string(), Some(r)),
+            Err(_) => return Ok(status(400, "Bad revision")),
+        },
+        _ => return Ok(status(404, "Not found")),
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Ok(status(400, "Flock names are letters, digits, - and _"));
+    }
+    let dir = dir.join(&name);
+    let latest = latest_revision(&dir)?;
+
+    match (request.method(), revision) {
+        (Method::Get, revision) => match revision.or(latest) {
+            Some(revision) if dir.join(file_name(revision)).exists() => {
+                let body = fs::read(dir.join(file_name(revision)))?;
+                Ok(Response::from_data(body).with_header(revision_header(revision)))
+            }
+            _ => Ok(status(404, "No such flock or revision")),
+        },
+        (Method::Put, None) => {
+            if request.body_length().is_some_and(|l| l as u64 > MAX_BODY) {
+                return Ok(status(413, "Flock too large"));
+            }
+            let base = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("X-Base-Revision"))
+                .and_then(|h| h.value.as_str().parse::<u64>().ok())
+                .unwrap_or(0);
+            let latest = latest.unwrap_or(0);
+            if base != latest {
+                return Ok(status(409, "Pull first, the flock has changed")
+                    .with_header(revision_header(latest)));
+            }
+            // a chunked body has no length, so stop reading once past the limit
+            let mut body = vec![];
+            request
+                .as_reader()
+                .take(MAX_BODY + 1)
+                .read_to_end(&mut body)?;
+            if body.len() as u64 > MAX_BODY {
+                return Ok(status(413, "Flock too large"));
+            }
+            if serde_json::from_slice::<serde_json::Value>(&body).is_err() {
+                return Ok(status(400, "Not JSON"));
+            }
+            let revision = latest + 1;
+            fs::create_dir_all(&dir)?;
+            let tmp = dir.join(format!("{revision}.tmp"));
+            fs::write(&tmp, body)?;
+            fs::rename(tmp, dir.join(file_name(revision)))?;
+            Ok(status(200, "Stored").with_header(revision_header(revision)))
+        }
+        _ => Ok(status(405, "Method not allowed")),
+    }
+}
+
+fn file_name(revision: u64) -> String {
+    format!("{revision}.flok")
+}
+
+fn latest_revision(dir: &Path) -> Result<Option<u64>> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut latest = None;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "flok") {
+            if let Some(r) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                latest = latest.max(Some(r));
+            }
+        }
+    }
+    Ok(latest)
+}
+
+fn revision_header(revision: u64) -> Header {
+    Header::from_bytes("X-Revision", revision.to_string()).unwrap()
+}
+
+fn status(code: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(message).with_status_code(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpStream,
+    };
+
+    #[test]
+    fn too_large() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        let store = dir.path().to_path_buf();
+        std::thread::spawn(move || run(server, &store));
+
+        // refused from the length alone, before any of the body is sent
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "PUT /flocks/home HTTP/1.1\r\nHost: {addr}\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        )
+        .unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        assert!(line.contains("413"), "{line}");
+        assert!(!dir.path().join("home").exists());
+    }
+}
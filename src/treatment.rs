@@ -0,0 +1,212 @@
+use chrono::Days;
+use serde::Serialize;
+
+use crate::{
+    flok::*,
+    report::{escape, open_in_browser},
+    validate::FieldError,
+};
+
+/// A treatment event and the product it used.
+pub fn treatments<'a>(flok: &'a Flok, animal: &'a Animal) -> Vec<(&'a Event, &'a Product)> {
+    animal
+        .events
+        .iter()
+        .filter_map(|e| flok.product(&e.name).map(|p| (e, p)))
+        .collect()
+}
+
+fn clear_date(treated: Date, days: u32) -> Date {
+    (treated.day + Days::new(days as u64)).into()
+}
+
+/// The first day the animal can be slaughtered after all its treatments, `None` if it was never
+/// treated with a product that has a meat withdrawal.
+pub fn withdrawal_clear(flok: &Flok, animal: &Animal) -> Option<Date> {
+    treatments(flok, animal)
+        .iter()
+        .filter(|(_, p)| p.meat_withdrawal > 0)
+        .map(|(e, p)| clear_date(e.date, p.meat_withdrawal))
+        .max()
+}
+
+/// The first day the animal's milk can be used.
+pub fn milk_clear(flok: &Flok, animal: &Animal) -> Option<Date> {
+    treatments(flok, animal)
+        .iter()
+        .filter(|(_, p)| p.milk_withdrawal > 0)
+        .map(|(e, p)| clear_date(e.date, p.milk_withdrawal))
+        .max()
+}
+
+/// A sale inside the withdrawal period of an earlier treatment, other than the `recorded` ones.
+pub fn check_sales(flok: &Flok, animal: &Animal, recorded: &[Event]) -> Vec<FieldError> {
+    let treatments = treatments(flok, animal);
+    animal
+        .events
+        .iter()
+        .filter(|e| e.is_sale() && !recorded.contains(e))
+        .filter_map(|sale| {
+            treatments
+                .iter()
+                .filter(|(e, _)| e.date.day <= sale.date.day)
+                .map(|(e, p)| (clear_date(e.date, p.meat_withdrawal), p))
+                .filter(|(clear, _)| clear.day > sale.date.day)
+                .max_by_key(|(clear, _)| *clear)
+                .map(|(clear, p)| {
+                    FieldError::new(
+                        "Events",
+                        format!(
+                            "Sold on {} inside the withdrawal period of {}, clear on {clear}",
+                            sale.date, p.name
+                        ),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// The dose for an animal of `kg`, if the product has a dose per kg.
+pub fn dose(product: &Product, kg: f64) -> Option<f64> {
+    product.dose_per_kg.map(|d| d * kg)
+}
+
+/// The dose of `product` for `animal` at its latest weight, as text for an event value.
+pub fn suggested_dose(product: &Product, animal: &Animal) -> Option<String> {
+    let weight = animal.weights.iter().max_by_key(|w| w.date)?;
+    dose(product, weight.value_in(WeightUnit::Kg)).map(|ml| format!("{ml:.1} ml"))
+}
+
+#[derive(Serialize)]
+pub struct Withdrawal {
+    pub id: Id,
+    pub last_treatment: Option<String>,
+    pub meat_clear: Option<Date>,
+    pub milk_clear: Option<Date>,
+}
+
+/// Animals still inside a withdrawal period on `date`.
+pub fn in_withdrawal(flok: &Flok, date: Date) -> Vec<Withdrawal> {
+    flok.animals
+        .iter()
+        .filter(|a| a.died().is_none())
+        .map(|a| Withdrawal {
+            id: a.id.first().cloned().unwrap_or_default(),
+            last_treatment: treatments(flok, a)
+                .iter()
+                .max_by_key(|(e, _)| e.date)
+                .map(|(e, _)| format!("{} {}", e.date, e.name)),
+            meat_clear: withdrawal_clear(flok, a),
+            milk_clear: milk_clear(flok, a),
+        })
+        .filter(|w| {
+            w.meat_clear
+                .iter()
+                .chain(w.milk_clear.iter())
+                .any(|c| c.day > date.day)
+        })
+        .collect()
+}
+
+pub fn withdrawal_html(flok: &Flok, date: Date) -> String {
+    let show = |d: Option<Date>| d.map(|d| d.to_string()).unwrap_or_default();
+    let rows: String = in_withdrawal(flok, date)
+        .iter()
+        .map(|w| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&w.id),
+                escape(w.last_treatment.as_deref().unwrap_or_default()),
+                show(w.meat_clear),
+                show(w.milk_clear)
+            )
+        })
+        .collect();
+    format!(
+        "<html><head><title>Withdrawal</title></head><body>\n<h1>In withdrawal on {date}</h1>\n\
+         <table border=\"1\">\n<tr><th>Animal</th><th>Last treatment</th><th>Meat clear</th>\
+         <th>Milk clear</th></tr>\n\
+         {rows}</table>\n</body></html>\n"
+    )
+}
+
+pub fn report_withdrawal(flok: &Flok) -> anyhow::Result<()> {
+    open_in_browser(&withdrawal_html(flok, Date::today()), ".html")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(name: &str, date: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn withdrawal() {
+        let flok = Flok {
+            products: vec![
+                Product {
+                    name: "Valbazen".to_string(),
+                    meat_withdrawal: 7,
+                    milk_withdrawal: 1,
+                    dose_per_kg: Some(0.2),
+                    ..Default::default()
+                },
+                Product {
+                    name: "CD&T".to_string(),
+                    kind: ProductKind::Vaccine,
+                    meat_withdrawal: 21,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut ewe = Animal {
+            events: vec![
+                event("cd&t ", "2024-05-01"),
+                event("valbazen", "2024-05-20"),
+            ],
+            weights: vec![Weight {
+                date: Date::parse("2024-05-01").unwrap(),
+                value: 50.0,
+                unit: WeightUnit::Kg,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(withdrawal_clear(&flok, &ewe), Date::parse("2024-05-27"));
+        assert_eq!(milk_clear(&flok, &ewe), Date::parse("2024-05-21"));
+        assert_eq!(
+            suggested_dose(&flok.products[0], &ewe),
+            Some("10.0 ml".to_string())
+        );
+
+        ewe.events.push(event("sold", "2024-05-21"));
+        let errors = check_sales(&flok, &ewe, &[]);
+        assert_eq!(errors.len(), 1);
+        // the message shows dates in the chosen format, which other tests change
+        assert!(errors[0].message.contains("withdrawal period of Valbazen"));
+        assert_eq!(
+            withdrawal_clear(&flok, &ewe).map(|d| d.to_iso()),
+            Some("2024-05-27".to_string())
+        );
+        assert!(check_sales(&flok, &ewe, &ewe.events).is_empty());
+        ewe.events.last_mut().unwrap().date = Date::parse("2024-05-27").unwrap();
+        assert!(check_sales(&flok, &ewe, &[]).is_empty());
+        assert_eq!(
+            in_withdrawal(
+                &Flok {
+                    animals: vec![ewe],
+                    ..flok
+                },
+                Date::parse("2024-05-25").unwrap()
+            )
+            .len(),
+            1
+        );
+    }
+}
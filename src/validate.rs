@@ -0,0 +1,361 @@
+use std::fmt::Display;
+
+use crate::{
+    feed::{self, FeedPurchase, Feeding},
+    flok::*,
+    groups, regulatory, treatment,
+};
+
+/// A problem with the value of one field of a form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Combine validation errors into one error for display.
+pub fn validation_error(errors: Vec<FieldError>) -> anyhow::Error {
+    anyhow::anyhow!(
+        "\n{}",
+        errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+pub fn check_event(event: &Event) -> Vec<FieldError> {
+    let mut errors = vec![];
+    if event.name.trim().is_empty() {
+        errors.push(FieldError::new("Name", "Event name is empty"));
+    }
+    if event.date.is_future() {
+        errors.push(FieldError::new("Date", "Date is in the future"));
+    }
+    errors
+}
+
+pub fn check_score(score: &Score) -> Vec<FieldError> {
+    let mut errors = vec![];
+    let steps = (score.value - 1.0) / score.kind.step();
+    if !(1.0..=5.0).contains(&score.value) || steps.fract() != 0.0 {
+        errors.push(FieldError::new(
+            "Scores",
+            format!(
+                "{} {} is not a score from 1 to 5 in steps of {}",
+                score.kind.name(),
+                score.value,
+                score.kind.step()
+            ),
+        ));
+    }
+    if score.date.is_future() {
+        errors.push(FieldError::new("Scores", "Score date is in the future"));
+    }
+    errors
+}
+
+pub fn check_feed_purchase(purchase: &FeedPurchase) -> Vec<FieldError> {
+    let mut errors = vec![];
+    if purchase.feed.trim().is_empty() {
+        errors.push(FieldError::new("Feed", "Feed name is empty"));
+    }
+    if purchase.quantity <= 0.0 {
+        errors.push(FieldError::new("Quantity", "Quantity must be more than 0"));
+    }
+    if purchase.cost < 0.0 {
+        errors.push(FieldError::new("Cost", "Cost can't be negative"));
+    }
+    if purchase.date.is_future() {
+        errors.push(FieldError::new("Date", "Date is in the future"));
+    }
+    errors
+}
+
+/// A feeding must be of a feed that was bought, so it has a cost, to a group in `flok`.
+pub fn check_feeding(flok: &Flok, feeding: &Feeding) -> Vec<FieldError> {
+    let mut errors = vec![];
+    if groups::group(flok, &feeding.group).is_none() {
+        errors.push(FieldError::new(
+            "Group",
+            format!("{} is not a group", feeding.group),
+        ));
+    }
+    if feed::purchases(flok, &feeding.feed).next().is_none() {
+        errors.push(FieldError::new(
+            "Feed",
+            format!("No {} has been bought", feeding.feed),
+        ));
+    }
+    if feeding.quantity <= 0.0 {
+        errors.push(FieldError::new("Quantity", "Quantity must be more than 0"));
+    }
+    if feeding.date.is_future() {
+        errors.push(FieldError::new("Date", "Date is in the future"));
+    }
+    errors
+}
+
+/// Check `animal` as if it replaced the animal at `index` in `flok`.
+pub fn check_animal(flok: &Flok, index: Option<usize>, animal: &Animal) -> Vec<FieldError> {
+    // a sale already recorded isn't refused again, so the animal can still be edited
+    let recorded = index
+        .and_then(|i| flok.animals.get(i))
+        .map_or(&[][..], |a| &a.events);
+    check_edited_animal(flok, index, animal, recorded)
+}
+
+/// Like `check_animal`, for an animal whose events were edited in place: `recorded` are its
+/// events from before the edit, and only sales not among them are checked against withdrawal.
+pub fn check_edited_animal(
+    flok: &Flok,
+    index: Option<usize>,
+    animal: &Animal,
+    recorded: &[Event],
+) -> Vec<FieldError> {
+    let mut errors = vec![];
+    if animal.id.is_empty() || animal.id.iter().any(|id| id.trim().is_empty()) {
+        errors.push(FieldError::new("Identity", "Empty ID"));
+    }
+    for id in animal.id.iter().filter(|id| !id.trim().is_empty()) {
+        let duplicate = flok
+            .animals
+            .iter()
+            .enumerate()
+            .any(|(i, other)| Some(i) != index && other.id.contains(id));
+        if duplicate {
+            errors.push(FieldError::new(
+                "Identity",
+                format!("{id} is used by another animal"),
+            ));
+        }
+    }
+    for (field, role, parent) in [("Sire", "sire", &animal.sire), ("Dam", "dam", &animal.dam)] {
+        if let Some(parent) = parent {
+            if animal.id.contains(parent) {
+                errors.push(FieldError::new(
+                    field,
+                    format!("An animal cannot be its own {role}"),
+                ));
+            } else if flok
+                .ancestors(parent.clone())
+                .iter()
+                .any(|a| animal.id.contains(a))
+            {
+                errors.push(FieldError::new(
+                    field,
+                    format!("{parent} is a descendant of this animal"),
+                ));
+            }
+        }
+    }
+    if let Some(born) = animal.born {
+        if born.is_future() {
+            errors.push(FieldError::new("Born", "Birth date is in the future"));
+        }
+        if animal.died().is_some_and(|died| died < born) {
+            errors.push(FieldError::new("Born", "Born after death"));
+        }
+    }
+    for event in &animal.events {
+        errors.extend(check_animal_event(flok, event));
+    }
+    errors.extend(animal.scores.iter().flat_map(check_score));
+    errors.extend(treatment::check_sales(flok, animal, recorded));
+    errors
+}
+
+/// An event of an animal in `flok`, and the contact and pasture it names.
+fn check_animal_event(flok: &Flok, event: &Event) -> Vec<FieldError> {
+    let mut errors: Vec<FieldError> = check_event(event)
+        .into_iter()
+        .map(|e| FieldError::new("Events", format!("{}: {}", event.name, e.message)))
+        .collect();
+    if let Some(contact) = event.contact.as_ref().filter(|c| flok.contact(c).is_none()) {
+        errors.push(FieldError::new(
+            "Events",
+            format!("{}: {contact} is not a contact", event.name),
+        ));
+    }
+    if event.name.trim().eq_ignore_ascii_case(groups::MOVE_EVENT)
+        && groups::pasture(flok, &event.value).is_none()
+    {
+        errors.push(FieldError::new(
+            "Events",
+            format!("{}: {} is not a pasture", event.name, event.value),
+        ));
+    }
+    errors
+}
+
+/// Check `events` about to be added to the animal at `index`, and new sales among them against
+/// withdrawal. Problems the animal already has don't stop them, so one old record doesn't block
+/// a whole group.
+pub fn check_new_events(flok: &Flok, index: usize, events: &[Event]) -> Vec<FieldError> {
+    let recorded = &flok.animals[index];
+    let mut animal = recorded.clone();
+    animal.events.extend(events.iter().cloned());
+    events
+        .iter()
+        .flat_map(|e| check_animal_event(flok, e))
+        .chain(treatment::check_sales(flok, &animal, &recorded.events))
+        .collect()
+}
+
+/// Every problem in the flock, with the id of the animal it belongs to, and animals that left
+/// the farm without an official ID.
+pub fn check_flok(flok: &Flok) -> Vec<(Id, FieldError)> {
+    flok.animals
+        .iter()
+        .enumerate()
+        .flat_map(|(i, animal)| {
+            let id = animal.id.first().cloned().unwrap_or_default();
+            check_animal(flok, Some(i), animal)
+                .into_iter()
+                .chain(treatment::check_sales(flok, animal, &[]))
+                .map(move |e| (id.clone(), e))
+        })
+        .chain(regulatory::missing_ids(flok))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn animal(id: &str) -> Animal {
+        Animal {
+            id: vec![id.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duplicate_and_empty_ids() {
+        let flok = Flok {
+            animals: vec![animal("1"), animal("2")],
+            ..Default::default()
+        };
+        assert!(check_animal(&flok, Some(0), &flok.animals[0]).is_empty());
+        assert_eq!(
+            check_animal(&flok, None, &animal("2")),
+            vec![FieldError::new("Identity", "2 is used by another animal")]
+        );
+        assert_eq!(
+            check_animal(&flok, None, &animal(" ")),
+            vec![FieldError::new("Identity", "Empty ID")]
+        );
+    }
+
+    /// The event editor writes into the flock's animal before the animal form checks it.
+    #[test]
+    fn sale_edited_in_place() {
+        use crate::business_obj::BusinessObject;
+        use std::sync::{Arc, Mutex};
+
+        let event = |name: &str, date: &str| Event {
+            name: name.to_string(),
+            date: Date::parse(date).unwrap(),
+            ..Default::default()
+        };
+        let mut ewe = animal("1");
+        ewe.events = vec![
+            event("Valbazen", "2024-05-20"),
+            event("weaned", "2024-05-21"),
+        ];
+        let flok = Arc::new(Mutex::new(Flok {
+            animals: vec![ewe],
+            products: vec![Product {
+                name: "Valbazen".to_string(),
+                meat_withdrawal: 7,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }));
+        // as the flock table and animal form do
+        let ewe = flok.clone().map(|f| &mut f.animals[0]);
+        let recorded = ewe.exec(|a| a.events.clone());
+        // as the event form does when the second event is changed to a sale
+        ewe.clone()
+            .map(|a| &mut a.events[1])
+            .exec(|e| e.name = "sold".to_string());
+
+        let edited = ewe.exec(|a| a.clone());
+        let errors = check_edited_animal(&flok.lock().unwrap(), Some(0), &edited, &recorded);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("withdrawal period of Valbazen"));
+        // once saved, the sale isn't refused again
+        assert!(check_animal(&flok.lock().unwrap(), Some(0), &edited).is_empty());
+    }
+
+    #[test]
+    fn own_ancestor() {
+        let mut ewe = animal("1");
+        let mut lamb = animal("2");
+        lamb.dam = Some("1".to_string());
+        let flok = Flok {
+            animals: vec![ewe.clone(), lamb],
+            ..Default::default()
+        };
+        ewe.dam = Some("2".to_string());
+        assert_eq!(
+            check_animal(&flok, Some(0), &ewe),
+            vec![FieldError::new("Dam", "2 is a descendant of this animal")]
+        );
+        ewe.dam = Some("1".to_string());
+        assert_eq!(
+            check_animal(&flok, Some(0), &ewe),
+            vec![FieldError::new("Dam", "An animal cannot be its own dam")]
+        );
+    }
+
+    #[test]
+    fn scores() {
+        let score = |kind, value| Score {
+            kind,
+            value,
+            ..Default::default()
+        };
+        assert!(check_score(&score(ScoreKind::Bcs, 2.5)).is_empty());
+        assert!(check_score(&score(ScoreKind::Famacha, 5.0)).is_empty());
+        assert_eq!(
+            check_score(&score(ScoreKind::Famacha, 2.5)),
+            vec![FieldError::new(
+                "Scores",
+                "FAMACHA 2.5 is not a score from 1 to 5 in steps of 1"
+            )]
+        );
+        assert_eq!(check_score(&score(ScoreKind::Bcs, 0.5)).len(), 1);
+    }
+
+    #[test]
+    fn born_after_death() {
+        let mut a = animal("1");
+        a.born = Some(chrono::NaiveDate::from_ymd_opt(2024, 4, 2).unwrap().into());
+        a.events.push(Event {
+            name: "died".to_string(),
+            date: chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap().into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            check_animal(&Flok::default(), None, &a),
+            vec![FieldError::new("Born", "Born after death")]
+        );
+    }
+}